@@ -0,0 +1,72 @@
+//! Compares parsing a large program from a vector of owned tokens
+//! against parsing it straight from the borrowing `Lexer`.
+//!
+//! Run with `cargo bench`.
+extern crate qasm;
+
+use qasm::{lex, parse, parse_tokens, process, Lexer};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 10;
+const LINES: usize = 10_000;
+
+/// Builds a program of at least `LINES` lines by repeating the
+/// body of `bigadder.qasm` (everything after its register declarations).
+fn large_program() -> String {
+    let source = include_str!("../tests/source/bigadder.qasm");
+    let processed = process(source, Path::new("tests/source"));
+
+    let body_start = processed.find("qreg").unwrap();
+    let (header, body) = processed.split_at(body_start);
+    let (registers, operations): (Vec<&str>, Vec<&str>) = body
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .partition(|l| l.starts_with("qreg") || l.starts_with("creg"));
+
+    let mut program = String::from(header);
+    for line in registers {
+        program.push_str(line);
+        program.push('\n');
+    }
+    while program.lines().count() < LINES {
+        for line in &operations {
+            program.push_str(line);
+            program.push('\n');
+        }
+    }
+    program
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f();
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let program = large_program();
+    println!(
+        "program: {} lines, {} bytes",
+        program.lines().count(),
+        program.len()
+    );
+
+    let owned = time(|| {
+        let tokens = lex(&program);
+        assert!(parse(&tokens).is_ok());
+    });
+    println!("lex + parse (owned tokens):   {:?}", owned);
+
+    let borrowed = time(|| {
+        assert!(parse_tokens(Lexer::new(&program)).is_ok());
+    });
+    println!("parse_tokens (borrowed lexer): {:?}", borrowed);
+
+    println!(
+        "speedup: {:.2}x",
+        owned.as_secs_f64() / borrowed.as_secs_f64()
+    );
+}
@@ -1,24 +1,24 @@
 extern crate qasm;
 
-use qasm::{lex, parse, process};
+use qasm::draw::{self, TextOptions};
+use qasm::parse_file;
 use std::path::Path;
 
 // Start a custom repl
 fn main() {
-    let input = include_str!("qft.qasm");
-    let cwd = Path::new(file!()).parent().unwrap();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/source/qft.qasm");
 
-    let processed = process(input, cwd);
-    let tokens = lex(&processed);
+    let diagram = parse_file(&path)
+        .and_then(|program| program.flatten())
+        .and_then(|circuit| draw::text(&circuit, &TextOptions::default()));
 
-    match parse(&tokens) {
-        Ok(ast) => {
-            println!("AST: {:?}", ast);
+    match diagram {
+        Ok(diagram) => {
+            println!("{}", diagram);
             println!("\x1b[32mAll Okay!\x1b[0m");
         }
         Err(e) => {
             println!("\x1b[31mGot an error: {}\x1b[0m", e);
-            println!("{:?}", tokens)
         }
     }
 }
@@ -3,6 +3,11 @@
 /// The nodes are representative of what operation should be done,
 /// please look at their documentation.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "::serialize::AstNodeRepr", into = "::serialize::AstNodeRepr")
+)]
 pub enum AstNode {
     /// Represents the initialization of a Quantum Register.
     /// The String is the identifier, and the integer is the number of qubits.
@@ -10,13 +15,20 @@ pub enum AstNode {
     /// Represents the initialization of a Classical Register.
     /// The String is the identifier, and the integer is the number of bits.
     CReg(String, i32),
-    /// Represents a barrier to a qubit / register
-    Barrier(Argument),
+    /// Represents a barrier to a list of qubits / registers
+    Barrier(Vec<Argument>),
     /// Represents reseting a qubit / register
     Reset(Argument),
     /// Representing measuremnt of a qubit/register to a bit/register
     Measure(Argument, Argument),
-    /// Represents application of a gate
+    /// Represents application of the built in `U` gate.
+    /// The argument is the qubit that the gate is applied to.
+    /// The parameters are theta, phi and lambda (mathematical expressions).
+    U(Argument, [String; 3]),
+    /// Represents application of the built in `CX` gate.
+    /// The arguments are the control and the target.
+    CX(Argument, Argument),
+    /// Represents application of a gate, other than `U` and `CX`
     /// String is the name of the gate.
     /// The first arguments is the qubits that the gates are being applied to
     /// The second is the parameters (mathematical expressions).
@@ -31,7 +43,8 @@ pub enum AstNode {
     /// String is the name of the gate
     /// The first is the qubits it acts on,
     /// The seconds is the ids of the params.
-    /// finally, a list of nodes, which the gate applies
+    /// finally, a list of nodes, which the gate applies.
+    /// These are `U`, `CX`, `ApplyGate` and `Barrier` nodes.
     Gate(String, Vec<String>, Vec<String>, Vec<AstNode>),
     /// Represents a conditional
     /// String is classical register
@@ -44,6 +57,11 @@ pub enum AstNode {
 /// These are never top level, thus they have been
 /// left to a seperate enum.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "::serialize::ArgumentRepr", into = "::serialize::ArgumentRepr")
+)]
 pub enum Argument {
     /// Represents a single qubit / bit argument.
     /// The string is the name of the register, and the integer is the index
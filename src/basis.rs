@@ -0,0 +1,508 @@
+//! Translation of circuits into the gates that a device supports.
+
+use ast::{Argument, AstNode};
+use circuit::{Circuit, Operation};
+use error::Error;
+use lexer::Lexer;
+use program::Gate;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::result;
+use strict;
+
+type Result<T> = result::Result<T, Error>;
+
+/// Gates with the same effect as the `qelib1.inc` gates of the same names,
+/// up to a global phase, besides their definitions in `qelib1.inc`.
+const RULES: &str = "
+gate sx a { sdg a; h a; sdg a; }
+gate sx a { rx(pi/2) a; }
+gate sx a { u3(pi/2,-pi/2,pi/2) a; }
+gate u3(theta,phi,lambda) a { rz(lambda) a; sx a; rz(theta+pi) a; sx a; rz(phi+pi) a; }
+gate u1(lambda) a { rz(lambda) a; }
+gate id a { }
+gate x a { rx(pi) a; }
+gate x a { sx a; sx a; }
+gate y a { rz(pi) a; x a; }
+gate z a { rz(pi) a; }
+gate h a { rz(pi/2) a; sx a; rz(pi/2) a; }
+gate h a { ry(pi/2) a; x a; }
+gate s a { rz(pi/2) a; }
+gate sdg a { rz(-pi/2) a; }
+gate t a { rz(pi/4) a; }
+gate tdg a { rz(-pi/4) a; }
+gate rx(theta) a { h a; rz(theta) a; h a; }
+gate ry(theta) a { sdg a; rx(theta) a; s a; }
+gate rz(phi) a { h a; rx(phi) a; h a; }
+gate cx a,b { h b; cz a,b; h b; }
+";
+
+/// Rules for translating gates into other gates, given as gate definitions.
+///
+/// A rule is a gate definition whose body has the same effect as the gate
+/// of its name, up to a global phase. There can be any number of rules for
+/// a gate, and they can be for opaque gates, or gates that a program
+/// defines another way.
+///
+/// These are used by [translate](fn.translate.html) along with rules that
+/// are built in for the gates of `qelib1.inc`.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::basis::Equivalences;
+///
+/// let mut equivalences = Equivalences::default();
+/// equivalences
+///     .add("gate iswap a,b { s a; s b; h a; cx a,b; cx b,a; h b; }")
+///     .unwrap();
+///
+/// assert!(equivalences.add("qreg q[2];").is_err());
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Equivalences {
+    /// The rules, with the ones added last first
+    rules: Vec<Gate>,
+}
+
+impl Equivalences {
+    /// Adds the rules in a source of gate definitions, without a version
+    /// header or includes.
+    ///
+    /// Rules that are added later are used over rules added before them and
+    /// built in rules, when they take as many gates. This returns an
+    /// `Unsupported` error if there are statements that aren't gate
+    /// definitions, and the errors of [parse_str_strict](../fn.parse_str_strict.html)
+    /// for bodies that use arguments or parameters that the rule doesn't
+    /// declare. Rules with other numbers of qubits or parameters than the
+    /// gate they're for, or that give them to the gates they use, are left
+    /// out when translating.
+    pub fn add(&mut self, source: &str) -> Result<()> {
+        let comment_regex = Regex::new(r"//.*").unwrap();
+        let source = format!("OPENQASM 2.0;\n{}", comment_regex.replace_all(source, ""));
+        let mut rules = gates(::parse_tokens(Lexer::new(&source))?)?;
+        for rule in &rules {
+            strict::check_body(&rule.qubits, &rule.params, &rule.body)?;
+        }
+        rules.append(&mut self.rules);
+        self.rules = rules;
+        Ok(())
+    }
+}
+
+/// The gate definitions in a list of AST nodes, or an `Unsupported` error if
+/// there are other nodes.
+fn gates(nodes: Vec<AstNode>) -> Result<Vec<Gate>> {
+    nodes
+        .into_iter()
+        .map(|node| match node {
+            AstNode::Gate(name, qubits, params, body) => Ok(Gate {
+                name,
+                qubits,
+                params,
+                body,
+            }),
+            _ => Err(Error::Unsupported(
+                "statements other than gate definitions in rules".to_string(),
+            )),
+        })
+        .collect()
+}
+
+/// Rewrites every gate in a circuit into the gates of a basis, such as
+/// `["rz", "sx", "x", "cx"]`, `["u1", "u2", "u3", "cx"]` or `["rx", "rz", "cz"]`.
+///
+/// Each gate that isn't in the basis is replaced by the body of a rule for
+/// it, and the gates in that body are replaced in turn, until only gates in
+/// the basis are left. The rules are the [Equivalences](struct.Equivalences.html),
+/// the definitions in the circuit, and rules that are built in for the
+/// gates of `qelib1.inc`, which are used when the circuit defines the gates
+/// they use as `qelib1.inc` does. Of the rules for a gate, the one that ends
+/// in the fewest gates in the basis is used. `U` and `CX` are `u3` and `cx`,
+/// and can be in the basis too.
+///
+/// The parameters are substituted into the expressions of the rules, and
+/// the conditions of the gates are kept. Definitions are added for the gates
+/// of the basis that are used but not defined, from `qelib1.inc` or the
+/// rules. This returns an `Unsupported` error with the name of a gate that
+/// can't be translated into the basis.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::basis::{self, Equivalences};
+/// use qasm::Operation;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// opaque iswap a,b;
+/// qreg q[2];
+/// h q[0];
+/// cx q[0], q[1];
+/// iswap q[0], q[1];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let mut equivalences = Equivalences::default();
+/// equivalences
+///     .add("gate iswap a,b { s a; s b; h a; cx a,b; cx b,a; h b; }")
+///     .unwrap();
+///
+/// let translated = basis::translate(&circuit, &["rz", "sx", "x", "cx"], &equivalences).unwrap();
+/// for instruction in &translated.instructions {
+///     if let Operation::Gate(ref name, _, _) = instruction.operation {
+///         assert!(["rz", "sx", "x", "cx"].contains(&name.as_str()));
+///     }
+/// }
+/// assert!(translated.gate("sx").is_some());
+///
+/// assert!(basis::translate(&circuit, &["rz", "sx", "x", "cx"], &Equivalences::default()).is_err());
+/// ```
+pub fn translate(
+    circuit: &Circuit,
+    basis: &[&str],
+    equivalences: &Equivalences,
+) -> Result<Circuit> {
+    let (qelib1, built_in) = built_in_rules(circuit);
+    let signatures = signatures(circuit);
+    let added: Vec<Gate> = equivalences
+        .rules
+        .iter()
+        .filter(|rule| fits(rule, &signatures))
+        .cloned()
+        .collect();
+    let rules: Vec<Gate> = added
+        .iter()
+        .chain(&circuit.gates)
+        .chain(&qelib1)
+        .chain(&built_in)
+        .cloned()
+        .collect();
+
+    let translator = Circuit {
+        gates: choose(&rules, basis),
+        ..circuit.clone()
+    };
+    let mut translated = translator.decompose(&|name| basis.contains(&name))?;
+
+    let mut used = Vec::new();
+    for instruction in &translated.instructions {
+        if let Operation::Gate(ref name, _, _) = instruction.operation {
+            if !basis.contains(&name.as_str()) {
+                return Err(Error::Unsupported(name.clone()));
+            }
+            if !used.contains(name) {
+                used.push(name.clone());
+            }
+        }
+    }
+
+    // Definitions come from qelib1.inc before the other rules
+    let definitions: Vec<Gate> = qelib1.into_iter().chain(added).chain(built_in).collect();
+    translated.gates = circuit.gates.clone();
+    for name in &used {
+        define(name, &definitions, &mut translated, &mut Vec::new())?;
+    }
+    Ok(translated)
+}
+
+/// The definitions of `qelib1.inc` and the other built in rules, without
+/// those that use gates the circuit defines another way.
+fn built_in_rules(circuit: &Circuit) -> (Vec<Gate>, Vec<Gate>) {
+    let standard = circuit.standard_gates();
+    let other: HashSet<&str> = circuit
+        .gates
+        .iter()
+        .map(|g| g.name.as_str())
+        .chain(circuit.opaques.iter().map(|g| g.name.as_str()))
+        .filter(|name| !standard.contains(name))
+        .collect();
+    let applies = |rule: &Gate| {
+        !other.contains(rule.name.as_str())
+            && !rule
+                .body
+                .iter()
+                .filter_map(gate)
+                .any(|name| other.contains(name))
+    };
+
+    let qelib1 = gates(::qelib1_gates()).expect("qelib1.inc only defines gates");
+    let source = format!("OPENQASM 2.0;\n{}", RULES);
+    let mut rules = gates(::parse_tokens(Lexer::new(&source)).expect("the rules are valid"))
+        .expect("the rules are gate definitions");
+
+    // U and CX are u3 and cx, which can't be written as gate definitions
+    let register = |name: &str| Argument::Register(name.to_string());
+    let params: Vec<String> = vec!["theta".to_string(), "phi".to_string(), "lambda".to_string()];
+    rules.push(Gate {
+        name: "U".to_string(),
+        qubits: vec!["a".to_string()],
+        params: params.clone(),
+        body: vec![AstNode::ApplyGate(
+            "u3".to_string(),
+            vec![register("a")],
+            params,
+        )],
+    });
+    rules.push(Gate {
+        name: "CX".to_string(),
+        qubits: vec!["a".to_string(), "b".to_string()],
+        params: Vec::new(),
+        body: vec![AstNode::ApplyGate(
+            "cx".to_string(),
+            vec![register("a"), register("b")],
+            Vec::new(),
+        )],
+    });
+
+    (
+        qelib1.into_iter().filter(&applies).collect(),
+        rules.into_iter().filter(&applies).collect(),
+    )
+}
+
+/// The number of qubits and parameters of the gates that a circuit declares,
+/// and of `U`, `CX` and the other gates of `qelib1.inc`.
+fn signatures(circuit: &Circuit) -> HashMap<&str, (usize, usize)> {
+    let mut signatures: HashMap<&str, (usize, usize)> = ::qelib1()
+        .gates()
+        .iter()
+        .map(|g| (g.name.as_str(), (g.qubits.len(), g.params.len())))
+        .collect();
+    signatures.insert("U", (1, 3));
+    signatures.insert("CX", (2, 0));
+    for gate in &circuit.gates {
+        signatures.insert(&gate.name, (gate.qubits.len(), gate.params.len()));
+    }
+    for opaque in &circuit.opaques {
+        signatures.insert(&opaque.name, (opaque.qubits.len(), opaque.params.len()));
+    }
+    signatures
+}
+
+/// Whether a rule has the number of qubits and parameters of the gate it
+/// replaces, and applies the gates it uses with theirs, where they are known.
+fn fits(rule: &Gate, signatures: &HashMap<&str, (usize, usize)>) -> bool {
+    let matches = |name: &str, qubits: usize, params: usize| {
+        signatures
+            .get(name)
+            .is_none_or(|&signature| signature == (qubits, params))
+    };
+
+    matches(&rule.name, rule.qubits.len(), rule.params.len())
+        && rule.body.iter().all(|node| match *node {
+            AstNode::ApplyGate(ref id, ref args, ref exprs) => matches(id, args.len(), exprs.len()),
+            _ => true,
+        })
+}
+
+/// The name of the gate that a node of a gate body applies.
+fn gate(node: &AstNode) -> Option<&str> {
+    match *node {
+        AstNode::U(..) => Some("U"),
+        AstNode::CX(..) => Some("CX"),
+        AstNode::ApplyGate(ref name, ..) => Some(name),
+        _ => None,
+    }
+}
+
+/// The rule to use for each gate that isn't in the basis and can be
+/// translated into it: the one that ends in the fewest gates of the basis,
+/// or the first of those.
+fn choose(rules: &[Gate], basis: &[&str]) -> Vec<Gate> {
+    // The number of gates of the basis that each gate ends in, and the rule
+    let mut costs: HashMap<&str, (usize, Option<usize>)> =
+        basis.iter().map(|&name| (name, (1, None))).collect();
+    loop {
+        let mut changed = false;
+        for (i, rule) in rules.iter().enumerate() {
+            if basis.contains(&rule.name.as_str()) {
+                continue;
+            }
+            let cost: Option<usize> = rule
+                .body
+                .iter()
+                .filter_map(gate)
+                .map(|name| costs.get(name).map(|&(cost, _)| cost))
+                .sum();
+            if let Some(cost) = cost {
+                if costs
+                    .get(rule.name.as_str())
+                    .is_none_or(|&(best, _)| cost < best)
+                {
+                    costs.insert(&rule.name, (cost, Some(i)));
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut chosen: Vec<usize> = costs.values().filter_map(|&(_, rule)| rule).collect();
+    chosen.sort_unstable();
+    chosen.into_iter().map(|i| rules[i].clone()).collect()
+}
+
+/// Adds a definition of a gate to a circuit that doesn't declare it, after
+/// definitions of the gates it uses. `defining` are the gates whose
+/// definitions are being added, which can't be used.
+fn define<'a>(
+    name: &'a str,
+    definitions: &'a [Gate],
+    circuit: &mut Circuit,
+    defining: &mut Vec<&'a str>,
+) -> Result<()> {
+    if name == "U" || name == "CX" || circuit.gate(name).is_some() || circuit.opaque(name).is_some()
+    {
+        return Ok(());
+    }
+    let definition = match definitions.iter().find(|g| g.name == name) {
+        Some(definition) if !defining.contains(&name) => definition,
+        _ => return Err(Error::UndefinedGate(name.to_string())),
+    };
+
+    defining.push(name);
+    for used in definition.body.iter().filter_map(gate) {
+        define(used, definitions, circuit, defining)?;
+    }
+    defining.pop();
+    circuit.gates.push(definition.clone());
+    Ok(())
+}
+
+#[test]
+fn translate_test() {
+    use circuit::tests::flatten;
+    use optimize::unitary::{equivalent, unitary};
+
+    let source = "OPENQASM 2.0;
+        include \"tests/source/qelib1.inc\";
+        opaque iswap a,b;
+        gate bell a,b { h a; cx a,b; }
+        qreg q[3];
+        creg c[1];
+        U(0.1, 0.2, 0.3) q[0];
+        CX q[0], q[1];
+        u3(0.4, 0.5, 0.6) q[1];
+        u2(0.7, 0.8) q[2];
+        u1(0.9) q[0];
+        id q[1];
+        x q[0];
+        y q[1];
+        z q[2];
+        h q[0];
+        s q[1];
+        sdg q[2];
+        t q[0];
+        tdg q[1];
+        rx(1.1) q[2];
+        ry(1.2) q[0];
+        rz(1.3) q[1];
+        cz q[0], q[2];
+        cy q[1], q[0];
+        ch q[2], q[1];
+        ccx q[0], q[1], q[2];
+        crz(1.4) q[1], q[2];
+        cu1(1.5) q[2], q[0];
+        cu3(1.6, 1.7, 1.8) q[0], q[1];
+        bell q[1], q[2];
+        iswap q[0], q[2];
+        measure q[0] -> c[0];
+        if(c==1) h q[1];";
+    let circuit = flatten(source);
+    let mut equivalences = Equivalences::default();
+    equivalences
+        .add("gate iswap a,b { s a; s b; h a; cx a,b; cx b,a; h b; }")
+        .unwrap();
+    let with_iswap = |circuit: &Circuit| Circuit {
+        opaques: Vec::new(),
+        gates: circuit
+            .gates
+            .iter()
+            .cloned()
+            .chain(equivalences.rules.iter().cloned())
+            .collect(),
+        ..circuit.clone()
+    };
+
+    let bases: [&[&str]; 5] = [
+        &["rz", "sx", "x", "cx"],
+        &["u1", "u2", "u3", "cx"],
+        &["rx", "rz", "cz"],
+        &["U", "CX"],
+        &["h", "t", "tdg", "cx", "u3"],
+    ];
+    for basis in &bases {
+        let translated = translate(&circuit, basis, &equivalences).unwrap();
+        for instruction in &translated.instructions {
+            if let Operation::Gate(ref name, _, _) = instruction.operation {
+                assert!(basis.contains(&name.as_str()), "{} in {:?}", name, basis);
+            }
+        }
+        assert!(equivalent(
+            &unitary(&with_iswap(&circuit)),
+            &unitary(&with_iswap(&translated))
+        ));
+
+        // The conditional h and the measurement are still there
+        let last = translated.instructions.last().unwrap();
+        assert!(last.condition.is_some());
+        assert!(translated
+            .instructions
+            .iter()
+            .any(|i| i.operation == Operation::Measure(0, 0)));
+
+        let reparsed = ::Program::from_ast(translated.to_ast())
+            .and_then(|p| p.flatten())
+            .unwrap();
+        assert_eq!(reparsed.instructions.len(), translated.instructions.len());
+    }
+
+    // Without a rule, the opaque gate can't be translated
+    assert_eq!(
+        translate(&circuit, &["rz", "sx", "x", "cx"], &Equivalences::default()),
+        Err(Error::Unsupported("iswap".to_string()))
+    );
+    // Rules added later are used over the ones before them
+    let mut equivalences = Equivalences::default();
+    equivalences.add("gate t a { u1(pi/4) a; }").unwrap();
+    equivalences.add("gate t a { rz(pi/4) a; }").unwrap();
+    assert_eq!(equivalences.rules[0].body.len(), 1);
+    assert!(matches!(
+        equivalences.rules[0].body[0],
+        AstNode::ApplyGate(ref name, ..) if name == "rz"
+    ));
+    // Rules must only use their own arguments and parameters
+    assert_eq!(
+        equivalences.add("gate iswap a,b { cx a,c; }"),
+        Err(Error::UndefinedGateArgument("c".to_string()))
+    );
+    assert_eq!(
+        equivalences.add("gate iswap a,b { rz(undefined) a; cx a,b; }"),
+        Err(Error::UndefinedParameter("undefined".to_string()))
+    );
+    assert_eq!(
+        equivalences.add("gate iswap a,b { cx a,a; }"),
+        Err(Error::DuplicateArgument("a".to_string()))
+    );
+
+    // Rules that don't match the gates they replace or use are left out
+    for rule in &[
+        "gate iswap(t) a,b { rz(t) a; cx a,b; }",
+        "gate iswap a,b { cx a; }",
+        "gate iswap a,b { rz a; cx a,b; }",
+    ] {
+        let mut equivalences = Equivalences::default();
+        equivalences.add(rule).unwrap();
+        assert_eq!(
+            translate(&circuit, &["rz", "sx", "x", "cx"], &equivalences),
+            Err(Error::Unsupported("iswap".to_string())),
+            "{}",
+            rule
+        );
+    }
+}
@@ -0,0 +1,47 @@
+extern crate qasm;
+
+use qasm::{Estimate, Stats};
+use std::env;
+use std::process;
+
+// Print the resource usage of each file given, and an estimate of its
+// fault-tolerant cost when it has no opaque gates
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: stats <file.qasm>...");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for (i, path) in paths.iter().enumerate() {
+        if paths.len() > 1 {
+            if i > 0 {
+                println!();
+            }
+            println!("{}:", path);
+        }
+        let result = qasm::parse_file(path)
+            .and_then(|program| program.flatten())
+            .and_then(|circuit| {
+                let stats = Stats::from_circuit(&circuit)?;
+                Ok((stats, Estimate::from_circuit(&circuit).ok()))
+            });
+        match result {
+            Ok((stats, estimate)) => {
+                print!("{}", stats);
+                if let Some(estimate) = estimate {
+                    println!();
+                    print!("{}", estimate);
+                }
+            }
+            Err(e) => {
+                println!("\x1b[31mGot an error: {}\x1b[0m", e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,717 @@
+//! A program with its registers flattened, so that every operation
+//! acts on global qubit and bit indices.
+
+use ast::{Argument, AstNode};
+use error::Error;
+use expr::Expr;
+use matrix::Matrix;
+use program::{Gate, Opaque, Program, Register};
+use std::collections::{HashMap, HashSet};
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// An operation on qubits and bits, given by their global indices.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operation {
+    /// Applies a gate, with its name, parameters and qubits.
+    /// The built in gates are named `U` and `CX`.
+    Gate(String, Vec<Expr>, Vec<usize>),
+    /// Measures a qubit into a bit.
+    Measure(usize, usize),
+    /// Resets a qubit.
+    Reset(usize),
+    /// A barrier to a list of qubits.
+    Barrier(Vec<usize>),
+}
+
+/// The condition of an `if` statement: the classical register must equal the value.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition {
+    pub register: Register,
+    pub value: i32,
+}
+
+/// An operation, and the condition it is run under.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Instruction {
+    pub operation: Operation,
+    pub condition: Option<Condition>,
+}
+
+/// A program with its registers flattened.
+///
+/// Statements that are broadcast over whole registers are expanded into one
+/// instruction for each qubit, and every qubit and bit is given by its global
+/// index, as laid out by the [Program](struct.Program.html). The declarations
+/// are kept, so that gates can still be looked up by name.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{Operation, Program};
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// qreg a[1];
+/// qreg b[2];
+/// creg c[2];
+/// CX a[0], b;
+/// measure b -> c;
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+///
+/// assert_eq!(circuit.instructions.len(), 4);
+/// assert_eq!(
+///     circuit.instructions[1].operation,
+///     Operation::Gate("CX".to_string(), vec![], vec![0, 2])
+/// );
+/// assert_eq!(circuit.instructions[3].operation, Operation::Measure(2, 1));
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Circuit {
+    pub qregs: Vec<Register>,
+    pub cregs: Vec<Register>,
+    pub gates: Vec<Gate>,
+    pub opaques: Vec<Opaque>,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Circuit {
+    /// Flattens a program. The program is checked first, and any error is returned.
+    pub fn from_program(program: &Program) -> Result<Circuit> {
+        program.check()?;
+
+        let mut circuit = Circuit {
+            qregs: program.qregs().to_vec(),
+            cregs: program.cregs().to_vec(),
+            gates: program.gates().to_vec(),
+            opaques: program.opaques().to_vec(),
+            instructions: Vec::new(),
+        };
+        for node in program.statements() {
+            circuit.push(program, node, None)?;
+        }
+
+        Ok(circuit)
+    }
+
+    /// The total number of qubits in all quantum registers.
+    pub fn num_qubits(&self) -> usize {
+        self.qregs.last().map_or(0, |r| r.offset + r.size)
+    }
+
+    /// The total number of bits in all classical registers.
+    pub fn num_clbits(&self) -> usize {
+        self.cregs.last().map_or(0, |r| r.offset + r.size)
+    }
+
+    /// Finds a gate definition by name.
+    pub fn gate(&self, name: &str) -> Option<&Gate> {
+        self.gates.iter().find(|g| g.name == name)
+    }
+
+    /// Finds an opaque gate declaration by name.
+    pub fn opaque(&self, name: &str) -> Option<&Opaque> {
+        self.opaques.iter().find(|g| g.name == name)
+    }
+
+    /// The names of the gates that are defined as in `qelib1.inc`.
+    pub(crate) fn standard_gates(&self) -> HashSet<&str> {
+        let qelib1 = ::qelib1();
+        self.gates
+            .iter()
+            .filter(|g| qelib1.gate(&g.name) == Some(*g))
+            .map(|g| g.name.as_str())
+            .collect()
+    }
+
+    /// Converts the circuit back into AST nodes: the opaque gates, gate
+    /// definitions and registers, followed by a statement for each instruction,
+    /// on single qubits and bits of the registers.
+    pub fn to_ast(&self) -> Vec<AstNode> {
+        let mut nodes: Vec<AstNode> = self
+            .opaques
+            .iter()
+            .map(|g| {
+                AstNode::Opaque(
+                    g.name.clone(),
+                    g.qubits.iter().cloned().map(Argument::Register).collect(),
+                    g.params.clone(),
+                )
+            })
+            .collect();
+        nodes.extend(self.gates.iter().map(|g| {
+            AstNode::Gate(
+                g.name.clone(),
+                g.qubits.clone(),
+                g.params.clone(),
+                g.body.clone(),
+            )
+        }));
+        nodes.extend(
+            self.qregs
+                .iter()
+                .map(|r| AstNode::QReg(r.name.clone(), r.size as i32)),
+        );
+        nodes.extend(
+            self.cregs
+                .iter()
+                .map(|r| AstNode::CReg(r.name.clone(), r.size as i32)),
+        );
+
+        let qubit = |index: usize| argument(&self.qregs, index);
+        let bit = |index: usize| argument(&self.cregs, index);
+        for instruction in &self.instructions {
+            let node = match instruction.operation {
+                Operation::Gate(ref name, ref params, ref qubits) => {
+                    let params: Vec<_> = params.iter().map(|p| p.to_string()).collect();
+                    match name.as_str() {
+                        "U" if params.len() == 3 && qubits.len() == 1 => AstNode::U(
+                            qubit(qubits[0]),
+                            [params[0].clone(), params[1].clone(), params[2].clone()],
+                        ),
+                        "CX" if params.is_empty() && qubits.len() == 2 => {
+                            AstNode::CX(qubit(qubits[0]), qubit(qubits[1]))
+                        }
+                        _ => AstNode::ApplyGate(
+                            name.clone(),
+                            qubits.iter().map(|&q| qubit(q)).collect(),
+                            params,
+                        ),
+                    }
+                }
+                Operation::Measure(q, b) => AstNode::Measure(qubit(q), bit(b)),
+                Operation::Reset(q) => AstNode::Reset(qubit(q)),
+                Operation::Barrier(ref qubits) => {
+                    AstNode::Barrier(qubits.iter().map(|&q| qubit(q)).collect())
+                }
+            };
+            nodes.push(match instruction.condition {
+                Some(ref condition) => AstNode::If(
+                    condition.register.name.clone(),
+                    condition.value,
+                    Box::new(node),
+                ),
+                None => node,
+            });
+        }
+        nodes
+    }
+
+    fn push(
+        &mut self,
+        program: &Program,
+        node: &AstNode,
+        condition: Option<Condition>,
+    ) -> Result<()> {
+        let qubit = |arg: &Argument, i: usize| index(program.qreg(name(arg)), arg, i);
+        let bit = |arg: &Argument, i: usize| index(program.creg(name(arg)), arg, i);
+        // The number of times a statement is applied, from the size of its registers
+        let times = |args: &[&Argument]| {
+            args.iter()
+                .filter_map(|arg| match **arg {
+                    Argument::Register(ref id) => program.qreg(id).or_else(|| program.creg(id)),
+                    Argument::Qubit(..) => None,
+                })
+                .map(|r| r.size)
+                .next()
+                .unwrap_or(1)
+        };
+
+        let operations: Vec<Operation> = match *node {
+            AstNode::If(ref id, value, ref node) => {
+                let condition = Condition {
+                    register: program
+                        .creg(id)
+                        .cloned()
+                        .ok_or_else(|| Error::UndefinedCReg(id.clone()))?,
+                    value,
+                };
+                return self.push(program, node, Some(condition));
+            }
+            AstNode::Barrier(ref args) => {
+                let mut qubits = Vec::new();
+                for arg in args {
+                    qubits.extend((0..times(&[arg])).map(|i| qubit(arg, i)));
+                }
+                vec![Operation::Barrier(qubits)]
+            }
+            AstNode::Reset(ref arg) => (0..times(&[arg]))
+                .map(|i| Operation::Reset(qubit(arg, i)))
+                .collect(),
+            AstNode::Measure(ref q, ref c) => (0..times(&[q, c]))
+                .map(|i| Operation::Measure(qubit(q, i), bit(c, i)))
+                .collect(),
+            AstNode::U(ref arg, ref params) => {
+                let params = params
+                    .iter()
+                    .map(|p| Expr::parse(p))
+                    .collect::<Result<Vec<_>>>()?;
+                (0..times(&[arg]))
+                    .map(|i| Operation::Gate("U".to_string(), params.clone(), vec![qubit(arg, i)]))
+                    .collect()
+            }
+            AstNode::CX(ref control, ref target) => (0..times(&[control, target]))
+                .map(|i| {
+                    Operation::Gate(
+                        "CX".to_string(),
+                        vec![],
+                        vec![qubit(control, i), qubit(target, i)],
+                    )
+                })
+                .collect(),
+            AstNode::ApplyGate(ref id, ref args, ref params) => {
+                let params = params
+                    .iter()
+                    .map(|p| Expr::parse(p))
+                    .collect::<Result<Vec<_>>>()?;
+                let refs: Vec<_> = args.iter().collect();
+                (0..times(&refs))
+                    .map(|i| {
+                        let qubits = args.iter().map(|arg| qubit(arg, i)).collect();
+                        Operation::Gate(id.clone(), params.clone(), qubits)
+                    })
+                    .collect()
+            }
+            AstNode::QReg(ref id, _)
+            | AstNode::CReg(ref id, _)
+            | AstNode::Gate(ref id, ..)
+            | AstNode::Opaque(ref id, ..) => return Err(Error::Redefinition(id.clone())),
+        };
+
+        self.instructions
+            .extend(operations.into_iter().map(|operation| Instruction {
+                operation,
+                condition: condition.clone(),
+            }));
+        Ok(())
+    }
+
+    /// The unitary matrix of a gate, given the values of its parameters.
+    ///
+    /// The first qubit of the gate is the most significant bit of the matrix's
+    /// indices. This returns an `Unsupported` error for opaque gates.
+    pub(crate) fn unitary(&self, name: &str, params: &[f64]) -> Result<Matrix> {
+        self.gate_unitary(name, params, &mut Vec::new())
+    }
+
+    /// The unitary matrix of a gate, where `expanding` are the gates whose
+    /// matrices are being found, which a gate can't use.
+    fn gate_unitary<'a>(
+        &'a self,
+        name: &str,
+        params: &[f64],
+        expanding: &mut Vec<&'a str>,
+    ) -> Result<Matrix> {
+        match name {
+            "U" if params.len() == 3 => return Ok(Matrix::u(params[0], params[1], params[2])),
+            "U" => return Err(Error::WrongParameterCount(name.to_string())),
+            "CX" => return Ok(Matrix::cx()),
+            _ => {}
+        }
+
+        let gate = match self.gate(name) {
+            Some(gate) if !expanding.contains(&name) => gate,
+            None if self.opaque(name).is_some() => {
+                return Err(Error::Unsupported(format!("opaque {}", name)))
+            }
+            _ => return Err(Error::UndefinedGate(name.to_string())),
+        };
+        if gate.params.len() != params.len() {
+            return Err(Error::WrongParameterCount(name.to_string()));
+        }
+        let values: HashMap<String, f64> = gate
+            .params
+            .iter()
+            .cloned()
+            .zip(params.iter().cloned())
+            .collect();
+        let qubit = |arg: &Argument| match *arg {
+            Argument::Register(ref id) => gate
+                .qubits
+                .iter()
+                .position(|q| q == id)
+                .ok_or_else(|| Error::UndefinedGateArgument(id.clone())),
+            Argument::Qubit(ref id, _) => Err(Error::IndexedGateArgument(id.clone())),
+        };
+        let eval = |exprs: &[String]| {
+            exprs
+                .iter()
+                .map(|e| Expr::parse(e)?.eval_with(&values))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        let n = gate.qubits.len();
+        let mut unitary = Matrix::identity(1 << n);
+        expanding.push(&gate.name);
+        for node in &gate.body {
+            let (matrix, qubits) = match *node {
+                AstNode::U(ref arg, ref exprs) => {
+                    (self.unitary("U", &eval(exprs)?)?, vec![qubit(arg)?])
+                }
+                AstNode::CX(ref control, ref target) => {
+                    (Matrix::cx(), vec![qubit(control)?, qubit(target)?])
+                }
+                AstNode::ApplyGate(ref id, ref args, ref exprs) => (
+                    self.gate_unitary(id, &eval(exprs)?, expanding)?,
+                    args.iter().map(&qubit).collect::<Result<Vec<_>>>()?,
+                ),
+                _ => continue,
+            };
+            unitary = matrix.embed(&qubits, n).mul(&unitary);
+        }
+        expanding.pop();
+
+        Ok(unitary)
+    }
+
+    /// Replaces a gate with the gates in its definition, recursively, until
+    /// `keep` accepts them, then calls `apply` with each gate's name, the
+    /// values of its parameters, and its global qubits.
+    ///
+    /// `U`, `CX` and opaque gates are always kept, and barriers in definitions
+    /// are left out. This returns an `InvalidExpression` error if a parameter
+    /// isn't a finite number.
+    pub(crate) fn expand<K, F>(
+        &self,
+        name: &str,
+        params: &[Expr],
+        qubits: &[usize],
+        keep: &K,
+        apply: &mut F,
+    ) -> Result<()>
+    where
+        K: Fn(&str) -> bool,
+        F: FnMut(&str, &[f64], &[usize]) -> Result<()>,
+    {
+        let params = params
+            .iter()
+            .map(|p| finite(p, &HashMap::new()))
+            .collect::<Result<Vec<_>>>()?;
+        self.expand_values(name, &params, qubits, keep, apply, &mut Vec::new())
+    }
+
+    /// The circuit with every gate replaced by the gates in its definition,
+    /// as [expand](#method.expand) does, with the values of the parameters.
+    pub(crate) fn inline<K>(&self, keep: &K) -> Result<Circuit>
+    where
+        K: Fn(&str) -> bool,
+    {
+        let mut inlined = Circuit {
+            instructions: Vec::new(),
+            ..self.clone()
+        };
+        for instruction in &self.instructions {
+            match instruction.operation {
+                Operation::Gate(ref name, ref params, ref qubits) => {
+                    self.expand(name, params, qubits, keep, &mut |name, params, qubits| {
+                        inlined.instructions.push(Instruction {
+                            operation: Operation::Gate(
+                                name.to_string(),
+                                params.iter().map(|&p| Expr::Real(p)).collect(),
+                                qubits.to_vec(),
+                            ),
+                            condition: instruction.condition.clone(),
+                        });
+                        Ok(())
+                    })?
+                }
+                _ => inlined.instructions.push(instruction.clone()),
+            }
+        }
+        Ok(inlined)
+    }
+
+    /// The circuit with every gate replaced by the gates in its definition,
+    /// as [inline](#method.inline) does, but with the parameters substituted
+    /// into the expressions of the definitions rather than evaluated.
+    ///
+    /// `U` and `CX` are replaced too, when there are gates named `U` or `CX`.
+    pub(crate) fn decompose<K>(&self, keep: &K) -> Result<Circuit>
+    where
+        K: Fn(&str) -> bool,
+    {
+        let mut decomposed = Circuit {
+            instructions: Vec::new(),
+            ..self.clone()
+        };
+        for instruction in &self.instructions {
+            match instruction.operation {
+                Operation::Gate(ref name, ref params, ref qubits) => {
+                    let mut operations = Vec::new();
+                    self.decompose_gate(
+                        name,
+                        params,
+                        qubits,
+                        keep,
+                        &mut operations,
+                        &mut Vec::new(),
+                    )?;
+                    decomposed
+                        .instructions
+                        .extend(operations.into_iter().map(|operation| Instruction {
+                            operation,
+                            condition: instruction.condition.clone(),
+                        }));
+                }
+                _ => decomposed.instructions.push(instruction.clone()),
+            }
+        }
+        Ok(decomposed)
+    }
+
+    /// Decomposes a gate like [decompose](#method.decompose), where `expanding`
+    /// are the gates being decomposed, which a gate can't use.
+    fn decompose_gate<'a, K>(
+        &'a self,
+        name: &str,
+        params: &[Expr],
+        qubits: &[usize],
+        keep: &K,
+        operations: &mut Vec<Operation>,
+        expanding: &mut Vec<&'a str>,
+    ) -> Result<()>
+    where
+        K: Fn(&str) -> bool,
+    {
+        let gate = match self.gate(name) {
+            Some(_) if expanding.contains(&name) => {
+                return Err(Error::UndefinedGate(name.to_string()))
+            }
+            Some(gate) if !keep(name) => gate,
+            _ => {
+                operations.push(Operation::Gate(
+                    name.to_string(),
+                    params.to_vec(),
+                    qubits.to_vec(),
+                ));
+                return Ok(());
+            }
+        };
+
+        let values: HashMap<String, Expr> = gate
+            .params
+            .iter()
+            .cloned()
+            .zip(params.iter().cloned())
+            .collect();
+        let qubit = |arg: &Argument| match *arg {
+            Argument::Register(ref id) => gate
+                .qubits
+                .iter()
+                .position(|q| q == id)
+                .map(|i| qubits[i])
+                .ok_or_else(|| Error::UndefinedGateArgument(id.clone())),
+            Argument::Qubit(ref id, _) => Err(Error::IndexedGateArgument(id.clone())),
+        };
+        let substitute = |exprs: &[String]| {
+            exprs
+                .iter()
+                .map(|e| Ok(Expr::parse(e)?.substitute(&values)))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        expanding.push(&gate.name);
+        for node in &gate.body {
+            match *node {
+                AstNode::U(ref arg, ref exprs) => self.decompose_gate(
+                    "U",
+                    &substitute(exprs)?,
+                    &[qubit(arg)?],
+                    keep,
+                    operations,
+                    expanding,
+                )?,
+                AstNode::CX(ref control, ref target) => self.decompose_gate(
+                    "CX",
+                    &[],
+                    &[qubit(control)?, qubit(target)?],
+                    keep,
+                    operations,
+                    expanding,
+                )?,
+                AstNode::ApplyGate(ref id, ref args, ref exprs) => {
+                    let args = args.iter().map(&qubit).collect::<Result<Vec<_>>>()?;
+                    self.decompose_gate(
+                        id,
+                        &substitute(exprs)?,
+                        &args,
+                        keep,
+                        operations,
+                        expanding,
+                    )?
+                }
+                _ => {}
+            }
+        }
+        expanding.pop();
+        Ok(())
+    }
+
+    /// Expands a gate like [expand](#method.expand), where `expanding` are
+    /// the gates being expanded, which a gate can't use.
+    fn expand_values<'a, K, F>(
+        &'a self,
+        name: &str,
+        params: &[f64],
+        qubits: &[usize],
+        keep: &K,
+        apply: &mut F,
+        expanding: &mut Vec<&'a str>,
+    ) -> Result<()>
+    where
+        K: Fn(&str) -> bool,
+        F: FnMut(&str, &[f64], &[usize]) -> Result<()>,
+    {
+        let gate = match self.gate(name) {
+            Some(_) if expanding.contains(&name) => {
+                return Err(Error::UndefinedGate(name.to_string()))
+            }
+            Some(gate) if !keep(name) => gate,
+            _ => return apply(name, params, qubits),
+        };
+
+        let values: HashMap<String, f64> = gate
+            .params
+            .iter()
+            .cloned()
+            .zip(params.iter().cloned())
+            .collect();
+        let qubit = |arg: &Argument| match *arg {
+            Argument::Register(ref id) => gate
+                .qubits
+                .iter()
+                .position(|q| q == id)
+                .map(|i| qubits[i])
+                .ok_or_else(|| Error::UndefinedGateArgument(id.clone())),
+            Argument::Qubit(ref id, _) => Err(Error::IndexedGateArgument(id.clone())),
+        };
+        let eval = |exprs: &[String]| {
+            exprs
+                .iter()
+                .map(|e| finite(&Expr::parse(e)?, &values))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        expanding.push(&gate.name);
+        for node in &gate.body {
+            match *node {
+                AstNode::U(ref arg, ref exprs) => {
+                    self.expand_values("U", &eval(exprs)?, &[qubit(arg)?], keep, apply, expanding)?
+                }
+                AstNode::CX(ref control, ref target) => {
+                    apply("CX", &[], &[qubit(control)?, qubit(target)?])?
+                }
+                AstNode::ApplyGate(ref id, ref args, ref exprs) => {
+                    let args = args.iter().map(&qubit).collect::<Result<Vec<_>>>()?;
+                    self.expand_values(id, &eval(exprs)?, &args, keep, apply, expanding)?
+                }
+                _ => {}
+            }
+        }
+        expanding.pop();
+        Ok(())
+    }
+}
+
+/// Evaluates a parameter, which must be a finite number.
+fn finite(expr: &Expr, values: &HashMap<String, f64>) -> Result<f64> {
+    let value = expr.eval_with(values)?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(Error::InvalidExpression(expr.to_string()))
+    }
+}
+
+fn name(arg: &Argument) -> &str {
+    match *arg {
+        Argument::Register(ref id) | Argument::Qubit(ref id, _) => id,
+    }
+}
+
+/// The qubit or bit of a register with a global index.
+fn argument(registers: &[Register], index: usize) -> Argument {
+    let register = registers
+        .iter()
+        .find(|r| index < r.offset + r.size)
+        .expect("the index is in a register");
+    Argument::Qubit(register.name.clone(), (index - register.offset) as i32)
+}
+
+/// The global index of the `i`th qubit or bit that an argument refers to.
+/// The program has been checked, so the register exists.
+fn index(register: Option<&Register>, arg: &Argument, i: usize) -> usize {
+    let register = register.expect("the program has been checked");
+    match *arg {
+        Argument::Register(_) => register.offset + i,
+        Argument::Qubit(_, index) => register.offset + index as usize,
+    }
+}
+
+/// Helpers for the tests of other modules.
+#[cfg(test)]
+pub(crate) mod tests {
+    use circuit::Circuit;
+    use std::path::Path;
+
+    /// Parses and flattens a source, which must be valid.
+    pub fn flatten(source: &str) -> Circuit {
+        ::parse_str(source, Path::new(""))
+            .and_then(|p| p.flatten())
+            .unwrap()
+    }
+}
+
+#[test]
+fn flatten_test() {
+    let program = Program::from_ast(vec![
+        AstNode::QReg("q".to_string(), 2),
+        AstNode::CReg("c".to_string(), 2),
+        AstNode::If(
+            "c".to_string(),
+            1,
+            Box::new(AstNode::Reset(Argument::Register("q".to_string()))),
+        ),
+    ])
+    .unwrap();
+    let circuit = Circuit::from_program(&program).unwrap();
+
+    assert_eq!(circuit.instructions.len(), 2);
+    assert_eq!(circuit.instructions[1].operation, Operation::Reset(1));
+    assert_eq!(circuit.instructions[1].condition.as_ref().unwrap().value, 1);
+}
+
+#[test]
+fn recursion_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0; qreg q[1]; gate g a { U(0, 0, 0) a; } g q[0];";
+    let mut circuit = flatten(source);
+    circuit.gates[0].body = vec![AstNode::ApplyGate(
+        "g".to_string(),
+        vec![Argument::Register("a".to_string())],
+        vec![],
+    )];
+
+    assert_eq!(
+        circuit.unitary("g", &[]),
+        Err(Error::UndefinedGate("g".to_string()))
+    );
+    assert_eq!(
+        circuit.expand("g", &[], &[0], &|_| false, &mut |_, _, _| Ok(())),
+        Err(Error::UndefinedGate("g".to_string()))
+    );
+    assert_eq!(
+        circuit.inline(&|_| false),
+        Err(Error::UndefinedGate("g".to_string()))
+    );
+    assert_eq!(
+        circuit.decompose(&|_| false),
+        Err(Error::UndefinedGate("g".to_string()))
+    );
+}
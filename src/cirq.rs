@@ -0,0 +1,348 @@
+//! # Cirq
+//!
+//! Converts programs to the JSON serialization of a circuit in
+//! [Cirq](https://quantumai.google/cirq), as read by `cirq.read_json`.
+//!
+//! Programs are flattened with [Program::flatten](../struct.Program.html#method.flatten)
+//! first, then written with [emit](fn.emit.html).
+
+use circuit::{Circuit, Operation};
+use error::Error;
+use expr::Expr;
+use std::collections::HashSet;
+use std::f64::consts::PI;
+use std::fmt::Write;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// The gates of `qelib1.inc` that have an exact equivalent in Cirq.
+const STANDARD: [&str; 18] = [
+    "u3", "u2", "u1", "cx", "id", "x", "y", "z", "h", "s", "sdg", "t", "tdg", "rx", "ry", "rz",
+    "cz", "ccx",
+];
+
+/// A JSON value, with the keys of objects kept in order.
+#[derive(Clone)]
+enum Json {
+    Number(f64),
+    Int(usize),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn object(cirq_type: &str, fields: Vec<(&'static str, Json)>) -> Json {
+        let mut object = vec![("cirq_type", Json::Str(cirq_type.to_string()))];
+        object.extend(fields);
+        Json::Object(object)
+    }
+
+    /// Writes the value with two spaces of indentation, as `cirq.to_json` does.
+    fn write(&self, out: &mut String, indent: usize) {
+        let pad = |out: &mut String, indent: usize| out.extend((0..indent).map(|_| ' '));
+        match *self {
+            Json::Number(n) => write!(out, "{:?}", n).unwrap(),
+            Json::Int(n) => write!(out, "{}", n).unwrap(),
+            Json::Str(ref s) => write!(out, "{:?}", s).unwrap(),
+            Json::Array(ref values) if values.is_empty() => out.push_str("[]"),
+            Json::Array(ref values) => {
+                out.push_str("[\n");
+                for (i, value) in values.iter().enumerate() {
+                    pad(out, indent + 2);
+                    value.write(out, indent + 2);
+                    out.push_str(if i + 1 < values.len() { ",\n" } else { "\n" });
+                }
+                pad(out, indent);
+                out.push(']');
+            }
+            Json::Object(ref fields) => {
+                out.push_str("{\n");
+                for (i, &(key, ref value)) in fields.iter().enumerate() {
+                    pad(out, indent + 2);
+                    write!(out, "{:?}: ", key).unwrap();
+                    value.write(out, indent + 2);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                pad(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// Converts a flattened program into Cirq's JSON.
+///
+/// * Qubits are `LineQubit`s, numbered by their global index.
+/// * Operations are put in the earliest moment that all of their qubits,
+///   and any bits they measure or depend on, are free. Barriers aren't
+///   written, but operations aren't moved past them.
+/// * `U` is written as a `QasmUGate`, and the gates of `qelib1.inc` that
+///   Cirq has, such as `h`, `rz` and `ccx`, as their Cirq gates. Other gates
+///   are replaced by the gates in their definition.
+/// * Each bit of a classical register is given its own measurement key,
+///   such as `c_0`, and `if` statements become classically controlled
+///   operations, with a condition that each bit has its value.
+///
+/// This returns an `Unsupported` error for opaque gates, and an
+/// `InvalidExpression` error if a parameter isn't a finite number.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// h q[0];
+/// cx q[0], q[1];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let json = qasm::cirq::emit(&circuit).unwrap();
+///
+/// assert!(json.starts_with("{\n  \"cirq_type\": \"Circuit\",\n  \"moments\": ["));
+/// assert!(json.contains("\"cirq_type\": \"HPowGate\""));
+/// assert!(json.contains("\"cirq_type\": \"CXPowGate\""));
+/// ```
+pub fn emit(circuit: &Circuit) -> Result<String> {
+    let qelib1 = ::qelib1();
+    let standard: HashSet<&str> = circuit
+        .gates
+        .iter()
+        .filter(|g| STANDARD.contains(&g.name.as_str()) && qelib1.gate(&g.name) == Some(*g))
+        .map(|g| g.name.as_str())
+        .collect();
+
+    // The next moment that each qubit and bit is free in
+    let mut qubits = vec![0; circuit.num_qubits()];
+    let mut bits = vec![0; circuit.num_clbits()];
+    let mut moments: Vec<Vec<Json>> = Vec::new();
+
+    for instruction in &circuit.instructions {
+        let (read, conditions) = match instruction.condition {
+            Some(ref condition) => {
+                let register = &condition.register;
+                let bits: Vec<_> = (register.offset..register.offset + register.size).collect();
+                let values = (0..register.size).map(|i| {
+                    (condition.value as usize)
+                        .checked_shr(i as u32)
+                        .unwrap_or(0)
+                        & 1
+                });
+                let conditions = bits
+                    .iter()
+                    .zip(values)
+                    .map(|(&bit, value)| equals(circuit, bit, value))
+                    .collect();
+                (bits, conditions)
+            }
+            None => (vec![], vec![]),
+        };
+
+        let mut operations = Vec::new();
+        match instruction.operation {
+            Operation::Gate(ref name, ref params, ref targets) => {
+                gate(circuit, &standard, name, params, targets, &mut operations)?
+            }
+            Operation::Measure(qubit, bit) => {
+                let key = Json::object(
+                    "MeasurementKey",
+                    vec![
+                        ("name", Json::Str(key(circuit, bit))),
+                        ("path", Json::Array(vec![])),
+                    ],
+                );
+                let measure = Json::object(
+                    "MeasurementGate",
+                    vec![
+                        ("num_qubits", Json::Int(1)),
+                        ("key", key),
+                        ("invert_mask", Json::Array(vec![])),
+                        ("qid_shape", Json::Array(vec![Json::Int(2)])),
+                    ],
+                );
+                operations.push((measure, vec![qubit], Some(bit)));
+            }
+            Operation::Reset(qubit) => operations.push((
+                Json::object("ResetChannel", vec![("dimension", Json::Int(2))]),
+                vec![qubit],
+                None,
+            )),
+            Operation::Barrier(ref targets) => {
+                let moment = targets.iter().map(|&q| qubits[q]).max().unwrap_or(0);
+                for &q in targets {
+                    qubits[q] = moment;
+                }
+            }
+        }
+
+        for (gate, targets, written) in operations {
+            let moment = targets
+                .iter()
+                .map(|&q| qubits[q])
+                .chain(read.iter().chain(&written).map(|&b| bits[b]))
+                .max()
+                .unwrap_or(0);
+            for &q in &targets {
+                qubits[q] = moment + 1;
+            }
+            for &b in read.iter().chain(&written) {
+                bits[b] = moment + 1;
+            }
+
+            let line_qubits = targets
+                .iter()
+                .map(|&q| Json::object("LineQubit", vec![("x", Json::Int(q))]))
+                .collect();
+            let mut operation = Json::object(
+                "GateOperation",
+                vec![("gate", gate), ("qubits", Json::Array(line_qubits))],
+            );
+            if !conditions.is_empty() {
+                operation = Json::object(
+                    "ClassicallyControlledOperation",
+                    vec![
+                        ("conditions", Json::Array(conditions.clone())),
+                        ("operation", operation),
+                    ],
+                );
+            }
+
+            if moments.len() <= moment {
+                moments.resize_with(moment + 1, Vec::new);
+            }
+            moments[moment].push(operation);
+        }
+    }
+
+    let moments = moments
+        .into_iter()
+        .map(|operations| Json::object("Moment", vec![("operations", Json::Array(operations))]))
+        .collect();
+    let mut out = String::new();
+    Json::object("Circuit", vec![("moments", Json::Array(moments))]).write(&mut out, 0);
+    out.push('\n');
+    Ok(out)
+}
+
+/// The measurement key of a bit, from its register and index.
+fn key(circuit: &Circuit, bit: usize) -> String {
+    let register = circuit
+        .cregs
+        .iter()
+        .find(|r| bit < r.offset + r.size)
+        .expect("the bit is in a register");
+    format!("{}_{}", register.name, bit - register.offset)
+}
+
+/// A condition that a bit was measured to have a value.
+fn equals(circuit: &Circuit, bit: usize, value: usize) -> Json {
+    let args = vec![
+        Json::object("sympy.Symbol", vec![("name", Json::Str(key(circuit, bit)))]),
+        Json::object("sympy.Integer", vec![("i", Json::Int(value))]),
+    ];
+    let expr = Json::object("sympy.Equality", vec![("args", Json::Array(args))]);
+    Json::object("SympyCondition", vec![("expr", expr)])
+}
+
+/// Adds the Cirq gates for a gate, with the qubits they act on.
+fn gate(
+    circuit: &Circuit,
+    standard: &HashSet<&str>,
+    name: &str,
+    params: &[Expr],
+    qubits: &[usize],
+    operations: &mut Vec<(Json, Vec<usize>, Option<usize>)>,
+) -> Result<()> {
+    circuit.expand(
+        name,
+        params,
+        qubits,
+        &|name| standard.contains(name),
+        &mut |name, params, qubits| {
+            let pow = |cirq_type: &str, exponent: f64| {
+                Json::object(
+                    cirq_type,
+                    vec![
+                        ("exponent", Json::Number(exponent)),
+                        ("global_shift", Json::Number(0.0)),
+                    ],
+                )
+            };
+            let rotation =
+                |cirq_type: &str| Json::object(cirq_type, vec![("rads", Json::Number(params[0]))]);
+            let u = |theta: f64, phi: f64, lambda: f64| {
+                Json::object(
+                    "QasmUGate",
+                    vec![
+                        ("theta", Json::Number(theta / PI)),
+                        ("phi", Json::Number(phi / PI)),
+                        ("lmda", Json::Number(lambda / PI)),
+                    ],
+                )
+            };
+
+            let gate = match name {
+                "U" | "u3" => u(params[0], params[1], params[2]),
+                "u2" => u(PI / 2.0, params[0], params[1]),
+                "u1" => pow("ZPowGate", params[0] / PI),
+                "CX" | "cx" => pow("CXPowGate", 1.0),
+                "id" => Json::object(
+                    "IdentityGate",
+                    vec![("qid_shape", Json::Array(vec![Json::Int(2)]))],
+                ),
+                "x" => pow("XPowGate", 1.0),
+                "y" => pow("YPowGate", 1.0),
+                "z" => pow("ZPowGate", 1.0),
+                "h" => pow("HPowGate", 1.0),
+                "s" => pow("ZPowGate", 0.5),
+                "sdg" => pow("ZPowGate", -0.5),
+                "t" => pow("ZPowGate", 0.25),
+                "tdg" => pow("ZPowGate", -0.25),
+                "rx" => rotation("Rx"),
+                "ry" => rotation("Ry"),
+                "rz" => rotation("Rz"),
+                "cz" => pow("CZPowGate", 1.0),
+                "ccx" => pow("CCXPowGate", 1.0),
+                _ => return Err(Error::Unsupported(format!("opaque {}", name))),
+            };
+            operations.push((gate, qubits.to_vec(), None));
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn emit_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        qreg q[2];
+        creg c[2];
+        gate g(t) a, b { U(t, 0, pi) a; CX a, b; }
+        g(pi/2) q[1], q[0];
+        barrier q;
+        measure q[1] -> c[1];
+        U(0, 0, 0) q[0];
+        if(c==2) CX q[0], q[1];";
+    let circuit = flatten(source);
+    let json = emit(&circuit).unwrap();
+    let moments: Vec<_> = json.split("\"cirq_type\": \"Moment\"").skip(1).collect();
+
+    assert_eq!(moments.len(), 4);
+    assert!(moments[0].contains("\"theta\": 0.5") && moments[0].contains("\"x\": 1"));
+    assert!(moments[1].contains("CXPowGate") && !moments[1].contains("Measurement"));
+    assert!(moments[2].contains("\"name\": \"c_1\"") && moments[2].contains("QasmUGate"));
+    assert!(moments[3].contains("ClassicallyControlledOperation"));
+    assert!(moments[3].contains("\"name\": \"c_0\"") && moments[3].contains("\"i\": 0"));
+
+    // Bits past the width of the value are compared with zero
+    let wide = flatten("OPENQASM 2.0; qreg q[1]; creg c[70]; if(c==1) U(0, 0, 0) q[0];");
+    assert!(emit(&wide).is_ok());
+}
@@ -0,0 +1,495 @@
+//! A circuit as a directed acyclic graph of its operations.
+
+use ast::AstNode;
+use circuit::{Circuit, Instruction, Operation};
+use error::Error;
+use program::{Gate, Opaque, Program, Register};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// A qubit or a bit, given by its global index.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum Wire {
+    Qubit(usize),
+    Clbit(usize),
+}
+
+/// The index of a node in a [Dag](struct.Dag.html).
+///
+/// Indices aren't reused, so they stay valid when other nodes are removed.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct NodeIndex(usize);
+
+impl NodeIndex {
+    /// The position of the node in the order the nodes were added.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    instruction: Instruction,
+    /// Where the node is in the circuit: its index when it was pushed, followed
+    /// by its position among the instructions that were substituted for a node
+    position: Vec<usize>,
+    wires: Vec<Wire>,
+    /// The nodes before and after this one on each of its wires
+    predecessors: Vec<Option<NodeIndex>>,
+    successors: Vec<Option<NodeIndex>>,
+}
+
+impl Node {
+    fn position(&self, wire: Wire) -> Option<usize> {
+        self.wires.iter().position(|&w| w == wire)
+    }
+}
+
+/// The wires that an instruction uses: the qubits it acts on, the bit it
+/// measures into, and the bits of the register its condition reads. Each
+/// wire is listed once, as a barrier can name a qubit more than once.
+fn wires(instruction: &Instruction) -> Vec<Wire> {
+    let used: Vec<_> = match instruction.operation {
+        Operation::Gate(_, _, ref qubits) | Operation::Barrier(ref qubits) => {
+            qubits.iter().map(|&q| Wire::Qubit(q)).collect()
+        }
+        Operation::Measure(q, b) => vec![Wire::Qubit(q), Wire::Clbit(b)],
+        Operation::Reset(q) => vec![Wire::Qubit(q)],
+    };
+    let condition = instruction.condition.iter().flat_map(|condition| {
+        let register = &condition.register;
+        (register.offset..register.offset + register.size).map(Wire::Clbit)
+    });
+
+    let mut wires = Vec::new();
+    for wire in used.into_iter().chain(condition) {
+        if !wires.contains(&wire) {
+            wires.push(wire);
+        }
+    }
+    wires
+}
+
+/// A flattened program as a directed acyclic graph.
+///
+/// Each node is an instruction, and there is an edge from one node to the
+/// next on each qubit and bit, or wire, that it uses. Conditions use all of
+/// the bits of their register. The declarations are kept, as in a
+/// [Circuit](struct.Circuit.html).
+///
+/// Nodes can be visited in topological order, removed and substituted, and
+/// the graph can be converted back into a circuit or AST nodes.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{Dag, Expr, Operation, Wire};
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// qreg q[2];
+/// U(0, 0, 0) q[0];
+/// CX q[0], q[1];
+/// U(pi, 0, pi) q[1];
+/// "#;
+///
+/// let program = qasm::parse_str(source, &env::current_dir().unwrap()).unwrap();
+/// let mut dag = Dag::from_program(&program).unwrap();
+/// let order = dag.topological();
+///
+/// assert_eq!(dag.predecessors(order[1]), vec![order[0]]);
+/// assert_eq!(dag.successor(order[1], Wire::Qubit(1)), Some(order[2]));
+///
+/// dag.remove(order[1]);
+/// assert_eq!(dag.successor(order[0], Wire::Qubit(0)), None);
+/// assert_eq!(dag.first(Wire::Qubit(1)), Some(order[2]));
+///
+/// let circuit = dag.to_circuit();
+/// assert_eq!(circuit.instructions.len(), 2);
+/// assert_eq!(
+///     circuit.instructions[1].operation,
+///     Operation::Gate("U".to_string(), vec![Expr::Pi, Expr::Int(0), Expr::Pi], vec![1])
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Dag {
+    pub qregs: Vec<Register>,
+    pub cregs: Vec<Register>,
+    pub gates: Vec<Gate>,
+    pub opaques: Vec<Opaque>,
+    /// The nodes, by index, with `None` for removed nodes
+    nodes: Vec<Option<Node>>,
+    /// The first and last node on each wire
+    first: HashMap<Wire, NodeIndex>,
+    last: HashMap<Wire, NodeIndex>,
+}
+
+impl Dag {
+    /// Builds the graph of a circuit, with a node for each instruction.
+    pub fn from_circuit(circuit: &Circuit) -> Dag {
+        let mut dag = Dag {
+            qregs: circuit.qregs.clone(),
+            cregs: circuit.cregs.clone(),
+            gates: circuit.gates.clone(),
+            opaques: circuit.opaques.clone(),
+            ..Dag::default()
+        };
+        for instruction in &circuit.instructions {
+            dag.push(instruction.clone());
+        }
+        dag
+    }
+
+    /// Flattens a program, then builds its graph.
+    pub fn from_program(program: &Program) -> Result<Dag> {
+        Ok(Dag::from_circuit(&program.flatten()?))
+    }
+
+    /// Converts the graph back into a circuit, with the instructions in
+    /// topological order.
+    pub fn to_circuit(&self) -> Circuit {
+        Circuit {
+            qregs: self.qregs.clone(),
+            cregs: self.cregs.clone(),
+            gates: self.gates.clone(),
+            opaques: self.opaques.clone(),
+            instructions: self
+                .topological()
+                .into_iter()
+                .map(|node| self.instruction(node).clone())
+                .collect(),
+        }
+    }
+
+    /// Converts the graph back into AST nodes, as [Circuit::to_ast](struct.Circuit.html#method.to_ast) does.
+    pub fn to_ast(&self) -> Vec<AstNode> {
+        self.to_circuit().to_ast()
+    }
+
+    /// The number of nodes in the graph.
+    pub fn len(&self) -> usize {
+        self.nodes.iter().filter(|n| n.is_some()).count()
+    }
+
+    /// Whether the graph has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn node(&self, node: NodeIndex) -> &Node {
+        self.nodes[node.0]
+            .as_ref()
+            .expect("the node hasn't been removed")
+    }
+
+    fn node_mut(&mut self, node: NodeIndex) -> &mut Node {
+        self.nodes[node.0]
+            .as_mut()
+            .expect("the node hasn't been removed")
+    }
+
+    /// Whether a node is in the graph, and hasn't been removed.
+    pub fn contains(&self, node: NodeIndex) -> bool {
+        self.nodes.get(node.0).is_some_and(|n| n.is_some())
+    }
+
+    /// The instruction of a node. This panics if the node has been removed.
+    pub fn instruction(&self, node: NodeIndex) -> &Instruction {
+        &self.node(node).instruction
+    }
+
+    /// The wires that a node uses: its qubits, then its bits.
+    pub fn wires(&self, node: NodeIndex) -> &[Wire] {
+        &self.node(node).wires
+    }
+
+    /// The first node on a wire.
+    pub fn first(&self, wire: Wire) -> Option<NodeIndex> {
+        self.first.get(&wire).cloned()
+    }
+
+    /// The last node on a wire.
+    pub fn last(&self, wire: Wire) -> Option<NodeIndex> {
+        self.last.get(&wire).cloned()
+    }
+
+    /// The node before a node on one of its wires.
+    pub fn predecessor(&self, node: NodeIndex, wire: Wire) -> Option<NodeIndex> {
+        let node = self.node(node);
+        node.position(wire).and_then(|i| node.predecessors[i])
+    }
+
+    /// The node after a node on one of its wires.
+    pub fn successor(&self, node: NodeIndex, wire: Wire) -> Option<NodeIndex> {
+        let node = self.node(node);
+        node.position(wire).and_then(|i| node.successors[i])
+    }
+
+    /// The nodes directly before a node, on any of its wires, in order.
+    pub fn predecessors(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut nodes: Vec<_> = self
+            .node(node)
+            .predecessors
+            .iter()
+            .filter_map(|&n| n)
+            .collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+
+    /// The nodes directly after a node, on any of its wires, in order.
+    pub fn successors(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut nodes: Vec<_> = self
+            .node(node)
+            .successors
+            .iter()
+            .filter_map(|&n| n)
+            .collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+
+    /// The nodes in an order where every node comes after its predecessors.
+    /// Of the nodes that could come next, the one that comes first in the
+    /// circuit is picked, so a graph built from a circuit keeps the circuit's
+    /// order. Instructions that were substituted for a node take its place.
+    pub fn topological(&self) -> Vec<NodeIndex> {
+        let mut waiting: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut ready = BinaryHeap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.is_some() {
+                let count = self.predecessors(NodeIndex(i)).len();
+                if count == 0 {
+                    ready.push(Reverse((
+                        self.node(NodeIndex(i)).position.clone(),
+                        NodeIndex(i),
+                    )));
+                } else {
+                    waiting.insert(NodeIndex(i), count);
+                }
+            }
+        }
+
+        let mut order = Vec::new();
+        while let Some(Reverse((_, node))) = ready.pop() {
+            order.push(node);
+            for successor in self.successors(node) {
+                let count = waiting.get_mut(&successor).expect("the node is waiting");
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(Reverse((self.node(successor).position.clone(), successor)));
+                }
+            }
+        }
+        order
+    }
+
+    /// Sets the node after `before` on a wire to `after`, and the other way
+    /// around. `None` is the start or end of the wire.
+    fn link(&mut self, wire: Wire, before: Option<NodeIndex>, after: Option<NodeIndex>) {
+        match before {
+            Some(node) => {
+                let node = self.node_mut(node);
+                let i = node.position(wire).expect("the node uses the wire");
+                node.successors[i] = after;
+            }
+            None => match after {
+                Some(after) => {
+                    self.first.insert(wire, after);
+                }
+                None => {
+                    self.first.remove(&wire);
+                }
+            },
+        }
+        match after {
+            Some(node) => {
+                let node = self.node_mut(node);
+                let i = node.position(wire).expect("the node uses the wire");
+                node.predecessors[i] = before;
+            }
+            None => match before {
+                Some(before) => {
+                    self.last.insert(wire, before);
+                }
+                None => {
+                    self.last.remove(&wire);
+                }
+            },
+        }
+    }
+
+    /// Adds a node, with no edges yet.
+    fn add(&mut self, instruction: Instruction, position: Vec<usize>) -> NodeIndex {
+        let wires = wires(&instruction);
+        let index = NodeIndex(self.nodes.len());
+        self.nodes.push(Some(Node {
+            instruction,
+            position,
+            predecessors: vec![None; wires.len()],
+            successors: vec![None; wires.len()],
+            wires,
+        }));
+        index
+    }
+
+    /// Adds an instruction at the end of the circuit.
+    pub fn push(&mut self, instruction: Instruction) -> NodeIndex {
+        let node = self.add(instruction, vec![self.nodes.len()]);
+        for wire in self.node(node).wires.clone() {
+            let last = self.last(wire);
+            self.link(wire, last, Some(node));
+            self.link(wire, Some(node), None);
+        }
+        node
+    }
+
+    /// Removes a node, joining the nodes before and after it on each wire,
+    /// and returns its instruction.
+    pub fn remove(&mut self, node: NodeIndex) -> Instruction {
+        let removed = self.nodes[node.0]
+            .take()
+            .expect("the node hasn't been removed");
+        for (i, &wire) in removed.wires.iter().enumerate() {
+            self.link(wire, removed.predecessors[i], removed.successors[i]);
+        }
+        removed.instruction
+    }
+
+    /// Replaces a node with a list of instructions, run in order in its place,
+    /// and returns their nodes.
+    ///
+    /// The instructions can only use the wires that the node used. Otherwise,
+    /// the graph is left as it was, and an `Unsupported` error is returned.
+    /// A gate that uses a qubit twice returns a `DuplicateArgument` error.
+    pub fn substitute(
+        &mut self,
+        node: NodeIndex,
+        instructions: Vec<Instruction>,
+    ) -> Result<Vec<NodeIndex>> {
+        for instruction in &instructions {
+            if let Operation::Gate(_, _, ref qubits) = instruction.operation {
+                if let Some((i, _)) = qubits
+                    .iter()
+                    .enumerate()
+                    .find(|&(i, q)| qubits[..i].contains(q))
+                {
+                    return Err(Error::DuplicateArgument(qubits[i].to_string()));
+                }
+            }
+        }
+        let old_wires = self.wires(node).to_vec();
+        if instructions
+            .iter()
+            .any(|i| wires(i).iter().any(|w| !old_wires.contains(w)))
+        {
+            return Err(Error::Unsupported(
+                "substituting an instruction on other wires".to_string(),
+            ));
+        }
+
+        let old = self.nodes[node.0]
+            .take()
+            .expect("the node hasn't been removed");
+        // The last node on each of the old node's wires, so far
+        let mut previous = old.predecessors.clone();
+        let mut nodes = Vec::new();
+        for (i, instruction) in instructions.into_iter().enumerate() {
+            let mut position = old.position.clone();
+            position.push(i);
+            let new = self.add(instruction, position);
+            for wire in self.node(new).wires.clone() {
+                let i = old.position(wire).expect("the wires were checked");
+                self.link(wire, previous[i], Some(new));
+                previous[i] = Some(new);
+            }
+            nodes.push(new);
+        }
+        for (i, &wire) in old.wires.iter().enumerate() {
+            self.link(wire, previous[i], old.successors[i]);
+        }
+        Ok(nodes)
+    }
+}
+
+#[test]
+fn substitute_test() {
+    let source = "OPENQASM 2.0;
+        qreg q[3];
+        creg c[1];
+        opaque a x;
+        opaque b x, y;
+        CX q[0], q[1];
+        U(0, 0, 0) q[1];
+        measure q[0] -> c[0];
+        if(c==1) U(0, 0, 0) q[2];";
+    let program = ::parse_str(source, ::std::path::Path::new("")).unwrap();
+    let mut dag = Dag::from_program(&program).unwrap();
+    let nodes = dag.topological();
+    let gate = |name: &str, qubits: Vec<usize>| Instruction {
+        operation: Operation::Gate(name.to_string(), vec![], qubits),
+        condition: None,
+    };
+
+    // The condition waits for the measurement
+    assert_eq!(dag.predecessors(nodes[3]), vec![nodes[2]]);
+    assert_eq!(dag.wires(nodes[3]), &[Wire::Qubit(2), Wire::Clbit(0)]);
+
+    let new = dag
+        .substitute(nodes[0], vec![gate("a", vec![1]), gate("b", vec![0, 1])])
+        .unwrap();
+    assert_eq!(dag.first(Wire::Qubit(1)), Some(new[0]));
+    assert_eq!(dag.first(Wire::Qubit(0)), Some(new[1]));
+    assert_eq!(dag.predecessors(new[1]), vec![new[0]]);
+    assert_eq!(dag.successors(new[1]), vec![nodes[1], nodes[2]]);
+    assert_eq!(
+        dag.substitute(new[0], vec![gate("b", vec![1, 1])]),
+        Err(Error::DuplicateArgument("1".to_string()))
+    );
+    assert_eq!(
+        dag.substitute(new[0], vec![gate("c", vec![2])]),
+        Err(Error::Unsupported(
+            "substituting an instruction on other wires".to_string()
+        ))
+    );
+
+    dag.substitute(nodes[1], vec![]).unwrap();
+    assert_eq!(dag.last(Wire::Qubit(1)), Some(new[1]));
+    assert!(!dag.contains(nodes[1]));
+    assert_eq!(dag.len(), 4);
+
+    let order: Vec<_> = dag.topological().into_iter().map(|n| n.index()).collect();
+    assert_eq!(order, vec![4, 5, 2, 3]);
+    let circuit = Program::from_ast(dag.to_ast())
+        .and_then(|p| p.flatten())
+        .unwrap();
+    assert_eq!(circuit, dag.to_circuit());
+}
+
+#[test]
+fn repeated_wire_test() {
+    let source = "OPENQASM 2.0; qreg q[2]; CX q[0], q[1]; barrier q, q[0]; U(0, 0, 0) q[0];";
+    let program = ::parse_str(source, ::std::path::Path::new("")).unwrap();
+    let dag = Dag::from_program(&program).unwrap();
+    let nodes = dag.topological();
+
+    assert_eq!(nodes.len(), 3);
+    assert_eq!(dag.wires(nodes[1]), &[Wire::Qubit(0), Wire::Qubit(1)]);
+    assert_eq!(dag.predecessors(nodes[2]), vec![nodes[1]]);
+
+    let program = ::parse_str(
+        "OPENQASM 2.0; qreg q[1]; CX q[0], q[0];",
+        ::std::path::Path::new(""),
+    );
+    assert_eq!(
+        program
+            .and_then(|p| Dag::from_program(&p))
+            .map(|dag| dag.len()),
+        Err(Error::DuplicateArgument("q[0]".to_string()))
+    );
+}
@@ -0,0 +1,273 @@
+use super::{bit_wire, columns, condition_wire, number, shape, Shape, Target};
+use circuit::{Circuit, Operation};
+use error::Error;
+use expr::Expr;
+use std::f64::consts::PI;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// The largest denominator of the fractions of pi that parameters are
+/// written as.
+const DENOMINATOR: i64 = 64;
+
+/// The symbol of a gate from `qelib1.inc`, or `U`, in math mode.
+fn symbol(name: &str) -> &str {
+    match name {
+        "id" => "I",
+        "x" => "X",
+        "y" => "Y",
+        "z" => "Z",
+        "h" => "H",
+        "s" => "S",
+        "sdg" => "S^\\dagger",
+        "t" => "T",
+        "tdg" => "T^\\dagger",
+        "rx" => "R_x",
+        "ry" => "R_y",
+        "rz" => "R_z",
+        "u1" => "U_1",
+        "u2" => "U_2",
+        "u3" => "U_3",
+        _ => name,
+    }
+}
+
+/// Writes a value as a multiple of `\pi` when it is a fraction of pi, with
+/// a denominator of at most 64, or as a decimal otherwise.
+fn pi_fraction(value: f64) -> String {
+    let turns = value / PI;
+    let fraction = (1..=DENOMINATOR).find_map(|q| {
+        let p = (turns * q as f64).round();
+        if (turns * q as f64 - p).abs() < 1e-9 {
+            Some((p as i64, q))
+        } else {
+            None
+        }
+    });
+    let (p, q) = match fraction {
+        Some((0, _)) => return "0".to_string(),
+        Some(fraction) => fraction,
+        None => return number(value),
+    };
+
+    let sign = if p < 0 { "-" } else { "" };
+    let numerator = match p.abs() {
+        1 => "\\pi".to_string(),
+        p => format!("{}\\pi", p),
+    };
+    if q == 1 {
+        format!("{}{}", sign, numerator)
+    } else {
+        format!("{}\\frac{{{}}}{{{}}}", sign, numerator, q)
+    }
+}
+
+/// The label of a gate, with its parameters.
+fn label(symbol: &str, params: &[Expr]) -> Result<String> {
+    if params.is_empty() {
+        return Ok(symbol.to_string());
+    }
+    let values = params
+        .iter()
+        .map(|p| p.eval().map(pi_fraction))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!("{}({})", symbol, values.join(", ")))
+}
+
+/// Escapes the underscores in a name, so it can be used in math mode.
+fn escape(name: &str) -> String {
+    name.replace('_', "\\_")
+}
+
+/// Writes a flattened program as a `quantikz` environment, for the LaTeX
+/// package of the same name.
+///
+/// * Each qubit has a wire, named after its register and index, such as
+///   `q_{0}`, followed by a classical wire for each classical register.
+/// * The gates of `qelib1.inc` use their usual symbol, such as `H` or
+///   `S^\dagger`, with parameters written as fractions of `\pi` when they
+///   can be. `CX`, `cx` and `ccx` use `\ctrl` and `\targ`, and `cz`, `cy`,
+///   `ch`, `crz`, `cu1` and `cu3` use `\ctrl` with the gate on the target.
+/// * Other gates are a `\gate` over all of their qubits, with their name.
+/// * Measurements are a `\meter{}`, with a classical wire down to the
+///   classical register.
+/// * `if` statements have a classical wire from the gate to the classical
+///   register, where the value is shown.
+/// * Resets are drawn as `|0\rangle`, and barriers as a `\slice{}`.
+///
+/// Instructions are put as far left as they can go without overlapping, and
+/// the cells are lined up in columns. The environment uses the wire commands
+/// of `quantikz` 1.0, such as `\setwiretype` and `\wire`.
+///
+/// This returns an `UndefinedParameter` error if a gate's parameters can't
+/// be evaluated.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// creg c[1];
+/// h q[0];
+/// cx q[0], q[1];
+/// rz(-pi/4) q[0];
+/// measure q[1] -> c[0];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+///
+/// assert_eq!(
+///     qasm::draw::quantikz(&circuit).unwrap(),
+///     r"\begin{quantikz}
+/// \lstick{$q_{0}$}             & \gate{H} & \ctrl{1} & \gate{R_z(-\frac{\pi}{4})} & \\
+/// \lstick{$q_{1}$}             &          & \targ{}  & \meter{} \wire[d][1]{c}    & \\
+/// \lstick{$c$} \setwiretype{c} &          &          &                            &
+/// \end{quantikz}
+/// "
+/// );
+/// ```
+pub fn quantikz(circuit: &Circuit) -> Result<String> {
+    let standard = circuit.standard_gates();
+    let wires = circuit.num_qubits() + circuit.cregs.len();
+
+    let mut cells: Vec<Vec<String>> = circuit
+        .qregs
+        .iter()
+        .flat_map(|r| {
+            (0..r.size).map(move |i| format!("\\lstick{{${}_{{{}}}$}}", escape(&r.name), i))
+        })
+        .chain(
+            circuit
+                .cregs
+                .iter()
+                .map(|r| format!("\\lstick{{${}$}} \\setwiretype{{c}}", escape(&r.name))),
+        )
+        .map(|label| vec![label])
+        .collect();
+
+    for column in columns(circuit) {
+        let mut column_cells = vec![String::new(); wires];
+        for instruction in column {
+            match instruction.operation {
+                Operation::Gate(ref name, ref params, ref targets) => {
+                    match shape(&standard, name, targets.len()) {
+                        Shape::Controlled(target) => {
+                            let (&last, controls) = targets.split_last().unwrap();
+                            for &control in controls {
+                                column_cells[control] =
+                                    format!("\\ctrl{{{}}}", last as isize - control as isize);
+                            }
+                            column_cells[last] = match target {
+                                Target::Not => "\\targ{}".to_string(),
+                                Target::Control => "\\control{}".to_string(),
+                                Target::Gate(name) => {
+                                    format!("\\gate{{{}}}", label(symbol(name), params)?)
+                                }
+                            };
+                        }
+                        Shape::Standard(name) => {
+                            column_cells[targets[0]] =
+                                format!("\\gate{{{}}}", label(symbol(name), params)?);
+                        }
+                        Shape::Box(name) => {
+                            let name = format!("\\mathrm{{{}}}", escape(name));
+                            let first = targets.iter().cloned().min().unwrap_or(0);
+                            let last = targets.iter().cloned().max().unwrap_or(0);
+                            column_cells[first] = if first == last {
+                                format!("\\gate{{{}}}", label(&name, params)?)
+                            } else {
+                                format!("\\gate[{}]{{{}}}", last - first + 1, label(&name, params)?)
+                            };
+                        }
+                    }
+                }
+                Operation::Measure(qubit, bit) => {
+                    let (wire, _) = bit_wire(circuit, bit);
+                    column_cells[qubit] = format!("\\meter{{}} \\wire[d][{}]{{c}}", wire - qubit);
+                }
+                Operation::Reset(qubit) => {
+                    column_cells[qubit] = "\\gate{|0\\rangle}".to_string();
+                }
+                Operation::Barrier(ref targets) => {
+                    let first = targets.iter().cloned().min().unwrap_or(0);
+                    column_cells[first] = "\\slice{}".to_string();
+                }
+            }
+
+            if let Some(wire) = condition_wire(circuit, instruction) {
+                let value = instruction.condition.as_ref().unwrap().value;
+                // The lowest qubit that the operation is drawn on
+                let bottom = match instruction.operation {
+                    Operation::Gate(_, _, ref targets) | Operation::Barrier(ref targets) => {
+                        targets.iter().cloned().max().unwrap_or(0)
+                    }
+                    Operation::Measure(qubit, _) | Operation::Reset(qubit) => qubit,
+                };
+                column_cells[wire] =
+                    format!("\\gate{{={}}} \\wire[u][{}]{{c}}", value, wire - bottom);
+            }
+        }
+        for (wire, cell) in column_cells.into_iter().enumerate() {
+            cells[wire].push(cell);
+        }
+    }
+
+    // Line up the columns, leaving an empty one at the end for the wires to end in
+    let mut widths = Vec::new();
+    for column in 0..cells.first().map_or(0, |row| row.len()) {
+        let width = cells
+            .iter()
+            .map(|row| row[column].chars().count())
+            .max()
+            .unwrap_or(0);
+        widths.push(width);
+    }
+    let mut out = String::from("\\begin{quantikz}\n");
+    for (wire, row) in cells.iter().enumerate() {
+        let mut line = String::new();
+        for (cell, &width) in row.iter().zip(&widths) {
+            line.push_str(&format!("{:1$} & ", cell, width));
+        }
+        if wire + 1 < wires {
+            line.push_str("\\\\");
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.push_str("\\end{quantikz}\n");
+    Ok(out)
+}
+
+#[test]
+fn quantikz_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        qreg q[3];
+        creg c[2];
+        gate my_g(t) a, b { U(t, 0, 0) a; CX a, b; }
+        my_g(3*pi/2) q[0], q[2];
+        barrier q[0], q[1];
+        reset q[1];
+        if(c==3) U(0.1, pi, 0) q[1];";
+    let circuit = flatten(source);
+    let latex = quantikz(&circuit).unwrap();
+
+    assert!(latex.contains("\\gate[3]{\\mathrm{my\\_g}(\\frac{3\\pi}{2})}"));
+    assert!(latex.contains("\\gate{U(0.1, \\pi, 0)}"));
+    assert!(latex.contains("\\gate{=3} \\wire[u][2]{c}"));
+    assert_eq!(latex.matches("\\slice{}").count(), 1);
+
+    assert_eq!(pi_fraction(PI), "\\pi");
+    assert_eq!(pi_fraction(-2.0 * PI), "-2\\pi");
+    assert_eq!(pi_fraction(PI / 64.0), "\\frac{\\pi}{64}");
+    assert_eq!(pi_fraction(-5.0 * PI / 6.0), "-\\frac{5\\pi}{6}");
+    assert_eq!(pi_fraction(1.0), "1");
+}
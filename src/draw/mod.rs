@@ -0,0 +1,162 @@
+//! # Drawing
+//!
+//! Draws circuit diagrams of flattened programs, with a wire for each qubit,
+//! then a wire for each classical register.
+//!
+//! Programs are flattened with [Program::flatten](../struct.Program.html#method.flatten)
+//! first, then drawn as text with [text](fn.text.html), as an SVG image
+//! with [svg](fn.svg.html), or as LaTeX with [quantikz](fn.quantikz.html).
+
+mod latex;
+mod svg;
+mod text;
+
+pub use self::latex::quantikz;
+pub use self::svg::{svg, SvgOptions};
+pub use self::text::{text, TextOptions};
+
+use circuit::{Circuit, Instruction, Operation};
+use std::collections::HashSet;
+
+/// The wire of a classical register's bit, and the bit's index in the register.
+fn bit_wire(circuit: &Circuit, bit: usize) -> (usize, usize) {
+    let i = circuit
+        .cregs
+        .iter()
+        .position(|r| bit < r.offset + r.size)
+        .expect("the bit is in a register");
+    (circuit.num_qubits() + i, bit - circuit.cregs[i].offset)
+}
+
+/// The wire of a classical register that a condition compares.
+fn condition_wire(circuit: &Circuit, instruction: &Instruction) -> Option<usize> {
+    instruction.condition.as_ref().map(|condition| {
+        let i = circuit
+            .cregs
+            .iter()
+            .position(|r| *r == condition.register)
+            .expect("the register is in the circuit");
+        circuit.num_qubits() + i
+    })
+}
+
+/// The first and last wires that an instruction is drawn across.
+fn span(circuit: &Circuit, instruction: &Instruction) -> (usize, usize) {
+    let mut wires = match instruction.operation {
+        Operation::Gate(_, _, ref qubits) | Operation::Barrier(ref qubits) => qubits.clone(),
+        Operation::Measure(qubit, bit) => vec![qubit, bit_wire(circuit, bit).0],
+        Operation::Reset(qubit) => vec![qubit],
+    };
+    wires.extend(condition_wire(circuit, instruction));
+    let first = wires.iter().cloned().min().unwrap_or(0);
+    let last = wires.iter().cloned().max().unwrap_or(0);
+    (first, last)
+}
+
+/// Puts each instruction in the first column after the instructions before it
+/// on the same wires, so that instructions in a column don't overlap.
+fn columns(circuit: &Circuit) -> Vec<Vec<&Instruction>> {
+    let mut next = vec![0; circuit.num_qubits() + circuit.cregs.len()];
+    let mut columns: Vec<Vec<&Instruction>> = Vec::new();
+    for instruction in &circuit.instructions {
+        let (first, last) = span(circuit, instruction);
+        let column = next[first..=last].iter().cloned().max().unwrap_or(0);
+        for wire in &mut next[first..=last] {
+            *wire = column + 1;
+        }
+        if columns.len() <= column {
+            columns.resize_with(column + 1, Vec::new);
+        }
+        columns[column].push(instruction);
+    }
+    columns
+}
+
+/// Whether a gate is `CX`, or `cx` as defined in `qelib1.inc`.
+fn is_cx(circuit: &Circuit, name: &str) -> bool {
+    match name {
+        "CX" => true,
+        "cx" => circuit.gate(name) == ::qelib1().gate(name),
+        _ => false,
+    }
+}
+
+/// How a gate is drawn.
+enum Shape<'a> {
+    /// A control on each qubit but the last, and a target on the last
+    Controlled(Target<'a>),
+    /// `U`, or a gate on one qubit from `qelib1.inc`, drawn with its usual symbol
+    Standard(&'a str),
+    /// A box with the gate's name over all of its qubits
+    Box(&'a str),
+}
+
+/// The target of a controlled gate.
+enum Target<'a> {
+    /// `⊕`, as for `CX`
+    Not,
+    /// Another control, as for `cz`
+    Control,
+    /// A gate on one qubit from `qelib1.inc`, as `y` is for `cy`
+    Gate(&'a str),
+}
+
+/// How a gate is drawn, given the names of the gates that are defined as in
+/// `qelib1.inc`, and the number of qubits it's applied to.
+fn shape<'a>(standard: &HashSet<&str>, name: &'a str, qubits: usize) -> Shape<'a> {
+    if name == "U" && qubits == 1 {
+        return Shape::Standard(name);
+    }
+    if name != "CX" && !standard.contains(name) {
+        return Shape::Box(name);
+    }
+    let (controls, target) = match name {
+        "CX" | "cx" => (1, Target::Not),
+        "ccx" => (2, Target::Not),
+        "cz" => (1, Target::Control),
+        "cy" => (1, Target::Gate("y")),
+        "ch" => (1, Target::Gate("h")),
+        "crz" => (1, Target::Gate("rz")),
+        "cu1" => (1, Target::Gate("u1")),
+        "cu3" => (1, Target::Gate("u3")),
+        _ if qubits == 1 => return Shape::Standard(name),
+        _ => return Shape::Box(name),
+    };
+    if controls + 1 == qubits {
+        Shape::Controlled(target)
+    } else {
+        Shape::Box(name)
+    }
+}
+
+/// Writes an evaluated parameter with at most 4 decimal places.
+fn number(value: f64) -> String {
+    let s = format!("{:.4}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+#[test]
+fn columns_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        qreg q[3];
+        creg c[1];
+        U(0, 0, 0) q[0];
+        U(0, 0, 0) q[2];
+        CX q[0], q[1];
+        measure q[2] -> c[0];
+        U(0, 0, 0) q[1];";
+    let circuit = flatten(source);
+    let sizes: Vec<_> = columns(&circuit).iter().map(|c| c.len()).collect();
+
+    assert_eq!(sizes, vec![2, 2, 1]);
+    assert_eq!(number(::std::f64::consts::PI), "3.1416");
+    assert_eq!(number(0.5), "0.5");
+    assert_eq!(number(-0.00001), "0");
+}
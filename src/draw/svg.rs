@@ -0,0 +1,527 @@
+use super::{bit_wire, columns, condition_wire, number, shape, Shape, Target};
+use circuit::{Circuit, Instruction, Operation};
+use error::Error;
+use expr::Expr;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// The distance between wires.
+const WIRE: f64 = 40.0;
+/// The height, and smallest width, of a gate's box.
+const BOX: f64 = 30.0;
+/// The space between columns, and around the diagram.
+const GAP: f64 = 12.0;
+
+/// How to draw a circuit as an SVG image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Whether gates defined in the program, other than those of `qelib1.inc`,
+    /// are drawn as a single box, or replaced by the gates in their definition.
+    pub collapse: bool,
+    /// The font of the labels, as a CSS `font-family`.
+    pub font_family: String,
+    /// The size of the font of labels, in pixels. Parameters are smaller.
+    pub font_size: f64,
+    /// The color of wires, borders and controls.
+    pub line_color: String,
+    /// The color inside gates' boxes.
+    pub gate_fill: String,
+    /// The color of labels.
+    pub text_color: String,
+    /// The color behind the diagram, or `None` to leave it transparent.
+    pub background: Option<String>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            collapse: true,
+            font_family: "Helvetica, Arial, sans-serif".to_string(),
+            font_size: 14.0,
+            line_color: "#000000".to_string(),
+            gate_fill: "#ffffff".to_string(),
+            text_color: "#000000".to_string(),
+            background: Some("#ffffff".to_string()),
+        }
+    }
+}
+
+/// The label of a gate from `qelib1.inc`, or `U`.
+fn glyph(name: &str) -> &str {
+    match name {
+        "id" => "I",
+        "x" => "X",
+        "y" => "Y",
+        "z" => "Z",
+        "h" => "H",
+        "s" => "S",
+        "sdg" => "S†",
+        "t" => "T",
+        "tdg" => "T†",
+        "rx" => "Rx",
+        "ry" => "Ry",
+        "rz" => "Rz",
+        "u1" => "U1",
+        "u2" => "U2",
+        "u3" => "U3",
+        _ => name,
+    }
+}
+
+/// Writes the elements of the image.
+struct Writer<'a> {
+    out: String,
+    options: &'a SvgOptions,
+}
+
+impl<'a> Writer<'a> {
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64) {
+        writeln!(
+            self.out,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}"/>"#,
+            number(x1),
+            number(y1),
+            number(x2),
+            number(y2),
+            escape(&self.options.line_color)
+        )
+        .unwrap();
+    }
+
+    /// Draws a classical wire, as two lines either side of a horizontal or
+    /// vertical line.
+    fn double_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64) {
+        let (dx, dy) = if y1 == y2 { (0.0, 1.5) } else { (1.5, 0.0) };
+        self.line(x1 - dx, y1 - dy, x2 - dx, y2 - dy);
+        self.line(x1 + dx, y1 + dy, x2 + dx, y2 + dy);
+    }
+
+    fn rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        writeln!(
+            self.out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="{}"/>"#,
+            number(x),
+            number(y),
+            number(width),
+            number(height),
+            escape(&self.options.gate_fill),
+            escape(&self.options.line_color)
+        )
+        .unwrap();
+    }
+
+    fn circle(&mut self, x: f64, y: f64, r: f64, filled: bool) {
+        let fill = if filled {
+            &self.options.line_color
+        } else {
+            &self.options.gate_fill
+        };
+        writeln!(
+            self.out,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}"/>"#,
+            number(x),
+            number(y),
+            number(r),
+            escape(fill),
+            escape(&self.options.line_color)
+        )
+        .unwrap();
+    }
+
+    /// Writes text, centered vertically on `y`.
+    fn text(&mut self, x: f64, y: f64, size: f64, anchor: &str, text: &str) {
+        writeln!(
+            self.out,
+            r#"<text x="{}" y="{}" font-size="{}" text-anchor="{}" fill="{}">{}</text>"#,
+            number(x),
+            number(y + size * 0.35),
+            number(size),
+            anchor,
+            escape(&self.options.text_color),
+            escape(text)
+        )
+        .unwrap();
+    }
+}
+
+/// Escapes text for XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The layout of the diagram.
+struct Layout<'a> {
+    circuit: &'a Circuit,
+    standard: HashSet<&'a str>,
+    /// The width of a character of a label, and of a parameter
+    char_width: f64,
+    small_width: f64,
+    /// The y coordinate of the first wire
+    top: f64,
+}
+
+impl<'a> Layout<'a> {
+    fn y(&self, wire: usize) -> f64 {
+        self.top + wire as f64 * WIRE
+    }
+
+    /// The width of a box for a label and parameters.
+    fn box_width(&self, label: &str, params: &str, indices: bool) -> f64 {
+        let label = label.chars().count() as f64 * self.char_width;
+        let params = params.chars().count() as f64 * self.small_width;
+        let indices = if indices { 2.0 * self.small_width } else { 0.0 };
+        (label.max(params) + 16.0 + 2.0 * indices).max(BOX)
+    }
+
+    /// The width of the column an instruction needs.
+    fn width(&self, instruction: &Instruction) -> Result<f64> {
+        let width = match instruction.operation {
+            Operation::Gate(ref name, ref params, ref qubits) => {
+                let params = parameters(params)?;
+                match shape(&self.standard, name, qubits.len()) {
+                    Shape::Controlled(Target::Gate(name)) | Shape::Standard(name) => {
+                        self.box_width(glyph(name), &params, false)
+                    }
+                    Shape::Controlled(_) => BOX,
+                    Shape::Box(name) => self.box_width(name, &params, qubits.len() > 1),
+                }
+            }
+            Operation::Measure(..) | Operation::Reset(_) => BOX,
+            Operation::Barrier(_) => BOX / 2.0,
+        };
+        let value = instruction
+            .condition
+            .as_ref()
+            .map_or(0.0, |c| c.value.to_string().len() as f64 * self.char_width);
+        Ok(width.max(value))
+    }
+
+    /// Draws a box over some wires, with a label and parameters.
+    fn gate_box(&self, writer: &mut Writer, x: f64, wires: &[usize], label: &str, params: &str) {
+        let first = wires.iter().cloned().min().unwrap_or(0);
+        let last = wires.iter().cloned().max().unwrap_or(0);
+        let width = self.box_width(label, params, wires.len() > 1);
+        let (top, bottom) = (self.y(first) - BOX / 2.0, self.y(last) + BOX / 2.0);
+        writer.rect(x - width / 2.0, top, width, bottom - top);
+
+        let middle = (top + bottom) / 2.0;
+        let size = writer.options.font_size;
+        if params.is_empty() {
+            writer.text(x, middle, size, "middle", label);
+        } else {
+            writer.text(x, middle - size * 0.4, size, "middle", label);
+            writer.text(x, middle + size * 0.5, size * 0.8, "middle", params);
+        }
+        if wires.len() > 1 {
+            for (i, &wire) in wires.iter().enumerate() {
+                let left = x - width / 2.0 + 4.0;
+                writer.text(left, self.y(wire), size * 0.8, "start", &i.to_string());
+            }
+        }
+    }
+
+    /// Draws an instruction in the middle of a column, returning the bottom
+    /// of what was drawn on the qubits.
+    fn draw(&self, writer: &mut Writer, x: f64, instruction: &Instruction) -> Result<f64> {
+        let bottom = match instruction.operation {
+            Operation::Gate(ref name, ref params, ref qubits) => {
+                let params = parameters(params)?;
+                match shape(&self.standard, name, qubits.len()) {
+                    Shape::Controlled(target) => {
+                        let first = qubits.iter().cloned().min().unwrap_or(0);
+                        let last = qubits.iter().cloned().max().unwrap_or(0);
+                        writer.line(x, self.y(first), x, self.y(last));
+                        let (&target_qubit, controls) = qubits.split_last().unwrap();
+                        for &control in controls {
+                            writer.circle(x, self.y(control), 4.0, true);
+                        }
+                        let y = self.y(target_qubit);
+                        match target {
+                            Target::Not => {
+                                writer.circle(x, y, 10.0, false);
+                                writer.line(x - 10.0, y, x + 10.0, y);
+                                writer.line(x, y - 10.0, x, y + 10.0);
+                            }
+                            Target::Control => writer.circle(x, y, 4.0, true),
+                            Target::Gate(name) => {
+                                self.gate_box(writer, x, &[target_qubit], glyph(name), &params)
+                            }
+                        }
+                        self.y(last) + 10.0
+                    }
+                    Shape::Standard(name) => {
+                        self.gate_box(writer, x, qubits, glyph(name), &params);
+                        self.y(qubits[0]) + BOX / 2.0
+                    }
+                    Shape::Box(name) => {
+                        self.gate_box(writer, x, qubits, name, &params);
+                        self.y(qubits.iter().cloned().max().unwrap_or(0)) + BOX / 2.0
+                    }
+                }
+            }
+            Operation::Measure(qubit, bit) => {
+                let (wire, index) = bit_wire(self.circuit, bit);
+                let (y, bit_y) = (self.y(qubit), self.y(wire));
+                writer.double_line(x, y + BOX / 2.0, x, bit_y - 8.0);
+                writeln!(
+                    writer.out,
+                    r#"<polygon points="{},{} {},{} {},{}" fill="{}"/>"#,
+                    number(x - 5.0),
+                    number(bit_y - 8.0),
+                    number(x + 5.0),
+                    number(bit_y - 8.0),
+                    number(x),
+                    number(bit_y),
+                    escape(&writer.options.line_color)
+                )
+                .unwrap();
+                let size = writer.options.font_size * 0.8;
+                writer.text(x, bit_y + size + 2.0, size, "middle", &index.to_string());
+
+                writer.rect(x - BOX / 2.0, y - BOX / 2.0, BOX, BOX);
+                writeln!(
+                    writer.out,
+                    r#"<path d="M {} {} A 10 10 0 0 1 {} {}" fill="none" stroke="{}"/>"#,
+                    number(x - 10.0),
+                    number(y + 6.0),
+                    number(x + 10.0),
+                    number(y + 6.0),
+                    escape(&writer.options.line_color)
+                )
+                .unwrap();
+                writer.line(x, y + 6.0, x + 7.0, y - 8.0);
+                y + BOX / 2.0
+            }
+            Operation::Reset(qubit) => {
+                self.gate_box(writer, x, &[qubit], "|0⟩", "");
+                self.y(qubit) + BOX / 2.0
+            }
+            Operation::Barrier(ref qubits) => {
+                for &qubit in qubits {
+                    let y = self.y(qubit);
+                    writeln!(
+                        writer.out,
+                        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="0.15"/>"#,
+                        number(x - BOX / 4.0),
+                        number(y - WIRE / 2.0),
+                        number(BOX / 2.0),
+                        number(WIRE),
+                        escape(&writer.options.line_color)
+                    )
+                    .unwrap();
+                    writeln!(
+                        writer.out,
+                        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-dasharray="4,3"/>"#,
+                        number(x),
+                        number(y - WIRE / 2.0),
+                        number(x),
+                        number(y + WIRE / 2.0),
+                        escape(&writer.options.line_color)
+                    )
+                    .unwrap();
+                }
+                0.0
+            }
+        };
+        Ok(bottom)
+    }
+}
+
+/// A gate's evaluated parameters.
+fn parameters(params: &[Expr]) -> Result<String> {
+    let values = params
+        .iter()
+        .map(|p| p.eval().map(number))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(values.join(", "))
+}
+
+/// Draws a flattened program as an SVG image.
+///
+/// * Each qubit has a wire, named after its register and index, such as
+///   `q[0]`, followed by a double wire for each classical register.
+/// * The gates of `qelib1.inc` are boxes with their usual symbol, such as
+///   `H` or `T†`, and their evaluated parameters. `CX`, `cx` and `ccx` have
+///   a dot on each control and `⊕` on the target, and `cz` has two dots.
+///   `cy`, `ch`, `crz`, `cu1` and `cu3` have a dot on the control, and a box
+///   on the target.
+/// * Other gates are boxes over all their qubits, which are numbered inside
+///   the box, in order. With `collapse` turned off in the options, gates
+///   defined in the program are drawn as the gates in their definition.
+/// * Measurements are a meter, with an arrow down to the classical register,
+///   and the index of the bit under it.
+/// * `if` statements have a line down to the classical register, with a dot
+///   on it, and the value under it.
+/// * Resets are drawn as `|0⟩`, and barriers as shaded dashed lines.
+///
+/// Instructions are put as far left as they can go without overlapping.
+/// The image is self-contained, with its size in pixels.
+///
+/// This returns an `UndefinedParameter` error if a gate's parameters can't
+/// be evaluated, and an `InvalidExpression` error if gates are expanded and
+/// a parameter isn't a finite number.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use qasm::draw::{self, SvgOptions};
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// creg c[2];
+/// h q[0];
+/// cx q[0], q[1];
+/// measure q -> c;
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let svg = draw::svg(&circuit, &SvgOptions::default()).unwrap();
+///
+/// assert!(svg.starts_with("<?xml"));
+/// assert!(svg.contains(">H</text>"));
+/// assert_eq!(svg.matches("<circle").count(), 2);
+/// assert!(svg.ends_with("</svg>\n"));
+/// ```
+pub fn svg(circuit: &Circuit, options: &SvgOptions) -> Result<String> {
+    let expanded;
+    let circuit = if options.collapse {
+        circuit
+    } else {
+        let standard = circuit.standard_gates();
+        expanded = circuit.inline(&|name| standard.contains(name))?;
+        &expanded
+    };
+
+    let mut labels: Vec<String> = circuit
+        .qregs
+        .iter()
+        .flat_map(|r| (0..r.size).map(move |i| format!("{}[{}]", r.name, i)))
+        .collect();
+    labels.extend(circuit.cregs.iter().map(|r| r.name.clone()));
+
+    let layout = Layout {
+        circuit,
+        standard: circuit.standard_gates(),
+        char_width: options.font_size * 0.6,
+        small_width: options.font_size * 0.8 * 0.6,
+        top: GAP + BOX / 2.0,
+    };
+    let label_width =
+        labels.iter().map(|l| l.chars().count()).max().unwrap_or(0) as f64 * layout.char_width;
+    let start = GAP + label_width + GAP;
+
+    let columns = columns(circuit);
+    let mut widths = Vec::new();
+    for column in &columns {
+        let mut width: f64 = 0.0;
+        for instruction in column {
+            width = width.max(layout.width(instruction)?);
+        }
+        widths.push(width);
+    }
+    let end = start + widths.iter().map(|w| w + GAP).sum::<f64>() + GAP;
+    let wires = labels.len();
+    let height = layout.y(wires.max(1) - 1) + BOX / 2.0 + options.font_size + GAP;
+
+    let mut writer = Writer {
+        out: String::new(),
+        options,
+    };
+    let width = end + GAP;
+    writeln!(writer.out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        writer.out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="{2}">"#,
+        number(width),
+        number(height),
+        escape(&options.font_family)
+    )
+    .unwrap();
+    if let Some(ref background) = options.background {
+        writeln!(
+            writer.out,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            escape(background)
+        )
+        .unwrap();
+    }
+
+    for (wire, label) in labels.iter().enumerate() {
+        let y = layout.y(wire);
+        writer.text(GAP + label_width, y, options.font_size, "end", label);
+        if wire < circuit.num_qubits() {
+            writer.line(start, y, end, y);
+        } else {
+            writer.double_line(start, y, end, y);
+        }
+    }
+
+    let mut x = start + GAP;
+    for (column, width) in columns.iter().zip(widths) {
+        let middle = x + width / 2.0;
+        for instruction in column {
+            let bottom = layout.draw(&mut writer, middle, instruction)?;
+            if let Some(wire) = condition_wire(circuit, instruction) {
+                let y = layout.y(wire);
+                writer.double_line(middle, bottom, middle, y);
+                writer.circle(middle, y, 4.0, true);
+                let value = instruction.condition.as_ref().unwrap().value;
+                let size = options.font_size * 0.8;
+                writer.text(middle, y + size + 2.0, size, "middle", &value.to_string());
+            }
+        }
+        x += width + GAP;
+    }
+
+    writer.out.push_str("</svg>\n");
+    Ok(writer.out)
+}
+
+#[test]
+fn svg_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        qreg q[3];
+        creg c[2];
+        gate g(t) a, b { U(t, 0, 0) a; CX a, b; }
+        g(pi/2) q[0], q[2];
+        barrier q[0], q[1];
+        reset q[1];
+        if(c==3) CX q[1], q[0];
+        measure q[2] -> c[1];";
+    let circuit = flatten(source);
+
+    let collapsed = svg(&circuit, &SvgOptions::default()).unwrap();
+    assert!(collapsed.contains(">g</text>"));
+    assert!(collapsed.contains(">1.5708</text>"));
+    assert!(collapsed.contains(">|0⟩</text>"));
+    assert!(collapsed.contains(">3</text>"));
+    assert!(collapsed.contains("<polygon"));
+    assert_eq!(collapsed.matches("stroke-dasharray").count(), 2);
+    assert_eq!(collapsed.matches("<circle").count(), 3);
+
+    let options = SvgOptions {
+        collapse: false,
+        background: None,
+        gate_fill: "<none>".to_string(),
+        ..SvgOptions::default()
+    };
+    let expanded = svg(&circuit, &options).unwrap();
+    assert!(!expanded.contains(">g</text>"));
+    assert!(expanded.contains(">U</text>"));
+    assert!(expanded.contains("fill=\"&lt;none&gt;\""));
+    assert_eq!(expanded.matches("<circle").count(), 5);
+    assert_eq!(expanded.matches("<rect").count(), 5);
+}
@@ -0,0 +1,455 @@
+use super::{bit_wire, columns, condition_wire, is_cx, number};
+use circuit::{Circuit, Instruction, Operation};
+use error::Error;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// How to draw a circuit as text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextOptions {
+    /// Whether to draw with Unicode box drawing characters, or only with ASCII.
+    pub unicode: bool,
+    /// The number of characters to wrap lines at, or `None` to not wrap them.
+    pub width: Option<usize>,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            unicode: true,
+            width: Some(80),
+        }
+    }
+}
+
+/// The characters that are replaced when drawing with ASCII.
+const ASCII: [(char, char); 23] = [
+    ('─', '-'),
+    ('═', '='),
+    ('│', '|'),
+    ('║', '|'),
+    ('┌', '+'),
+    ('┐', '+'),
+    ('└', '+'),
+    ('┘', '+'),
+    ('┤', '|'),
+    ('├', '|'),
+    ('┼', '+'),
+    ('╪', '+'),
+    ('╫', '+'),
+    ('╬', '+'),
+    ('╥', '+'),
+    ('╩', 'v'),
+    ('●', '*'),
+    ('⊕', 'X'),
+    ('■', '#'),
+    ('░', '#'),
+    ('⟩', '>'),
+    ('«', '<'),
+    ('»', '>'),
+];
+
+/// The characters of a column of the diagram, with three rows for each wire.
+/// The middle row of each is the wire itself.
+struct Grid {
+    rows: Vec<Vec<char>>,
+    qubits: usize,
+}
+
+impl Grid {
+    fn new(wires: usize, qubits: usize, width: usize) -> Grid {
+        let rows = (0..wires * 3)
+            .map(|row| vec![wire_char(row, qubits, ' '); width])
+            .collect();
+        Grid { rows, qubits }
+    }
+
+    fn text(&mut self, row: usize, x: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            self.rows[row][x + i] = c;
+        }
+    }
+
+    /// Draws a vertical line from one row to another, crossing the wires between.
+    fn vertical(&mut self, x: usize, from: usize, to: usize, double: bool) {
+        for row in from..=to {
+            self.rows[row][x] = match (row % 3 == 1, row / 3 < self.qubits, double) {
+                (false, _, false) => '│',
+                (false, _, true) => '║',
+                (true, true, false) => '┼',
+                (true, false, false) => '╪',
+                (true, true, true) => '╫',
+                (true, false, true) => '╬',
+            };
+        }
+    }
+}
+
+/// The character of a wire's row, or `blank` between the wires.
+fn wire_char(row: usize, qubits: usize, blank: char) -> char {
+    match (row % 3 == 1, row / 3 < qubits) {
+        (false, _) => blank,
+        (true, true) => '─',
+        (true, false) => '═',
+    }
+}
+
+/// How an instruction is drawn.
+struct Element<'a> {
+    instruction: &'a Instruction,
+    /// The width of the part drawn on the qubits
+    width: usize,
+    /// Marks on classical wires, with the text under them, for measurements
+    /// and conditions
+    marks: Vec<(usize, char, String)>,
+}
+
+impl<'a> Element<'a> {
+    fn new(circuit: &Circuit, instruction: &'a Instruction) -> Result<Element<'a>> {
+        let mut marks = Vec::new();
+        let width = match instruction.operation {
+            Operation::Gate(ref name, _, ref qubits)
+                if is_cx(circuit, name) && qubits.len() == 2 =>
+            {
+                1
+            }
+            Operation::Gate(ref name, ref params, ref qubits) => {
+                let label = label(name, params)?.chars().count();
+                if qubits.len() > 1 {
+                    label + 4 + 2 * index_width(qubits.len())
+                } else {
+                    label + 4
+                }
+            }
+            Operation::Measure(_, bit) => {
+                let (wire, index) = bit_wire(circuit, bit);
+                marks.push((wire, '╩', index.to_string()));
+                3
+            }
+            Operation::Reset(_) => 3,
+            Operation::Barrier(_) => 1,
+        };
+        if let Some(wire) = condition_wire(circuit, instruction) {
+            let value = instruction.condition.as_ref().unwrap().value;
+            marks.push((wire, '■', value.to_string()));
+        }
+
+        Ok(Element {
+            instruction,
+            width,
+            marks,
+        })
+    }
+
+    /// The width of the column the element needs.
+    fn column_width(&self) -> usize {
+        self.marks
+            .iter()
+            .map(|m| m.2.chars().count())
+            .fold(self.width, usize::max)
+    }
+
+    /// Draws the element in the middle of a column.
+    fn draw(&self, circuit: &Circuit, grid: &mut Grid, width: usize) -> Result<()> {
+        let start = (width - self.width) / 2;
+        let x = start + self.width / 2;
+
+        // The last row drawn on the qubits, where lines to the classical wires start
+        let bottom = match self.instruction.operation {
+            Operation::Gate(ref name, _, ref qubits)
+                if is_cx(circuit, name) && qubits.len() == 2 =>
+            {
+                let (control, target) = (qubits[0], qubits[1]);
+                grid.rows[control * 3 + 1][x] = '●';
+                grid.rows[target * 3 + 1][x] = '⊕';
+                let (first, last) = (control.min(target), control.max(target));
+                grid.vertical(x, first * 3 + 2, last * 3, false);
+                last * 3 + 1
+            }
+            Operation::Gate(ref name, ref params, ref qubits) => {
+                let label = label(name, params)?;
+                let first = qubits.iter().cloned().min().unwrap_or(0);
+                let last = qubits.iter().cloned().max().unwrap_or(0);
+                let (top, bottom) = (first * 3, last * 3 + 2);
+                let end = start + self.width - 1;
+
+                let border = |left, right| {
+                    let mut border = String::new();
+                    border.push(left);
+                    border.extend((0..self.width - 2).map(|_| '─'));
+                    border.push(right);
+                    border
+                };
+                grid.text(top, start, &border('┌', '┐'));
+                grid.text(bottom, start, &border('└', '┘'));
+                for row in top + 1..bottom {
+                    grid.text(row, start, &border('│', '│').replace('─', " "));
+                }
+                for (i, &qubit) in qubits.iter().enumerate() {
+                    grid.rows[qubit * 3 + 1][start] = '┤';
+                    grid.rows[qubit * 3 + 1][end] = '├';
+                    if qubits.len() > 1 {
+                        grid.text(qubit * 3 + 1, start + 1, &i.to_string());
+                    }
+                }
+                let length = label.chars().count();
+                grid.text(
+                    (top + bottom) / 2,
+                    start + (self.width - length) / 2,
+                    &label,
+                );
+                if !self.marks.is_empty() {
+                    grid.rows[bottom][x] = '╥';
+                }
+                bottom
+            }
+            Operation::Measure(qubit, _) => {
+                grid.text(qubit * 3, start, "┌─┐");
+                grid.text(qubit * 3 + 1, start, "┤M├");
+                grid.text(qubit * 3 + 2, start, "└╥┘");
+                qubit * 3 + 2
+            }
+            Operation::Reset(qubit) => {
+                grid.text(qubit * 3 + 1, start, "|0⟩");
+                qubit * 3 + 1
+            }
+            Operation::Barrier(ref qubits) => {
+                for &qubit in qubits {
+                    for row in qubit * 3..qubit * 3 + 3 {
+                        grid.rows[row][x] = '░';
+                    }
+                }
+                0
+            }
+        };
+
+        if let Some(last) = self.marks.iter().map(|m| m.0).max() {
+            grid.vertical(x, bottom + 1, last * 3, true);
+        }
+        for &(wire, mark, ref text) in &self.marks {
+            grid.rows[wire * 3 + 1][x] = mark;
+            let length = text.chars().count();
+            let at = x.saturating_sub(length / 2).min(width - length);
+            grid.text(wire * 3 + 2, at, text);
+        }
+        Ok(())
+    }
+}
+
+/// A gate's name, with its evaluated parameters.
+fn label(name: &str, params: &[::expr::Expr]) -> Result<String> {
+    if params.is_empty() {
+        return Ok(name.to_string());
+    }
+    let values = params
+        .iter()
+        .map(|p| p.eval().map(number))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!("{}({})", name, values.join(", ")))
+}
+
+/// The number of digits in the index of a gate's last qubit.
+fn index_width(qubits: usize) -> usize {
+    (qubits - 1).to_string().len()
+}
+
+/// Draws a flattened program as a text diagram.
+///
+/// * Each qubit has a wire, named after its register and index, such as
+///   `q[0]`, followed by a double wire for each classical register.
+/// * Gates are boxes with their name and evaluated parameters. The qubits
+///   of a gate on more than one are numbered inside the box, in order.
+/// * `CX` is drawn with `●` on the control and `⊕` on the target.
+/// * Measurements are an `M` box, with a line down to the classical register,
+///   and the index of the bit under it.
+/// * `if` statements have a line down to the classical register, with `■`
+///   on it, and the value under it.
+/// * Resets are drawn as `|0⟩`, and barriers as `░`.
+///
+/// Instructions are put as far left as they can go without overlapping.
+/// Lines longer than the width in the options are wrapped, with `»` at the
+/// end of the wires and `«` at the start of the next part.
+///
+/// This returns an `UndefinedParameter` error if a gate's parameters
+/// can't be evaluated.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use qasm::draw::{self, TextOptions};
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// creg c[1];
+/// h q[0];
+/// cx q[0], q[1];
+/// measure q[1] -> c[0];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+///
+/// assert_eq!(
+///     draw::text(&circuit, &TextOptions::default()).unwrap(),
+///     "       ┌───┐
+/// q[0]: ─┤ h ├─●─────
+///        └───┘ │
+///              │ ┌─┐
+/// q[1]: ───────⊕─┤M├─
+///                └╥┘
+///                 ║
+///    c: ══════════╩══
+///                 0
+/// "
+/// );
+/// ```
+pub fn text(circuit: &Circuit, options: &TextOptions) -> Result<String> {
+    let qubits = circuit.num_qubits();
+    let wires = qubits + circuit.cregs.len();
+
+    // The rows of each column, drawn separately so they can be wrapped
+    let mut drawn: Vec<Vec<String>> = Vec::new();
+    for column in columns(circuit) {
+        let elements = column
+            .into_iter()
+            .map(|instruction| Element::new(circuit, instruction))
+            .collect::<Result<Vec<_>>>()?;
+        let width = elements
+            .iter()
+            .map(Element::column_width)
+            .max()
+            .unwrap_or(0);
+        let mut grid = Grid::new(wires, qubits, width);
+        for element in &elements {
+            element.draw(circuit, &mut grid, width)?;
+        }
+        drawn.push(
+            grid.rows
+                .into_iter()
+                .map(|r| r.into_iter().collect())
+                .collect(),
+        );
+    }
+
+    let mut labels: Vec<String> = circuit
+        .qregs
+        .iter()
+        .flat_map(|r| (0..r.size).map(move |i| format!("{}[{}]", r.name, i)))
+        .collect();
+    labels.extend(circuit.cregs.iter().map(|r| r.name.clone()));
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+    // Split the columns into parts that fit in the width
+    let mut parts: Vec<Vec<&Vec<String>>> = vec![Vec::new()];
+    let mut used = label_width + 3;
+    for column in &drawn {
+        let width = column.first().map_or(0, |r| r.chars().count()) + 1;
+        let full = options.width.is_some_and(|max| used + width + 1 > max);
+        if full && !parts.last().unwrap().is_empty() {
+            parts.push(Vec::new());
+            used = label_width + 3;
+        }
+        parts.last_mut().unwrap().push(column);
+        used += width;
+    }
+
+    let mut out = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let mut lines = Vec::new();
+        for row in 0..wires * 3 {
+            let mut line = String::new();
+            if row % 3 == 1 {
+                line.push_str(&format!("{:>1$}: ", labels[row / 3], label_width));
+                line.push(if i > 0 {
+                    '«'
+                } else {
+                    wire_char(row, qubits, ' ')
+                });
+            } else {
+                line.extend((0..label_width + 3).map(|_| ' '));
+            }
+            for column in part {
+                line.push_str(&column[row]);
+                line.push(wire_char(row, qubits, ' '));
+            }
+            if row % 3 == 1 && i + 1 < parts.len() {
+                line.push('»');
+            }
+            lines.push(line.trim_end().to_string());
+        }
+
+        // Leave out the empty rows above the first wire and below the last
+        let first = lines.iter().position(|l| !l.is_empty()).unwrap_or(0);
+        let last = lines
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(0, |l| l + 1);
+        for line in &lines[first..last] {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    if !options.unicode {
+        out = out
+            .chars()
+            .map(|c| ASCII.iter().find(|a| a.0 == c).map_or(c, |a| a.1))
+            .collect();
+    }
+    Ok(out)
+}
+
+#[test]
+fn text_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        qreg q[3];
+        creg c[2];
+        gate g(t) a, b { U(t, 0, 0) a; CX a, b; }
+        g(pi/2) q[0], q[2];
+        barrier q[0], q[1];
+        reset q[1];
+        if(c==3) CX q[1], q[0];";
+    let circuit = flatten(source);
+    let options = TextOptions {
+        unicode: false,
+        width: Some(30),
+    };
+
+    assert_eq!(
+        text(&circuit, &options).unwrap(),
+        "       +-------------+ #
+q[0]: -|0            |-#----->
+       |             | #
+       |             | #
+q[1]: -|  g(1.5708)  |-#-|0>->
+       |             | #
+       |             |
+q[2]: -|1            |------->
+       +-------------+
+
+   c: =======================>
+
+q[0]: <X-
+       |
+       |
+q[1]: <*-
+       |
+       |
+q[2]: <+-
+       |
+       |
+   c: <#=
+       3
+"
+    );
+}
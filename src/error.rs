@@ -1,8 +1,10 @@
 use std::fmt;
+use std::io;
 
-/// Represents Errors that can occur during parsing.
+/// Represents Errors that can occur while processing, parsing or checking a program.
 ///
-/// The name of each corresponds to the type of error.
+/// The name of each corresponds to the type of error. Errors that refer
+/// to something in the source hold its name.
 /// This enum implements the display trait, thus there is
 /// nice outputs when printing:
 ///
@@ -12,7 +14,12 @@ use std::fmt;
 /// println!("Got an error: {}", qasm::Error::UnsupportedVersion);
 /// // "Got an error: Unsupported Version. Please Use OpenQASM Version 2.0"
 /// ```
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "::serialize::ErrorRepr", into = "::serialize::ErrorRepr")
+)]
 pub enum Error {
     MissingSemicolon,
     UnsupportedVersion,
@@ -21,6 +28,23 @@ pub enum Error {
     MissingInt,
     MissingIdentifier,
     MissingVersion,
+    IoError(io::ErrorKind),
+    IncludeError(String),
+    UndefinedQReg(String),
+    UndefinedCReg(String),
+    UndefinedGate(String),
+    Redefinition(String),
+    IndexOutOfRange(String, i32),
+    RegisterSizeMismatch(String),
+    WrongArgumentCount(String),
+    WrongParameterCount(String),
+    InvalidExpression(String),
+    UndefinedParameter(String),
+    InvalidIdentifier(String),
+    UndefinedGateArgument(String),
+    IndexedGateArgument(String),
+    DuplicateArgument(String),
+    Unsupported(String),
 }
 
 impl fmt::Display for Error {
@@ -37,6 +61,35 @@ impl fmt::Display for Error {
             Error::MissingVersion => {
                 write!(f, "Missing A Version Statement At The Start Of The File")
             }
+            Error::IoError(kind) => write!(f, "Couldn't Read The Source: {:?}", kind),
+            Error::IncludeError(ref file) => write!(f, "Couldn't Include The File `{}`", file),
+            Error::UndefinedQReg(ref id) => write!(f, "Undefined Quantum Register `{}`", id),
+            Error::UndefinedCReg(ref id) => write!(f, "Undefined Classical Register `{}`", id),
+            Error::UndefinedGate(ref id) => write!(f, "Undefined Gate `{}`", id),
+            Error::Redefinition(ref id) => write!(f, "`{}` Is Already Defined", id),
+            Error::IndexOutOfRange(ref id, i) => {
+                write!(f, "Index {} Is Out Of Range For Register `{}`", i, id)
+            }
+            Error::RegisterSizeMismatch(ref id) => {
+                write!(f, "Registers Of Different Sizes Given To `{}`", id)
+            }
+            Error::WrongArgumentCount(ref id) => {
+                write!(f, "Wrong Number Of Arguments Given To `{}`", id)
+            }
+            Error::WrongParameterCount(ref id) => {
+                write!(f, "Wrong Number Of Parameters Given To `{}`", id)
+            }
+            Error::InvalidExpression(ref expr) => write!(f, "Invalid Expression `{}`", expr),
+            Error::UndefinedParameter(ref id) => write!(f, "Undefined Parameter `{}`", id),
+            Error::InvalidIdentifier(ref id) => write!(f, "Invalid Identifier `{}`", id),
+            Error::UndefinedGateArgument(ref id) => {
+                write!(f, "Undefined Gate Argument `{}`", id)
+            }
+            Error::IndexedGateArgument(ref id) => {
+                write!(f, "Gate Argument `{}` Can't Be Indexed", id)
+            }
+            Error::DuplicateArgument(ref id) => write!(f, "`{}` Is Used Twice", id),
+            Error::Unsupported(ref what) => write!(f, "`{}` Is Not Supported", what),
         }
     }
 }
@@ -0,0 +1,262 @@
+//! Fault-tolerant resource estimates: Clifford and T counts, and T-depth.
+
+use circuit::{Circuit, Instruction, Operation};
+use dag::Dag;
+use error::Error;
+use exact::exact;
+use expr::Expr;
+use program::Program;
+use stats::longest_path;
+use std::fmt;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// The kind of gate an inlined operation is, in the Clifford+T gate set.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GateClass {
+    /// A Clifford gate
+    Clifford,
+    /// Clifford gates and this many T or T† gates
+    T(usize),
+    /// Rotations by angles that aren't multiples of pi/4, which have to be
+    /// approximated, along with Clifford gates and T or T† gates
+    Arbitrary {
+        /// The number of T or T† gates
+        t: usize,
+        /// The number of rotations by other angles
+        rotations: usize,
+    },
+}
+
+impl GateClass {
+    /// The number of T or T† gates the gate needs, apart from any that
+    /// synthesizing its rotations needs.
+    pub fn t_count(&self) -> usize {
+        match *self {
+            GateClass::Clifford => 0,
+            GateClass::T(t) | GateClass::Arbitrary { t, .. } => t,
+        }
+    }
+
+    /// The number of rotations by arbitrary angles in the gate.
+    pub fn rotations(&self) -> usize {
+        match *self {
+            GateClass::Arbitrary { rotations, .. } => rotations,
+            _ => 0,
+        }
+    }
+}
+
+/// An estimate of the resources a program needs on a fault-tolerant machine
+/// with Clifford and T gates.
+///
+/// Gates are inlined into `U` and `CX`, keeping their parameters as
+/// expressions, and each `U(theta, phi, lambda)` is taken as
+/// `Rz(phi) Ry(theta) Rz(lambda)`. An angle is evaluated exactly, as a
+/// rational number plus a rational multiple of pi, so `pi/4` is a T gate,
+/// but `0.785398` or `sin(1)` are arbitrary rotations. Multiples of pi/2 are
+/// Clifford gates, odd multiples of pi/4 are a T gate each, and other angles
+/// are rotations that need to be synthesized. When `theta` is a multiple of
+/// pi, the two `Rz` rotations are combined.
+///
+/// The T-depth is the number of T gates on the longest path through the
+/// program, where an operation follows the ones before it on its qubits and
+/// bits.
+///
+/// The struct implements `Display`, giving a summary:
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::Estimate;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[3];
+/// ccx q[0], q[1], q[2];
+/// rz(pi/4) q[0];
+/// rz(0.3) q[1];
+/// h q[2];
+/// "#;
+///
+/// let program = qasm::parse_str(source, &env::current_dir().unwrap()).unwrap();
+/// let estimate = Estimate::from_program(&program).unwrap();
+///
+/// assert_eq!(estimate.t_count, 8);
+/// assert_eq!(estimate.rotations, 1);
+/// assert_eq!(
+///     estimate.to_string(),
+///     "clifford gates: 9
+/// t gates: 8 (t-count 8, t-depth 5)
+/// arbitrary rotations: 1 (in 1 gates)
+/// "
+/// );
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Estimate {
+    /// The number of inlined gates that are Clifford gates.
+    pub clifford: usize,
+    /// The number of inlined gates that need T gates, but no arbitrary
+    /// rotations.
+    pub t_gates: usize,
+    /// The number of inlined gates with arbitrary rotations.
+    pub arbitrary: usize,
+    /// The total number of T and T† gates, apart from any that synthesizing
+    /// the arbitrary rotations needs.
+    pub t_count: usize,
+    /// The number of T and T† gates on the longest path through the program.
+    pub t_depth: usize,
+    /// The total number of rotations that need to be synthesized.
+    pub rotations: usize,
+    /// Every inlined gate, with its class, in order.
+    pub gates: Vec<(Instruction, GateClass)>,
+}
+
+impl Estimate {
+    /// Flattens a program, then estimates its resources.
+    pub fn from_program(program: &Program) -> Result<Estimate> {
+        Estimate::from_circuit(&program.flatten()?)
+    }
+
+    /// Estimates the resources of a flattened program.
+    ///
+    /// This returns an `Unsupported` error if the program uses an opaque
+    /// gate, as its cost isn't known.
+    pub fn from_circuit(circuit: &Circuit) -> Result<Estimate> {
+        let decomposed = circuit.decompose(&|_| false)?;
+        let mut estimate = Estimate {
+            clifford: 0,
+            t_gates: 0,
+            arbitrary: 0,
+            t_count: 0,
+            t_depth: 0,
+            rotations: 0,
+            gates: Vec::new(),
+        };
+        for instruction in &decomposed.instructions {
+            let class = match instruction.operation {
+                Operation::Gate(ref name, ref params, _) => classify(name, params)?,
+                _ => continue,
+            };
+            match class {
+                GateClass::Clifford => estimate.clifford += 1,
+                GateClass::T(_) => estimate.t_gates += 1,
+                GateClass::Arbitrary { .. } => estimate.arbitrary += 1,
+            }
+            estimate.t_count += class.t_count();
+            estimate.rotations += class.rotations();
+            estimate.gates.push((instruction.clone(), class));
+        }
+
+        let dag = Dag::from_circuit(&decomposed);
+        estimate.t_depth = longest_path(&dag, |instruction| match instruction.operation {
+            Operation::Gate(ref name, ref params, _) => {
+                classify(name, params).map_or(0, |class| class.t_count())
+            }
+            _ => 0,
+        })
+        .0;
+        Ok(estimate)
+    }
+}
+
+/// The class of a `U` or `CX` gate.
+fn classify(name: &str, params: &[Expr]) -> Result<GateClass> {
+    let angles = match (name, params) {
+        ("CX", _) => return Ok(GateClass::Clifford),
+        ("U", [theta, phi, lambda]) => {
+            let (theta, phi, lambda) = (exact(theta), exact(phi), exact(lambda));
+            match theta.and_then(|t| t.half_turns()) {
+                // Ry(theta) is the identity or Y, up to a phase, and Y Rz(lambda) is
+                // Rz(-lambda) Y, so the rotations add up to one
+                Some(turns) => {
+                    let sum = match (phi, lambda) {
+                        (Some(phi), Some(lambda)) if turns % 2 == 0 => phi.add(lambda),
+                        (Some(phi), Some(lambda)) => phi.sub(lambda),
+                        _ => None,
+                    };
+                    vec![sum]
+                }
+                None => vec![theta, phi, lambda],
+            }
+        }
+        ("U", _) => return Err(Error::WrongParameterCount(name.to_string())),
+        _ => return Err(Error::Unsupported(format!("opaque {}", name))),
+    };
+
+    let (mut t, mut rotations) = (0, 0);
+    for angle in angles {
+        match angle.and_then(|a| a.quarter_turns()) {
+            Some(quarters) if quarters % 2 == 0 => {}
+            Some(_) => t += 1,
+            None => rotations += 1,
+        }
+    }
+    Ok(match (t, rotations) {
+        (0, 0) => GateClass::Clifford,
+        (t, 0) => GateClass::T(t),
+        (t, rotations) => GateClass::Arbitrary { t, rotations },
+    })
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "clifford gates: {}", self.clifford)?;
+        writeln!(
+            f,
+            "t gates: {} (t-count {}, t-depth {})",
+            self.t_gates, self.t_count, self.t_depth
+        )?;
+        writeln!(
+            f,
+            "arbitrary rotations: {} (in {} gates)",
+            self.rotations, self.arbitrary
+        )
+    }
+}
+
+#[test]
+fn estimate_test() {
+    let source = "OPENQASM 2.0;
+        qreg q[2];
+        creg c[1];
+        gate g(t) a, b { U(t, 0, 0) a; CX a, b; U(0, t/2, -t/2) b; }
+        g(pi/2) q[0], q[1];
+        U(pi, pi/4, pi/2) q[0];
+        U(pi/3, 2^2*pi/8, 0.25*pi) q[1];
+        measure q[0] -> c[0];
+        if(c==1) U(0.1, sin(0), 2*pi) q[1];";
+    let program = ::parse_str(source, ::std::path::Path::new("")).unwrap();
+    let estimate = Estimate::from_program(&program).unwrap();
+
+    let classes: Vec<_> = estimate.gates.iter().map(|g| g.1).collect();
+    assert_eq!(
+        classes,
+        vec![
+            GateClass::Clifford,
+            GateClass::Clifford,
+            GateClass::Clifford,
+            GateClass::T(1),
+            GateClass::Arbitrary { t: 1, rotations: 1 },
+            GateClass::Arbitrary { t: 0, rotations: 2 },
+        ]
+    );
+    assert_eq!(
+        (estimate.clifford, estimate.t_gates, estimate.arbitrary),
+        (3, 1, 2)
+    );
+    assert_eq!((estimate.t_count, estimate.rotations), (2, 3));
+    assert_eq!(estimate.t_depth, 1);
+
+    let opaque = ::parse_str(
+        "OPENQASM 2.0; qreg q[1]; opaque o a; o q[0];",
+        ::std::path::Path::new(""),
+    )
+    .unwrap();
+    assert_eq!(
+        Estimate::from_program(&opaque),
+        Err(Error::Unsupported("opaque o".to_string()))
+    );
+}
@@ -0,0 +1,283 @@
+//! Exact arithmetic on angles that are a rational number plus a rational
+//! multiple of pi, such as `pi/4` or `3*pi/2 + 1`.
+
+use expr::{Expr, Op};
+use std::convert::TryFrom;
+
+/// A rational number, in lowest terms, with a positive denominator.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Ratio {
+    pub num: i64,
+    pub den: i64,
+}
+
+impl Ratio {
+    pub const ZERO: Ratio = Ratio { num: 0, den: 1 };
+
+    pub fn new(num: i64, den: i64) -> Option<Ratio> {
+        if den == 0 {
+            return None;
+        }
+        let gcd = gcd(num, den)?;
+        let sign = if den < 0 { -1 } else { 1 };
+        Some(Ratio {
+            num: (num / gcd).checked_mul(sign)?,
+            den: (den / gcd).checked_mul(sign)?,
+        })
+    }
+
+    pub fn add(self, other: Ratio) -> Option<Ratio> {
+        Ratio::new(
+            self.num
+                .checked_mul(other.den)?
+                .checked_add(other.num.checked_mul(self.den)?)?,
+            self.den.checked_mul(other.den)?,
+        )
+    }
+
+    pub fn neg(self) -> Option<Ratio> {
+        Some(Ratio {
+            num: self.num.checked_neg()?,
+            den: self.den,
+        })
+    }
+
+    pub fn mul(self, other: Ratio) -> Option<Ratio> {
+        Ratio::new(
+            self.num.checked_mul(other.num)?,
+            self.den.checked_mul(other.den)?,
+        )
+    }
+
+    pub fn div(self, other: Ratio) -> Option<Ratio> {
+        Ratio::new(
+            self.num.checked_mul(other.den)?,
+            self.den.checked_mul(other.num)?,
+        )
+    }
+
+    /// The exact value of a float, if it is a fraction with a denominator of
+    /// at most 2^32.
+    pub fn from_f64(value: f64) -> Option<Ratio> {
+        let mut den: i64 = 1;
+        while den <= 1 << 32 {
+            let scaled = value * den as f64;
+            if scaled.fract() == 0.0 && scaled.abs() < (1u64 << 53) as f64 {
+                return Ratio::new(scaled as i64, den);
+            }
+            den *= 2;
+        }
+        None
+    }
+
+    /// The value as an integer, if it is one.
+    pub fn integer(self) -> Option<i64> {
+        if self.den == 1 {
+            Some(self.num)
+        } else {
+            None
+        }
+    }
+}
+
+/// The greatest common divisor, or `None` when it's 2^63, which doesn't fit.
+fn gcd(a: i64, b: i64) -> Option<i64> {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    i64::try_from(a.max(1)).ok()
+}
+
+/// A number `a + b pi`, where `a` and `b` are rational.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Exact {
+    pub a: Ratio,
+    pub b: Ratio,
+}
+
+impl Exact {
+    pub fn add(self, other: Exact) -> Option<Exact> {
+        Some(Exact {
+            a: self.a.add(other.a)?,
+            b: self.b.add(other.b)?,
+        })
+    }
+
+    pub fn neg(self) -> Option<Exact> {
+        Some(Exact {
+            a: self.a.neg()?,
+            b: self.b.neg()?,
+        })
+    }
+
+    pub fn sub(self, other: Exact) -> Option<Exact> {
+        self.add(other.neg()?)
+    }
+
+    /// The rational number, if the value has no multiple of pi.
+    pub fn rational(self) -> Option<Ratio> {
+        if self.b == Ratio::ZERO {
+            Some(self.a)
+        } else {
+            None
+        }
+    }
+
+    /// The value in multiples of pi, if it is an integer multiple.
+    pub fn half_turns(self) -> Option<i64> {
+        if self.a == Ratio::ZERO {
+            self.b.integer()
+        } else {
+            None
+        }
+    }
+
+    /// The value in multiples of pi/4, if it is an integer multiple.
+    pub fn quarter_turns(self) -> Option<i64> {
+        if self.a == Ratio::ZERO {
+            self.b.mul(Ratio::new(4, 1)?)?.integer()
+        } else {
+            None
+        }
+    }
+}
+
+/// Evaluates an expression exactly, if its value is a rational number plus a
+/// rational multiple of pi, without overflowing.
+pub fn exact(expr: &Expr) -> Option<Exact> {
+    let rational = |a| Some(Exact { a, b: Ratio::ZERO });
+    match *expr {
+        Expr::Int(n) => rational(Ratio::new(i64::from(n), 1)?),
+        Expr::Real(x) => rational(Ratio::from_f64(x)?),
+        Expr::Pi => Some(Exact {
+            a: Ratio::ZERO,
+            b: Ratio::new(1, 1)?,
+        }),
+        Expr::Neg(ref e) => exact(e)?.neg(),
+        Expr::Binary(op, ref l, ref r) => {
+            let (l, r) = (exact(l)?, exact(r)?);
+            match op {
+                Op::Add => l.add(r),
+                Op::Sub => l.sub(r),
+                Op::Mul => match (l.rational(), r.rational()) {
+                    (Some(l), _) => Some(Exact {
+                        a: r.a.mul(l)?,
+                        b: r.b.mul(l)?,
+                    }),
+                    (_, Some(r)) => Some(Exact {
+                        a: l.a.mul(r)?,
+                        b: l.b.mul(r)?,
+                    }),
+                    _ => None,
+                },
+                Op::Div => {
+                    let r = r.rational()?;
+                    Some(Exact {
+                        a: l.a.div(r)?,
+                        b: l.b.div(r)?,
+                    })
+                }
+                Op::Pow => {
+                    let (base, exponent) = (l.rational()?, r.rational()?.integer()?);
+                    if exponent.abs() > 64 {
+                        return None;
+                    }
+                    let mut power = Ratio::new(1, 1)?;
+                    for _ in 0..exponent.abs() {
+                        power = power.mul(base)?;
+                    }
+                    if exponent < 0 {
+                        power = Ratio::new(1, 1)?.div(power)?;
+                    }
+                    rational(power)
+                }
+            }
+        }
+        Expr::Id(_) | Expr::Call(..) => None,
+    }
+}
+
+impl Exact {
+    /// The value as an expression, such as `3*pi/4` or `1/2 - pi`, if its
+    /// numerators and denominators fit in an integer literal.
+    pub fn to_expr(self) -> Option<Expr> {
+        let pi = match (self.b.num.abs(), self.b.den) {
+            (0, _) => None,
+            (1, 1) => Some(Expr::Pi),
+            (1, den) => Some(binary(Op::Div, Expr::Pi, int(den)?)),
+            (num, 1) => Some(binary(Op::Mul, int(num)?, Expr::Pi)),
+            (num, den) => Some(binary(
+                Op::Div,
+                binary(Op::Mul, int(num)?, Expr::Pi),
+                int(den)?,
+            )),
+        };
+        let rational = match (self.a.num.abs(), self.a.den) {
+            (0, _) => None,
+            (num, 1) => Some(int(num)?),
+            (num, den) => Some(binary(Op::Div, int(num)?, int(den)?)),
+        };
+        Some(match (rational, pi) {
+            (None, None) => Expr::Int(0),
+            (Some(e), None) | (None, Some(e)) if self.a.num < 0 || self.b.num < 0 => {
+                Expr::Neg(Box::new(e))
+            }
+            (Some(e), None) | (None, Some(e)) => e,
+            (Some(a), Some(b)) => {
+                let a = if self.a.num < 0 {
+                    Expr::Neg(Box::new(a))
+                } else {
+                    a
+                };
+                let op = if self.b.num < 0 { Op::Sub } else { Op::Add };
+                binary(op, a, b)
+            }
+        })
+    }
+}
+
+fn int(n: i64) -> Option<Expr> {
+    if n >= 0 && n <= i64::from(i32::MAX) {
+        Some(Expr::Int(n as i32))
+    } else {
+        None
+    }
+}
+
+fn binary(op: Op, l: Expr, r: Expr) -> Expr {
+    Expr::Binary(op, Box::new(l), Box::new(r))
+}
+
+#[test]
+fn exact_test() {
+    let quarters = |expr| exact(&Expr::parse(expr).unwrap()).and_then(|e| e.quarter_turns());
+    assert_eq!(quarters("-3*pi/4 + 2*pi"), Some(5));
+    assert_eq!(quarters("pi/4 + 0.5 - 1/2"), Some(1));
+    assert_eq!(quarters("2^2*pi/8"), Some(2));
+    assert_eq!(quarters("pi*pi"), None);
+    assert_eq!(quarters("0.785398"), None);
+    assert_eq!(quarters("pi/0"), None);
+    assert_eq!(quarters("(-2)^63*pi"), None);
+    assert_eq!(
+        Ratio::new(i64::MIN, 2),
+        Some(Ratio {
+            num: i64::MIN / 2,
+            den: 1
+        })
+    );
+
+    let simplify = |expr| {
+        exact(&Expr::parse(expr).unwrap())
+            .and_then(|e| e.to_expr())
+            .map(|e| e.to_string())
+    };
+    assert_eq!(simplify("pi/4 + pi/4"), Some("pi/2".to_string()));
+    assert_eq!(simplify("-pi/4 - pi/2"), Some("-(3*pi/4)".to_string()));
+    assert_eq!(simplify("pi - pi"), Some("0".to_string()));
+    assert_eq!(simplify("0.5 - 2*pi"), Some("1/2-2*pi".to_string()));
+    assert_eq!(simplify("-1.5"), Some("-(3/2)".to_string()));
+    assert_eq!(simplify("sin(pi)"), None);
+}
@@ -0,0 +1,369 @@
+//! Mathematical expressions, as used for gate parameters.
+//!
+//! The parser keeps parameters as strings. They can be turned into an
+//! [Expr](enum.Expr.html) with `Expr::parse`, then evaluated or rewritten.
+
+use error::Error;
+use lexer::Lexer;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::iter::Peekable;
+use std::result;
+use token::Token;
+
+type Result<T> = result::Result<T, Error>;
+
+/// A binary operator in an expression.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Op {
+    /// Addition, `+`
+    Add,
+    /// Subtraction, `-`
+    Sub,
+    /// Multiplication, `*`
+    Mul,
+    /// Division, `/`
+    Div,
+    /// Exponentiation, `^`
+    Pow,
+}
+
+/// A unary function in an expression.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Ln,
+    Sqrt,
+}
+
+/// A parsed mathematical expression.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::Expr;
+///
+/// let expr = Expr::parse(" pi / 2 ").unwrap();
+/// assert_eq!(expr.eval().unwrap(), std::f64::consts::PI / 2.0);
+/// assert_eq!(expr.to_string(), "pi/2");
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    /// A real number
+    Real(f64),
+    /// A non negative integer
+    Int(i32),
+    /// The constant pi
+    Pi,
+    /// A parameter of a gate
+    Id(String),
+    /// Unary minus
+    Neg(Box<Expr>),
+    /// A binary operation
+    Binary(Op, Box<Expr>, Box<Expr>),
+    /// A function applied to an expression
+    Call(Function, Box<Expr>),
+}
+
+impl Expr {
+    /// Parses an expression, such as a parameter of an `AstNode`.
+    ///
+    /// This follows the OpenQASM 2.0 grammar, where `^` binds tightest
+    /// and is right associative, followed by unary minus, then `*` and `/`,
+    /// then `+` and `-`.
+    pub fn parse(source: &str) -> Result<Expr> {
+        let invalid = || Error::InvalidExpression(source.trim().to_string());
+        let mut tokens = Lexer::new(source).peekable();
+
+        let expr = expression(&mut tokens).ok_or_else(invalid)?;
+        match tokens.next() {
+            None => Ok(expr),
+            Some(_) => Err(invalid()),
+        }
+    }
+
+    /// Evaluates an expression that has no parameters.
+    ///
+    /// Returns an `UndefinedParameter` error if it has any.
+    pub fn eval(&self) -> Result<f64> {
+        self.eval_with(&HashMap::new())
+    }
+
+    /// Evaluates an expression, looking up any parameters by name.
+    pub fn eval_with(&self, values: &HashMap<String, f64>) -> Result<f64> {
+        Ok(match *self {
+            Expr::Real(n) => n,
+            Expr::Int(n) => f64::from(n),
+            Expr::Pi => PI,
+            Expr::Id(ref id) => *values
+                .get(id)
+                .ok_or_else(|| Error::UndefinedParameter(id.clone()))?,
+            Expr::Neg(ref e) => -e.eval_with(values)?,
+            Expr::Binary(op, ref l, ref r) => {
+                let (l, r) = (l.eval_with(values)?, r.eval_with(values)?);
+                match op {
+                    Op::Add => l + r,
+                    Op::Sub => l - r,
+                    Op::Mul => l * r,
+                    Op::Div => l / r,
+                    Op::Pow => l.powf(r),
+                }
+            }
+            Expr::Call(f, ref e) => {
+                let e = e.eval_with(values)?;
+                match f {
+                    Function::Sin => e.sin(),
+                    Function::Cos => e.cos(),
+                    Function::Tan => e.tan(),
+                    Function::Exp => e.exp(),
+                    Function::Ln => e.ln(),
+                    Function::Sqrt => e.sqrt(),
+                }
+            }
+        })
+    }
+
+    /// Replaces parameters with expressions. Parameters that aren't
+    /// in the map are left as they are.
+    pub fn substitute(&self, values: &HashMap<String, Expr>) -> Expr {
+        match *self {
+            Expr::Id(ref id) => values.get(id).cloned().unwrap_or_else(|| self.clone()),
+            Expr::Neg(ref e) => Expr::Neg(Box::new(e.substitute(values))),
+            Expr::Binary(op, ref l, ref r) => Expr::Binary(
+                op,
+                Box::new(l.substitute(values)),
+                Box::new(r.substitute(values)),
+            ),
+            Expr::Call(f, ref e) => Expr::Call(f, Box::new(e.substitute(values))),
+            _ => self.clone(),
+        }
+    }
+
+    /// The names of every parameter used in the expression.
+    pub fn ids(&self) -> Vec<&str> {
+        match *self {
+            Expr::Id(ref id) => vec![id],
+            Expr::Neg(ref e) | Expr::Call(_, ref e) => e.ids(),
+            Expr::Binary(_, ref l, ref r) => {
+                let mut ids = l.ids();
+                ids.extend(r.ids());
+                ids
+            }
+            _ => vec![],
+        }
+    }
+
+    /// How tightly the expression binds, used to decide on parentheses when printing.
+    fn precedence(&self) -> u8 {
+        match *self {
+            Expr::Binary(Op::Add, ..) | Expr::Binary(Op::Sub, ..) => 1,
+            Expr::Binary(Op::Mul, ..) | Expr::Binary(Op::Div, ..) => 2,
+            Expr::Neg(_) => 3,
+            Expr::Binary(Op::Pow, ..) => 4,
+            _ => 5,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Op::Add => write!(f, "+"),
+            Op::Sub => write!(f, "-"),
+            Op::Mul => write!(f, "*"),
+            Op::Div => write!(f, "/"),
+            Op::Pow => write!(f, "^"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Function::Sin => write!(f, "sin"),
+            Function::Cos => write!(f, "cos"),
+            Function::Tan => write!(f, "tan"),
+            Function::Exp => write!(f, "exp"),
+            Function::Ln => write!(f, "ln"),
+            Function::Sqrt => write!(f, "sqrt"),
+        }
+    }
+}
+
+/// The spelling of the parts of an expression that differ between languages.
+pub(crate) struct Syntax {
+    pub pow: &'static str,
+    pub ln: &'static str,
+    /// Written before each identifier
+    pub id_prefix: &'static str,
+}
+
+impl Syntax {
+    pub const QASM2: Syntax = Syntax {
+        pow: "^",
+        ln: "ln",
+        id_prefix: "",
+    };
+
+    pub const QASM3: Syntax = Syntax {
+        pow: "**",
+        ln: "log",
+        id_prefix: "",
+    };
+}
+
+impl Expr {
+    /// Writes the expression with only the parentheses that are needed.
+    pub(crate) fn write<W: fmt::Write>(&self, w: &mut W, syntax: &Syntax) -> fmt::Result {
+        let wrap = |w: &mut W, e: &Expr, min: u8| {
+            if e.precedence() < min {
+                w.write_char('(')?;
+                e.write(w, syntax)?;
+                w.write_char(')')
+            } else {
+                e.write(w, syntax)
+            }
+        };
+
+        match *self {
+            Expr::Real(n) => write!(w, "{:?}", n),
+            Expr::Int(n) => write!(w, "{}", n),
+            Expr::Pi => write!(w, "pi"),
+            Expr::Id(ref id) => write!(w, "{}{}", syntax.id_prefix, id),
+            Expr::Neg(ref e) => {
+                w.write_char('-')?;
+                wrap(w, e, 3)
+            }
+            Expr::Binary(op, ref l, ref r) => {
+                let p = self.precedence();
+                // Left associative operators need parentheses on the right for equal
+                // precedence. `^` is right associative, and its exponent can be negated.
+                let (left_min, right_min) = if op == Op::Pow { (p + 1, 3) } else { (p, p + 1) };
+                wrap(w, l, left_min)?;
+                match op {
+                    Op::Pow => w.write_str(syntax.pow)?,
+                    _ => write!(w, "{}", op)?,
+                }
+                wrap(w, r, right_min)
+            }
+            Expr::Call(Function::Ln, ref e) => {
+                write!(w, "{}(", syntax.ln)?;
+                e.write(w, syntax)?;
+                w.write_char(')')
+            }
+            Expr::Call(func, ref e) => {
+                write!(w, "{}(", func)?;
+                e.write(w, syntax)?;
+                w.write_char(')')
+            }
+        }
+    }
+
+    /// Prints the expression in the given syntax.
+    pub(crate) fn to_string_with(&self, syntax: &Syntax) -> String {
+        let mut s = String::new();
+        self.write(&mut s, syntax).unwrap();
+        s
+    }
+}
+
+/// Prints the expression as OpenQASM, with only the parentheses that are needed.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &Syntax::QASM2)
+    }
+}
+
+type Tokens<'a> = Peekable<Lexer<'a>>;
+
+fn expression(tokens: &mut Tokens) -> Option<Expr> {
+    let mut lhs = term(tokens)?;
+    loop {
+        let op = match tokens.peek() {
+            Some(&Token::Plus) => Op::Add,
+            Some(&Token::Minus) => Op::Sub,
+            _ => return Some(lhs),
+        };
+        tokens.next();
+        lhs = Expr::Binary(op, Box::new(lhs), Box::new(term(tokens)?));
+    }
+}
+
+fn term(tokens: &mut Tokens) -> Option<Expr> {
+    let mut lhs = unary(tokens)?;
+    loop {
+        let op = match tokens.peek() {
+            Some(&Token::Times) => Op::Mul,
+            Some(&Token::Divide) => Op::Div,
+            _ => return Some(lhs),
+        };
+        tokens.next();
+        lhs = Expr::Binary(op, Box::new(lhs), Box::new(unary(tokens)?));
+    }
+}
+
+fn unary(tokens: &mut Tokens) -> Option<Expr> {
+    if let Some(&Token::Minus) = tokens.peek() {
+        tokens.next();
+        return Some(Expr::Neg(Box::new(unary(tokens)?)));
+    }
+    power(tokens)
+}
+
+fn power(tokens: &mut Tokens) -> Option<Expr> {
+    let base = atom(tokens)?;
+    if let Some(&Token::Power) = tokens.peek() {
+        tokens.next();
+        return Some(Expr::Binary(Op::Pow, Box::new(base), Box::new(unary(tokens)?)));
+    }
+    Some(base)
+}
+
+fn atom(tokens: &mut Tokens) -> Option<Expr> {
+    let function = match tokens.next()? {
+        Token::Real(n) => return Some(Expr::Real(n)),
+        Token::NNInteger(n) => return Some(Expr::Int(n)),
+        Token::Pi => return Some(Expr::Pi),
+        Token::Id(id) => return Some(Expr::Id(id.to_string())),
+        Token::LParen => {
+            let e = expression(tokens)?;
+            return match tokens.next()? {
+                Token::RParen => Some(e),
+                _ => None,
+            };
+        }
+        Token::Sin => Function::Sin,
+        Token::Cos => Function::Cos,
+        Token::Tan => Function::Tan,
+        Token::Exp => Function::Exp,
+        Token::Ln => Function::Ln,
+        Token::Sqrt => Function::Sqrt,
+        _ => return None,
+    };
+
+    match (tokens.next()?, expression(tokens)?, tokens.next()?) {
+        (Token::LParen, e, Token::RParen) => Some(Expr::Call(function, Box::new(e))),
+        _ => None,
+    }
+}
+
+#[test]
+fn expr_test() {
+    let expr = Expr::parse("-(lambda+phi)/2 * 2^-1^2").unwrap();
+    assert_eq!(expr.to_string(), "-(lambda+phi)/2*2^-1^2");
+    assert_eq!(expr.ids(), vec!["lambda", "phi"]);
+
+    let mut values = HashMap::new();
+    values.insert("lambda".to_string(), 1.0);
+    values.insert("phi".to_string(), 3.0);
+    assert_eq!(expr.eval_with(&values), Ok(-1.0));
+    assert_eq!(expr.eval(), Err(Error::UndefinedParameter("lambda".to_string())));
+
+    assert_eq!(Expr::parse("-2^2").unwrap().eval(), Ok(-4.0));
+    assert_eq!(Expr::parse("1.5e-1").unwrap(), Expr::Real(0.15));
+    assert!(Expr::parse("1 2").is_err());
+    assert!(Expr::parse("sin 2").is_err());
+}
@@ -1,26 +1,65 @@
 use token;
 use token::Token;
 
-use std::str::Chars;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
 use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::CharIndices;
 
+/// An iterator over the tokens of a source string.
+///
+/// Identifiers are returned as slices of the source string, so lexing
+/// does not allocate. The tokens can be passed directly to
+/// [parse_tokens](fn.parse_tokens.html), or converted to owned tokens
+/// with [Token::into_owned](enum.Token.html#method.into_owned).
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{Lexer, Token};
+///
+/// let tokens: Vec<Token<&str>> = Lexer::new("qreg a[3];").collect();
+/// assert_eq!(tokens[1], Token::Id("a"));
+/// ```
 pub struct Lexer<'a> {
-    input: Peekable<Chars<'a>>,
+    source: &'a str,
+    input: Peekable<CharIndices<'a>>,
+    qasm3: bool,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(input: &str) -> Lexer {
+    pub fn new(input: &'a str) -> Lexer<'a> {
         Lexer {
-            input: input.chars().peekable(),
+            source: input,
+            input: input.char_indices().peekable(),
+            qasm3: false,
+        }
+    }
+
+    /// A lexer for OpenQASM 3, which also returns its reserved words,
+    /// such as `qubit` and `for`, instead of identifiers.
+    pub fn new_qasm3(input: &'a str) -> Lexer<'a> {
+        Lexer {
+            qasm3: true,
+            ..Lexer::new(input)
         }
     }
 
     fn read_char(&mut self) -> Option<char> {
-        self.input.next()
+        self.input.next().map(|(_, ch)| ch)
     }
 
     fn peek_char(&mut self) -> Option<&char> {
-        self.input.peek()
+        self.input.peek().map(|(_, ch)| ch)
+    }
+
+    /// The byte offset of the next character, or the end of the source.
+    fn position(&mut self) -> usize {
+        match self.input.peek() {
+            Some(&(i, _)) => i,
+            None => self.source.len(),
+        }
     }
 
     fn peek_char_eq(&mut self, ch: char) -> bool {
@@ -39,6 +78,13 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    fn peek_is_digit(&mut self) -> bool {
+        match self.peek_char() {
+            Some(&ch) => ch.is_ascii_digit(),
+            None => false,
+        }
+    }
+
     fn peek_is_alphanumeric(&mut self) -> bool {
         match self.peek_char() {
             Some(&ch) => is_alphanumeric(ch),
@@ -46,44 +92,84 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn read_identifier(&mut self, first: char) -> String {
-        let mut ident = String::new();
-        ident.push(first);
-
+    fn read_identifier(&mut self, start: usize) -> &'a str {
         while self.peek_is_alphanumeric() {
-            ident.push(self.read_char().unwrap());
+            self.read_char();
         }
 
-        ident
+        &self.source[start..self.position()]
+    }
+
+    /// Returns `then` and reads the next character if it is `ch`, otherwise returns `otherwise`.
+    fn read_if(&mut self, ch: char, then: Token<&'a str>, otherwise: Token<&'a str>) -> Token<&'a str> {
+        if self.peek_char_eq(ch) {
+            self.read_char();
+            then
+        } else {
+            otherwise
+        }
     }
 
-    fn read_number(&mut self, first: char) -> String {
-        let mut number = String::new();
-        number.push(first);
+    /// Reads a string after its opening quote. An unterminated string is illegal.
+    fn read_string(&mut self) -> Token<&'a str> {
+        let start = self.position();
+        while let Some(c) = self.read_char() {
+            if c == '"' {
+                let end = self.position() - 1;
+                return Token::Str(&self.source[start..end]);
+            }
+        }
+        Token::Illegal
+    }
 
+    fn read_digits(&mut self) {
         while let Some(&c) = self.peek_char() {
-            if !c.is_numeric() && c != '.' {
+            if !c.is_ascii_digit() {
                 break;
             }
-            number.push(self.read_char().unwrap());
+            self.read_char();
         }
+    }
 
-        number
+    /// Reads an integer or a real, which can have an exponent such as `1.5e-3`.
+    fn read_number(&mut self, start: usize, first: char) -> &'a str {
+        self.read_digits();
+        if first != '.' && self.peek_char_eq('.') {
+            self.read_char();
+            self.read_digits();
+        }
+
+        // Only take the exponent if it has digits, so `2e` is a number then an identifier
+        let mut exponent = self.input.clone();
+        if let Some((_, 'e')) | Some((_, 'E')) = exponent.next() {
+            if let Some(&(_, '+')) | Some(&(_, '-')) = exponent.peek() {
+                exponent.next();
+            }
+            if let Some(&(_, c)) = exponent.peek() {
+                if c.is_ascii_digit() {
+                    self.input = exponent;
+                    self.read_digits();
+                }
+            }
+        }
+
+        &self.source[start..self.position()]
     }
 
-    pub fn next_token(&mut self) -> Token {
+    pub fn next_token(&mut self) -> Token<&'a str> {
         self.skip_whitespace();
 
+        let start = self.position();
         match self.read_char() {
-            Some('=') => {
-                if self.peek_char_eq('=') {
-                    self.read_char();
-                    Token::Equals
-                } else {
-                    // Shouldn't Be A Single Equals!
-                    panic!("Error: Missing Charachter. Expected another `=`.");
-                }
-            }
+            Some('=') => self.read_if('=', Token::Equals, Token::Assign),
+            Some('!') => self.read_if('=', Token::NotEquals, Token::Not),
+            Some('<') => self.read_if('=', Token::LessEquals, Token::Less),
+            Some('>') => self.read_if('=', Token::GreaterEquals, Token::Greater),
+            Some('&') => self.read_if('&', Token::And, Token::Illegal),
+            Some('|') => self.read_if('|', Token::Or, Token::Illegal),
+            Some('@') => Token::At,
+            Some(':') => Token::Colon,
+            Some('"') => self.read_string(),
             Some('+') => Token::Plus,
             Some('-') => {
                 if self.peek_char_eq('>') {
@@ -93,8 +179,11 @@ impl<'a> Lexer<'a> {
                     Token::Minus
                 }
             }
+            // OpenQASM 3 writes powers as `**`, and uses `^` for xor
+            Some('*') if self.qasm3 => self.read_if('*', Token::Power, Token::Times),
             Some('*') => Token::Times,
             Some('/') => Token::Divide,
+            Some('^') if self.qasm3 => Token::Illegal,
             Some('^') => Token::Power,
             Some(';') => Token::Semicolon,
             Some(',') => Token::Comma,
@@ -106,16 +195,19 @@ impl<'a> Lexer<'a> {
             Some('}') => Token::RCParen,
             Some(ch) => {
                 if is_letter(ch) {
-                    let literal = self.read_identifier(ch);
-                    token::lookup_ident(&literal)
-                } else if ch.is_numeric() {
-                    let num_str = self.read_number(ch);
-                    if num_str.contains('.') {
-                        let num = num_str.parse::<f32>().unwrap();
-                        Token::Real(num)
+                    let literal = self.read_identifier(start);
+                    if self.qasm3 {
+                        token::lookup_ident_qasm3(literal)
                     } else {
-                        let num = num_str.parse::<i32>().unwrap();
-                        Token::NNInteger(num)
+                        token::lookup_ident(literal)
+                    }
+                } else if ch.is_ascii_digit() || (ch == '.' && self.peek_is_digit()) {
+                    let num_str = self.read_number(start, ch);
+                    if num_str.contains(&['.', 'e', 'E'][..]) {
+                        num_str.parse::<f64>().map(Token::Real).unwrap_or(Token::Illegal)
+                    } else {
+                        // Integers too large for an i32 are illegal
+                        num_str.parse::<i32>().map(Token::NNInteger).unwrap_or(Token::Illegal)
                     }
                 } else {
                     Token::Illegal
@@ -129,8 +221,8 @@ impl<'a> Lexer<'a> {
 }
 
 impl<'a> Iterator for Lexer<'a> {
-    type Item = Token;
-    fn next(&mut self) -> Option<Token> {
+    type Item = Token<&'a str>;
+    fn next(&mut self) -> Option<Token<&'a str>> {
         let tok = self.next_token();
         if tok == Token::EndOfFile {
             None
@@ -140,6 +232,63 @@ impl<'a> Iterator for Lexer<'a> {
     }
 }
 
+/// An iterator over the tokens of a source that is read one line at a time.
+///
+/// Comments are removed as each line is read. Include statements are not
+/// resolved, so the source must not contain any.
+///
+/// If reading fails, the tokens end early. The error is kept, and is
+/// reported by the [Statements](struct.Statements.html) parsing them.
+pub struct TokenReader<R> {
+    reader: R,
+    line: String,
+    tokens: VecDeque<Token>,
+    error: Rc<Cell<Option<io::ErrorKind>>>,
+}
+
+impl<R: BufRead> TokenReader<R> {
+    pub fn new(reader: R) -> TokenReader<R> {
+        TokenReader {
+            reader,
+            line: String::new(),
+            tokens: VecDeque::new(),
+            error: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /// A handle to the error that stopped reading, if there was one.
+    pub(crate) fn error(&self) -> Rc<Cell<Option<io::ErrorKind>>> {
+        self.error.clone()
+    }
+}
+
+impl<R: BufRead> Iterator for TokenReader<R> {
+    type Item = Token;
+    fn next(&mut self) -> Option<Token> {
+        while self.tokens.is_empty() {
+            if self.error.get().is_some() {
+                return None;
+            }
+
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    let code = match self.line.find("//") {
+                        Some(i) => &self.line[..i],
+                        None => &self.line[..],
+                    };
+                    self.tokens.extend(Lexer::new(code).map(Token::into_owned));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => self.error.set(Some(e.kind())),
+            }
+        }
+
+        self.tokens.pop_front()
+    }
+}
+
 fn is_letter(ch: char) -> bool {
     ch.is_alphabetic() || ch == '_'
 }
@@ -147,6 +296,53 @@ fn is_alphanumeric(ch: char) -> bool {
     ch.is_alphanumeric() || ch == '_'
 }
 
+#[test]
+fn borrowed_identifier_test() {
+    let source = "qreg abc[3];";
+    let tokens: Vec<_> = Lexer::new(source).collect();
+    assert_eq!(tokens[1], Token::Id("abc"));
+    assert_eq!(tokens[3], Token::NNInteger(3));
+}
+
+#[test]
+fn number_test() {
+    let tokens: Vec<_> = Lexer::new("2 2.0 .5 1.5e-3 2E+2 3e 99999999999").collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::NNInteger(2),
+            Token::Real(2.0),
+            Token::Real(0.5),
+            Token::Real(1.5e-3),
+            Token::Real(200.0),
+            Token::NNInteger(3),
+            Token::Id("e"),
+            Token::Illegal,
+        ]
+    );
+}
+
+#[test]
+fn qasm3_test() {
+    let tokens: Vec<_> = Lexer::new_qasm3("ctrl @ x; c = a != b; \"std.inc\"").collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Ctrl,
+            Token::At,
+            Token::Id("x"),
+            Token::Semicolon,
+            Token::Id("c"),
+            Token::Assign,
+            Token::Id("a"),
+            Token::NotEquals,
+            Token::Id("b"),
+            Token::Semicolon,
+            Token::Str("std.inc"),
+        ]
+    );
+}
+
 #[test]
 fn is_letter_test() {
     assert!(is_letter('_'));
@@ -7,7 +7,11 @@
 //! 2. Lexing - Splitting up the processed source file into a list of tokens (`Vec<Token>`)
 //! 3. Parsing - Turning the list of tokens into a list of AST nodes.
 //!
-//! There is methods provided for each.
+//! There is methods provided for each, as well as [parse_str](fn.parse_str.html) and
+//! [parse_file](fn.parse_file.html), which do all 3 and return a [Program](struct.Program.html).
+//!
+//! OpenQASM 3 programs can be parsed with the [qasm3](qasm3/index.html) module, and
+//! programs can be exported to Quil with the [quil](quil/index.html) module.
 //!
 //! ## Processing
 //!
@@ -70,6 +74,9 @@
 //!
 //! for a full list of tokens that can be returned, please see the [Token](enum.Token.html) enum.
 //!
+//! The [Lexer](struct.Lexer.html) can also be used directly as an iterator. Its tokens borrow
+//! their identifiers from the source string, so no allocation is done while lexing.
+//!
 //! ## Parsing
 //! Parsing is done with the [parse](fn.parse.html) function. It accepts a vector of [Token](enum.Tokem.html)s
 //! and returns a vector of [AstNode](enum.AstNode.html)s or an [Error](enum.Error.html) as a result
@@ -104,7 +111,20 @@
 //! ];
 //! let ast = qasm::parse(&mut tokens);
 //!
-//! // Ok([QReg("a", 3), ApplyGate("CX", [Qubit("a", 0), Qubit("a", 1)], [])])
+//! // Ok([QReg("a", 3), CX(Qubit("a", 0), Qubit("a", 1))])
+//! ```
+//!
+//! For large programs, the lexer can be passed straight to the parser with
+//! [parse_tokens](fn.parse_tokens.html). This avoids building the intermediate
+//! vector of tokens, and copying every identifier:
+//!
+//! ```rust
+//! extern crate qasm;
+//! use qasm::{parse_tokens, Lexer};
+//!
+//! let source = "OPENQASM 2.0; qreg a[3]; CX a[0], a[1];";
+//! let ast = parse_tokens(Lexer::new(source));
+//! assert!(ast.is_ok());
 //! ```
 //!
 //! ## Combining Functions
@@ -161,30 +181,124 @@
 //!     QReg("q", 2),
 //!     CReg("c", 1),
 //!     ApplyGate("h", [Qubit("q", 0)], []),
-//!     ApplyGate("CX", [Qubit("q", 0), Qubit("q", 1)], []),
+//!     CX(Qubit("q", 0), Qubit("q", 1)),
 //!     Measure(Qubit("q", 1), Qubit("c", 1))
 //! ])
 //! ```
+//!
+//! ## Parsing Files
+//! The same can be done in one step with [parse_file](fn.parse_file.html). This resolves the
+//! includes relative to the file, and returns a [Program](struct.Program.html), which has the
+//! registers, gate definitions and statements separated. The program can then be checked for
+//! errors such as undefined registers:
+//!
+//! ```no_run
+//! extern crate qasm;
+//!
+//! fn main() {
+//!     match qasm::parse_file("test.qasm").and_then(|p| p.check().map(|_| p)) {
+//!         Ok(program) => println!("{:?}", program.statements()),
+//!         Err(e) => println!("Got an error: {}", e),
+//!     }
+//! }
+//! ```
+//!
+//! ## Serialization
+//!
+//! With the `serde` feature enabled, `AstNode`, `Argument`, `Token` and `Error` implement
+//! `Serialize` and `Deserialize`. The shape is stable: every enum is tagged with a `"type"`
+//! field, and the values of AST nodes, arguments and errors have named fields. For example,
+//! `CX q[0], q[1];` becomes:
+//!
+//! ```text
+//! {
+//!   "type": "CX",
+//!   "control": { "type": "Qubit", "register": "q", "index": 0 },
+//!   "target": { "type": "Qubit", "register": "q", "index": 1 }
+//! }
+//! ```
+//!
+//! The fields of each node are `QReg`/`CReg` `{name, size}`, `Barrier` `{qubits}`,
+//! `Reset` `{qubit}`, `Measure` `{qubit, bit}`, `U` `{qubit, params}`, `CX` `{control, target}`,
+//! `ApplyGate`/`Opaque` `{name, qubits, params}`, `Gate` `{name, qubits, params, body}` and
+//! `If` `{register, value, statement}`. Arguments are `Qubit` `{register, index}` or
+//! `Register` `{register}`. Tokens keep their value in a `"value"` field, such as
+//! `{"type": "Id", "value": "q"}`, and errors hold a `name`, or `file`, `expression`,
+//! `construct`, `kind` or `index` where that describes the value better.
+//! The `kind` of an `IoError` is the name of a common `std::io::ErrorKind`, such as
+//! `"NotFound"`, and any other kind is written, and so read back, as `"Other"`.
 extern crate regex;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "qobj")]
+extern crate serde_json;
 
 mod token;
 mod lexer;
 mod error;
 mod parser;
 mod ast;
+mod program;
+mod semantic;
+mod expr;
+mod strict;
+mod circuit;
+mod dag;
+mod stats;
+mod estimate;
+mod exact;
+pub mod draw;
+pub mod cirq;
+pub mod qir;
+mod matrix;
+pub mod qasm3;
+pub mod quil;
+pub mod optimize;
+pub mod basis;
+#[cfg(feature = "qobj")]
+pub mod qobj;
+#[cfg(feature = "serde")]
+mod serialize;
 
 use std::fs::File;
+use std::io::BufReader;
 use std::io::prelude::*;
-use regex::{Captures, Regex};
+use regex::Regex;
 use std::path::Path;
+use std::sync::OnceLock;
 
 pub use error::Error;
+pub use expr::{Expr, Function, Op};
 pub use ast::Argument;
 pub use ast::AstNode;
+pub use circuit::{Circuit, Condition, Instruction, Operation};
+pub use dag::{Dag, NodeIndex, Wire};
+pub use stats::Stats;
+pub use estimate::{Estimate, GateClass};
+pub use lexer::{Lexer, TokenReader};
+pub use parser::Statements;
+pub use program::{Gate, Opaque, Program, Register};
 pub use token::Token;
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// The source of the OpenQASM 2.0 standard library, `qelib1.inc`.
+pub const QELIB1: &str = include_str!("qelib1.inc");
+
+/// The gate definitions in `qelib1.inc`.
+pub(crate) fn qelib1_gates() -> Vec<AstNode> {
+    let comment_regex = Regex::new(r"//.*").unwrap();
+    let source = format!("OPENQASM 2.0;\n{}", comment_regex.replace_all(QELIB1, ""));
+    parse_tokens(Lexer::new(&source)).expect("qelib1.inc is valid")
+}
+
+/// The gates of `qelib1.inc` as a program, which is only built once.
+pub(crate) fn qelib1() -> &'static Program {
+    static QELIB1_PROGRAM: OnceLock<Program> = OnceLock::new();
+    QELIB1_PROGRAM.get_or_init(|| Program::from_ast(qelib1_gates()).expect("qelib1.inc is valid"))
+}
+
 /// Remove comments from an input string and resolves include statements.
 ///
 /// This function has 2 arguments, the input string, and the path that the file is in.
@@ -223,24 +337,74 @@ type Result<T> = std::result::Result<T, Error>;
 ///  */
 /// ```
 pub fn process(input: &str, cwd: &Path) -> String {
+    try_process(input, cwd).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Remove comments from an input string and resolves include statements,
+/// returning an error when an included file couldn't be read.
+///
+/// This works like [process](fn.process.html), but returns an `IncludeError`
+/// naming the file instead of panicking.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "missing.inc";
+/// "#;
+///
+/// let cwd = env::current_dir().unwrap();
+/// let result = qasm::try_process(source, &cwd);
+/// assert_eq!(result, Err(qasm::Error::IncludeError("missing.inc".to_string())));
+/// ```
+pub fn try_process(input: &str, cwd: &Path) -> Result<String> {
     let comment_regex = Regex::new(r"//.*").unwrap();
     let cleaned = comment_regex.replace_all(input, ""); // Removed All Comments
 
     let include_regex = Regex::new(r#"include\s*"(?P<s>.*)";"#).unwrap(); // Regex for include statments
 
-    let replace_with_file = |caps: &Captures| {
-        let path = cwd.join(&caps["s"]);
+    resolve_includes(&cleaned, &comment_regex, &include_regex, |file| {
+        read_file(&cwd.join(file)).map_err(|_| Error::IncludeError(file.to_string()))
+    })
+}
+
+/// Replaces each include statement in a source that has no comments with the
+/// contents of the file, read by `read`, with their comments removed.
+/// The file name is the `s` group of `include_regex`.
+pub(crate) fn resolve_includes<F>(
+    cleaned: &str,
+    comment_regex: &Regex,
+    include_regex: &Regex,
+    read: F,
+) -> Result<String>
+where
+    F: Fn(&str) -> Result<String>,
+{
+    let mut processed = String::new();
+    let mut last = 0;
+
+    for caps in include_regex.captures_iter(cleaned) {
+        let statement = caps.get(0).unwrap();
+        processed.push_str(&cleaned[last..statement.start()]);
 
-        let mut f = File::open(path).expect("Couldn't Open An Include File");
-        let mut contents = String::new();
-        f.read_to_string(&mut contents)
-            .expect("Couldn't Read Include Statement");
-        comment_regex.replace_all(&contents, "").into()
-    };
+        let contents = read(&caps["s"])?;
+        processed.push_str(&comment_regex.replace_all(&contents, ""));
 
-    let processed = include_regex.replace_all(&cleaned, replace_with_file); // Remove Includes
+        last = statement.end();
+    }
+    processed.push_str(&cleaned[last..]); // Remove Includes
+
+    Ok(processed)
+}
 
-    processed.into()
+fn read_file(path: &Path) -> std::io::Result<String> {
+    let mut f = File::open(path)?;
+    let mut contents = String::new();
+    f.read_to_string(&mut contents)?;
+    Ok(contents)
 }
 
 /// Take a source string with no includes or comments and returns the tokens
@@ -267,18 +431,7 @@ pub fn process(input: &str, cwd: &Path) -> String {
 /// //  Id("CX"), Id("a"), LSParen, NNInteger(0), RSParen, Comma, Id("a"), LSParen, NNInteger(1), RSParen, Semicolon]
 /// ```
 pub fn lex(input: &str) -> Vec<token::Token> {
-    let mut lexer = lexer::Lexer::new(input);
-    let mut tokens = vec![];
-
-    loop {
-        let tok = lexer.next_token();
-        if tok == token::Token::EndOfFile {
-            break;
-        }
-        tokens.push(tok);
-    }
-
-    tokens
+    Lexer::new(input).map(Token::into_owned).collect()
 }
 
 /// Changes a vector of tokens into an AST.
@@ -315,9 +468,203 @@ pub fn lex(input: &str) -> Vec<token::Token> {
 /// ];
 /// let ast = qasm::parse(&mut tokens);
 ///
-/// // Ok([QReg("a", 3), ApplyGate("CX", [Qubit("a", 0), Qubit("a", 1)], [])])
+/// // Ok([QReg("a", 3), CX(Qubit("a", 0), Qubit("a", 1))])
+/// ```
+pub fn parse(tokens: &[token::Token]) -> Result<Vec<AstNode>> {
+    parse_tokens(tokens.iter().map(Token::as_borrowed))
+}
+
+/// Parses any sequence of tokens into an AST.
+///
+/// This works like [parse](fn.parse.html), but consumes the tokens as it goes.
+/// This means a [Lexer](struct.Lexer.html) can be parsed without first
+/// collecting the tokens into a vector. The identifiers in the tokens can be
+/// owned (`Token<String>`) or borrowed (`Token<&str>`).
+///
+/// ## Example
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{parse_tokens, Lexer};
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// qreg a[3];
+/// CX a[0], a[1];
+/// "#;
+///
+/// let ast = parse_tokens(Lexer::new(source));
+///
+/// // Ok([QReg("a", 3), CX(Qubit("a", 0), Qubit("a", 1))])
+/// ```
+pub fn parse_tokens<S, I>(tokens: I) -> Result<Vec<AstNode>>
+where
+    S: AsRef<str>,
+    I: IntoIterator<Item = Token<S>>,
+{
+    parser::parse(tokens.into_iter())
+}
+
+/// Parses a sequence of tokens one statement at a time.
+///
+/// The version header is checked straight away. The returned
+/// [Statements](struct.Statements.html) iterator then yields each
+/// [AstNode](enum.AstNode.html) as soon as its tokens have been read, so
+/// programs can be processed without holding the whole AST in memory.
+///
+/// ## Example
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{parse_stream, Lexer};
+///
+/// let source = "OPENQASM 2.0; qreg a[3]; CX a[0], a[1];";
+///
+/// for node in parse_stream(Lexer::new(source)).unwrap() {
+///     println!("{:?}", node.unwrap());
+/// }
+/// // QReg("a", 3)
+/// // CX(Qubit("a", 0), Qubit("a", 1))
+/// ```
+pub fn parse_stream<S, I>(tokens: I) -> Result<Statements<I::IntoIter>>
+where
+    S: AsRef<str>,
+    I: IntoIterator<Item = Token<S>>,
+{
+    Statements::new(tokens.into_iter())
+}
+
+/// Parses a source from a reader one statement at a time.
+///
+/// The source is read a line at a time, and comments are removed as it is read.
+/// Include statements are not resolved. Apart from that, this works like
+/// [parse_stream](fn.parse_stream.html). If reading fails, the iterator
+/// yields an `IoError`.
+///
+/// ## Example
+///
+/// ```no_run
+/// extern crate qasm;
+/// use std::net::TcpStream;
+///
+/// let stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+///
+/// for node in qasm::parse_reader(stream).unwrap() {
+///     println!("{:?}", node);
+/// }
+/// ```
+pub fn parse_reader<R: Read>(reader: R) -> Result<Statements<TokenReader<BufReader<R>>>> {
+    let tokens = TokenReader::new(BufReader::new(reader));
+    let error = tokens.error();
+    Statements::with_io_error(tokens, error)
+}
+
+/// Processes, lexes and parses a source string into a [Program](struct.Program.html).
+///
+/// The `cwd` is the directory that include statements are resolved from.
+/// Names that are declared twice are reported as a `Redefinition` error.
+/// Otherwise, the program is not checked, to do that call
+/// [Program::check](struct.Program.html#method.check) on the result.
+///
+/// ## Example
+///
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// qreg a[2];
+/// creg c[2];
+/// CX a[0], a[1];
+/// measure a -> c;
+/// "#;
+///
+/// let cwd = env::current_dir().unwrap();
+/// let program = qasm::parse_str(source, &cwd).unwrap();
+/// program.check().unwrap();
+///
+/// assert_eq!(program.num_qubits(), 2);
+/// assert_eq!(program.statements().len(), 2);
 /// ```
-pub fn parse(tokens: &Vec<token::Token>) -> Result<Vec<AstNode>> {
-    let mut tokens = tokens.iter().peekable();
-    parser::parse(&mut tokens)
+pub fn parse_str(source: &str, cwd: &Path) -> Result<Program> {
+    let processed = try_process(source, cwd)?;
+    let ast = parse_tokens(Lexer::new(&processed))?;
+    Program::from_ast(ast)
+}
+
+/// Parses a source string like [parse_str](fn.parse_str.html), following the
+/// OpenQASM 2.0 specification exactly.
+///
+/// As well as the checks done by [Program::check](struct.Program.html#method.check),
+/// this rejects programs that the parser would otherwise accept:
+///
+/// * Identifiers that don't start with a lower case letter (other than `U` and `CX`)
+/// * Gates that are used before they are declared
+/// * Gate bodies that use undeclared or indexed qubits, or undeclared parameters
+/// * Parameters outside of gates that aren't constant, or aren't valid expressions
+/// * Statements that use the same qubit twice
+///
+/// ## Example
+///
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// gate g a { CX a, b; }
+/// "#;
+///
+/// let cwd = env::current_dir().unwrap();
+/// assert!(qasm::parse_str(source, &cwd).is_ok());
+/// assert_eq!(
+///     qasm::parse_str_strict(source, &cwd),
+///     Err(qasm::Error::UndefinedGateArgument("b".to_string()))
+/// );
+/// ```
+pub fn parse_str_strict(source: &str, cwd: &Path) -> Result<Program> {
+    let processed = try_process(source, cwd)?;
+    let ast = parse_tokens(Lexer::new(&processed))?;
+    strict::check(&ast)?;
+
+    let program = Program::from_ast(ast)?;
+    program.check()?;
+    Ok(program)
+}
+
+/// Reads, processes, lexes and parses a file into a [Program](struct.Program.html).
+///
+/// Include statements are resolved from the directory that the file is in.
+/// Like [parse_str](fn.parse_str.html), the program is not checked.
+///
+/// ## Example
+///
+/// ```no_run
+/// extern crate qasm;
+///
+/// let program = qasm::parse_file("test.qasm").and_then(|program| {
+///     program.check()?;
+///     Ok(program)
+/// });
+///
+/// match program {
+///     Ok(program) => println!("{:?}", program.statements()),
+///     Err(e) => println!("Got an error: {}", e),
+/// }
+/// ```
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Program> {
+    let path = path.as_ref();
+    let source = read_file(path).map_err(|e| Error::IoError(e.kind()))?;
+    let cwd = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_str(&source, cwd)
+}
+
+/// Reads and parses a file like [parse_file](fn.parse_file.html), following the
+/// OpenQASM 2.0 specification exactly. See [parse_str_strict](fn.parse_str_strict.html).
+pub fn parse_file_strict<P: AsRef<Path>>(path: P) -> Result<Program> {
+    let path = path.as_ref();
+    let source = read_file(path).map_err(|e| Error::IoError(e.kind()))?;
+    let cwd = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_str_strict(&source, cwd)
 }
@@ -0,0 +1,248 @@
+//! Complex numbers and matrices, for working with the unitaries of gates.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+    pub const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    /// `e^(i theta)`
+    pub fn exp_i(theta: f64) -> Complex {
+        Complex::new(theta.cos(), theta.sin())
+    }
+
+    pub fn scale(self, k: f64) -> Complex {
+        Complex::new(self.re * k, self.im * k)
+    }
+
+    /// The absolute value.
+    pub fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The angle from the positive real axis, in `(-pi, pi]`.
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let norm = other.re * other.re + other.im * other.im;
+        (self * other.conj()).scale(1.0 / norm)
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+/// A square complex matrix, stored by rows.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Matrix {
+    pub size: usize,
+    pub data: Vec<Complex>,
+}
+
+impl Matrix {
+    pub fn identity(size: usize) -> Matrix {
+        let mut data = vec![Complex::ZERO; size * size];
+        for i in 0..size {
+            data[i * size + i] = Complex::ONE;
+        }
+        Matrix { size, data }
+    }
+
+    pub fn from_rows(rows: Vec<Vec<Complex>>) -> Matrix {
+        Matrix {
+            size: rows.len(),
+            data: rows.into_iter().flat_map(|r| r.into_iter()).collect(),
+        }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Complex {
+        self.data[row * self.size + col]
+    }
+
+    /// The built in `U(theta, phi, lambda)` gate.
+    pub fn u(theta: f64, phi: f64, lambda: f64) -> Matrix {
+        let (c, s) = ((theta / 2.0).cos(), (theta / 2.0).sin());
+        Matrix::from_rows(vec![
+            vec![Complex::new(c, 0.0), -Complex::exp_i(lambda).scale(s)],
+            vec![
+                Complex::exp_i(phi).scale(s),
+                Complex::exp_i(phi + lambda).scale(c),
+            ],
+        ])
+    }
+
+    /// The built in `CX` gate, with the control as the first qubit.
+    pub fn cx() -> Matrix {
+        let (o, l) = (Complex::ZERO, Complex::ONE);
+        Matrix::from_rows(vec![
+            vec![l, o, o, o],
+            vec![o, l, o, o],
+            vec![o, o, o, l],
+            vec![o, o, l, o],
+        ])
+    }
+
+    pub fn mul(&self, other: &Matrix) -> Matrix {
+        let n = self.size;
+        let mut data = vec![Complex::ZERO; n * n];
+        for i in 0..n {
+            for k in 0..n {
+                let a = self.get(i, k);
+                if a == Complex::ZERO {
+                    continue;
+                }
+                for j in 0..n {
+                    data[i * n + j] = data[i * n + j] + a * other.get(k, j);
+                }
+            }
+        }
+        Matrix { size: n, data }
+    }
+
+    pub fn scale(&self, k: Complex) -> Matrix {
+        Matrix {
+            size: self.size,
+            data: self.data.iter().map(|&a| a * k).collect(),
+        }
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let n = self.size;
+        Matrix {
+            size: n,
+            data: (0..n * n).map(|i| self.get(i % n, i / n)).collect(),
+        }
+    }
+
+    /// The conjugate transpose, which is the inverse of a unitary.
+    pub fn adjoint(&self) -> Matrix {
+        let mut adjoint = self.transpose();
+        for a in &mut adjoint.data {
+            *a = a.conj();
+        }
+        adjoint
+    }
+
+    /// The tensor product, with `self` on the most significant qubits.
+    pub fn kron(&self, other: &Matrix) -> Matrix {
+        let n = self.size * other.size;
+        let mut data = vec![Complex::ZERO; n * n];
+        for row in 0..n {
+            for col in 0..n {
+                data[row * n + col] = self.get(row / other.size, col / other.size)
+                    * other.get(row % other.size, col % other.size);
+            }
+        }
+        Matrix { size: n, data }
+    }
+
+    /// The determinant, by Gaussian elimination.
+    pub fn det(&self) -> Complex {
+        let n = self.size;
+        let mut a = self.clone();
+        let mut det = Complex::ONE;
+        for col in 0..n {
+            let pivot = (col..n)
+                .max_by(|&i, &j| a.get(i, col).norm().total_cmp(&a.get(j, col).norm()))
+                .expect("the matrix isn't empty");
+            if a.get(pivot, col) == Complex::ZERO {
+                return Complex::ZERO;
+            }
+            if pivot != col {
+                for j in 0..n {
+                    a.data.swap(pivot * n + j, col * n + j);
+                }
+                det = -det;
+            }
+            det = det * a.get(col, col);
+            for row in col + 1..n {
+                let factor = a.get(row, col) / a.get(col, col);
+                for j in col..n {
+                    a.data[row * n + j] = a.get(row, j) - factor * a.get(col, j);
+                }
+            }
+        }
+        det
+    }
+
+    /// Extends a gate on some of `n` qubits to a matrix on all of them.
+    ///
+    /// Qubit 0 is the most significant bit of the indices, for both the
+    /// gate and the result. `qubits` are the qubits the gate is applied to.
+    pub fn embed(&self, qubits: &[usize], n: usize) -> Matrix {
+        let size = 1 << n;
+        let bit = |index: usize, qubit: usize| (index >> (n - 1 - qubit)) & 1;
+        let sub_index = |index: usize| qubits.iter().fold(0, |acc, &q| (acc << 1) | bit(index, q));
+        let mask: usize = qubits.iter().map(|&q| 1 << (n - 1 - q)).sum();
+
+        let mut data = vec![Complex::ZERO; size * size];
+        for row in 0..size {
+            for col in 0..size {
+                if row & !mask == col & !mask {
+                    data[row * size + col] = self.get(sub_index(row), sub_index(col));
+                }
+            }
+        }
+        Matrix { size, data }
+    }
+}
+
+#[test]
+fn embed_test() {
+    // CX with the control on qubit 1 and the target on qubit 0
+    let flipped = Matrix::cx().embed(&[1, 0], 2);
+    let h = Matrix::u(::std::f64::consts::PI / 2.0, 0.0, ::std::f64::consts::PI);
+    let hh = h.embed(&[0], 2).mul(&h.embed(&[1], 2));
+    let conjugated = hh.mul(&Matrix::cx()).mul(&hh);
+
+    for (a, b) in conjugated.data.iter().zip(&flipped.data) {
+        assert!((*a - *b).re.abs() < 1e-12 && (*a - *b).im.abs() < 1e-12);
+    }
+}
@@ -0,0 +1,318 @@
+use super::{combine, is_identity, is_zero, standard_name};
+use circuit::{Circuit, Instruction, Operation};
+use expr::Expr;
+use std::collections::HashSet;
+
+/// The basis that a gate is diagonal in on one of its qubits.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Basis {
+    /// The gate only changes the phase of `|0>` and `|1>`, like `u1`, or
+    /// the control of `cx`.
+    Z,
+    /// The gate only changes the phase of `|+>` and `|->`, like `x`, or the
+    /// target of `cx`.
+    X,
+}
+
+/// Finds whether operations in a circuit commute.
+///
+/// Two instructions commute when they use none of the same qubits, and
+/// neither writes a bit that the other uses, or when both are gates, and on
+/// each qubit they share, both are diagonal in the same basis:
+///
+/// * Diagonal gates, such as `u1`, `rz`, `t`, `cz` or `cu1`, and the controls
+///   of `cx`, `ccx` and the other controlled gates, are diagonal in the Z basis.
+/// * `x`, `rx`, and the targets of `cx` and `ccx`, are diagonal in the X basis.
+///
+/// Only `U`, `CX` and gates defined as in `qelib1.inc` are known. Other
+/// gates, measurements, resets and barriers commute with nothing on their
+/// qubits.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::optimize::Commutation;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// cx q[0], q[1];
+/// t q[0];
+/// x q[1];
+/// h q[1];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let commutation = Commutation::new(&circuit);
+/// let i = &circuit.instructions;
+///
+/// assert!(commutation.commutes(&i[0], &i[1]));
+/// assert!(commutation.commutes(&i[0], &i[2]));
+/// assert!(commutation.commutes(&i[1], &i[3]));
+/// assert!(!commutation.commutes(&i[0], &i[3]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Commutation<'a> {
+    standard: HashSet<&'a str>,
+}
+
+impl<'a> Commutation<'a> {
+    /// Finds the gates of a circuit that are defined as in `qelib1.inc`.
+    pub fn new(circuit: &'a Circuit) -> Commutation<'a> {
+        Commutation {
+            standard: circuit.standard_gates(),
+        }
+    }
+
+    /// Whether running two instructions in either order has the same effect.
+    pub fn commutes(&self, first: &Instruction, second: &Instruction) -> bool {
+        if !clbits_commute(first, second) {
+            return false;
+        }
+        let (a, a_params, a_qubits, b, b_params, b_qubits) =
+            match (&first.operation, &second.operation) {
+                (
+                    Operation::Gate(a, a_params, a_qubits),
+                    Operation::Gate(b, b_params, b_qubits),
+                ) => (a, a_params, a_qubits, b, b_params, b_qubits),
+                _ => {
+                    let b = qubits(&second.operation);
+                    return !qubits(&first.operation).iter().any(|q| b.contains(q));
+                }
+            };
+        let a_bases = self.bases(a, a_params, a_qubits.len());
+        let b_bases = self.bases(b, b_params, b_qubits.len());
+        a_qubits
+            .iter()
+            .zip(&a_bases)
+            .all(|(q, basis)| match b_qubits.iter().position(|r| r == q) {
+                Some(j) => basis.is_some() && *basis == b_bases[j],
+                None => true,
+            })
+    }
+
+    /// The basis that a gate is diagonal in on each of its qubits, if any.
+    fn bases(&self, name: &str, params: &[Expr], qubits: usize) -> Vec<Option<Basis>> {
+        use self::Basis::{X, Z};
+        match standard_name(&self.standard, name) {
+            Some("u1") | Some("id") | Some("z") | Some("s") | Some("sdg") | Some("t")
+            | Some("tdg") | Some("cz") | Some("cu1") | Some("crz") => vec![Some(Z); qubits],
+            Some("U") | Some("u3") if is_zero(&params[0]) => vec![Some(Z)],
+            Some("x") | Some("rx") => vec![Some(X)],
+            Some("cx") => vec![Some(Z), Some(X)],
+            Some("ccx") => vec![Some(Z), Some(Z), Some(X)],
+            Some("cy") | Some("ch") | Some("cu3") => vec![Some(Z), None],
+            _ => vec![None; qubits],
+        }
+    }
+}
+
+/// The qubits that an operation uses.
+fn qubits(operation: &Operation) -> Vec<usize> {
+    match *operation {
+        Operation::Gate(_, _, ref qubits) | Operation::Barrier(ref qubits) => qubits.clone(),
+        Operation::Measure(q, _) | Operation::Reset(q) => vec![q],
+    }
+}
+
+/// Whether neither instruction writes a bit that the other reads or writes.
+fn clbits_commute(first: &Instruction, second: &Instruction) -> bool {
+    let written = |instruction: &Instruction| match instruction.operation {
+        Operation::Measure(_, b) => Some(b),
+        _ => None,
+    };
+    let uses = |instruction: &Instruction, bit: usize| {
+        written(instruction) == Some(bit)
+            || instruction.condition.as_ref().is_some_and(|c| {
+                c.register.offset <= bit && bit < c.register.offset + c.register.size
+            })
+    };
+    !written(first).is_some_and(|b| uses(second, b))
+        && !written(second).is_some_and(|b| uses(first, b))
+}
+
+/// Cancels and merges gates, as [peephole](fn.peephole.html) does, when they
+/// are separated by gates that they commute with, as found by
+/// [Commutation](struct.Commutation.html).
+///
+/// Each gate is moved back past the gates it commutes with, until it reaches
+/// one that it combines with, or one that it doesn't commute with. This is
+/// repeated until no more gates are combined.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{optimize, Expr, Operation};
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// cx q[0], q[1];
+/// u1(pi/8) q[0];
+/// x q[1];
+/// cx q[0], q[1];
+/// rz(pi/8) q[0];
+/// x q[1];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let optimized = optimize::commutative_cancellation(&circuit);
+///
+/// let operations: Vec<_> = optimized.instructions.into_iter().map(|i| i.operation).collect();
+/// assert_eq!(
+///     operations,
+///     vec![Operation::Gate("u1".to_string(), vec![Expr::parse("pi/4").unwrap()], vec![0])]
+/// );
+/// ```
+pub fn commutative_cancellation(circuit: &Circuit) -> Circuit {
+    let commutation = Commutation::new(circuit);
+    let mut instructions: Vec<Option<Instruction>> =
+        circuit.instructions.iter().cloned().map(Some).collect();
+
+    loop {
+        let before = instructions.iter().filter(|i| i.is_some()).count();
+        for j in 0..instructions.len() {
+            let identity = match instructions[j] {
+                Some(ref instruction) => is_identity(&commutation.standard, &instruction.operation),
+                None => continue,
+            };
+            if identity {
+                instructions[j] = None;
+                continue;
+            }
+            let mut current = j;
+            while let Some(merged) = merge_back(&commutation, &mut instructions, current) {
+                current = merged;
+            }
+        }
+        if instructions.iter().filter(|i| i.is_some()).count() == before {
+            break;
+        }
+    }
+
+    Circuit {
+        instructions: instructions.into_iter().flatten().collect(),
+        ..circuit.clone()
+    }
+}
+
+/// Moves the instruction at `index` back past the instructions it commutes
+/// with, and combines it with the first one it can. Returns the index of the
+/// combined gate, unless it is combined into nothing, or nothing is combined.
+fn merge_back(
+    commutation: &Commutation,
+    instructions: &mut [Option<Instruction>],
+    index: usize,
+) -> Option<usize> {
+    let current = instructions[index].clone()?;
+    for i in (0..index).rev() {
+        let earlier = match instructions[i] {
+            Some(ref earlier) => earlier,
+            None => continue,
+        };
+        if earlier.condition == current.condition {
+            if let Some(combined) = combine(
+                &commutation.standard,
+                &earlier.operation,
+                &current.operation,
+            ) {
+                instructions[index] = None;
+                instructions[i] = combined
+                    .into_iter()
+                    .find(|operation| !is_identity(&commutation.standard, operation))
+                    .map(|operation| Instruction {
+                        operation,
+                        condition: current.condition.clone(),
+                    });
+                return instructions[i].as_ref().map(|_| i);
+            }
+        }
+        if !commutation.commutes(earlier, &current) {
+            return None;
+        }
+    }
+    None
+}
+
+#[test]
+fn commutation_test() {
+    use super::unitary::{equivalent, unitary};
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        include \"tests/source/qelib1.inc\";
+        qreg q[3];
+        creg c[1];
+        cx q[0], q[1];
+        u1(0.5) q[0];
+        rx(0.5) q[1];
+        cz q[0], q[2];
+        cx q[0], q[1];
+        h q[2];
+        U(0, 0.5, 0.5) q[2];
+        cx q[2], q[1];
+        h q[2];
+        ccx q[0], q[2], q[1];
+        u1(0.25) q[0];
+        ccx q[0], q[2], q[1];
+        barrier q[0];
+        tdg q[0];
+        if(c==1) x q[1];
+        measure q[2] -> c[0];
+        if(c==1) x q[1];
+        y q[2];
+        reset q[1];
+        y q[2];";
+    let circuit = flatten(source);
+    let optimized = commutative_cancellation(&circuit);
+
+    let statements: Vec<_> = optimized
+        .to_ast()
+        .into_iter()
+        .skip_while(|node| !matches!(*node, ::AstNode::CReg(..)))
+        .skip(1)
+        .map(|node| format!("{:?}", node))
+        .collect();
+    assert_eq!(
+        statements,
+        vec![
+            r#"ApplyGate("u1", [Qubit("q", 0)], ["0.75"])"#,
+            r#"ApplyGate("rx", [Qubit("q", 1)], ["0.5"])"#,
+            r#"ApplyGate("cz", [Qubit("q", 0), Qubit("q", 2)], [])"#,
+            r#"ApplyGate("h", [Qubit("q", 2)], [])"#,
+            r#"U(Qubit("q", 2), ["0", "0.5", "0.5"])"#,
+            r#"ApplyGate("cx", [Qubit("q", 2), Qubit("q", 1)], [])"#,
+            r#"ApplyGate("h", [Qubit("q", 2)], [])"#,
+            r#"Barrier([Qubit("q", 0)])"#,
+            r#"ApplyGate("tdg", [Qubit("q", 0)], [])"#,
+            r#"If("c", 1, ApplyGate("x", [Qubit("q", 1)], []))"#,
+            r#"Measure(Qubit("q", 2), Qubit("c", 0))"#,
+            r#"If("c", 1, ApplyGate("x", [Qubit("q", 1)], []))"#,
+            r#"Reset(Qubit("q", 1))"#,
+        ]
+    );
+    assert_eq!(commutative_cancellation(&optimized), optimized);
+
+    // The unitaries up to the barrier are the same
+    let prefix = |circuit: &Circuit| {
+        let end = circuit
+            .instructions
+            .iter()
+            .position(|i| matches!(i.operation, Operation::Barrier(_)))
+            .unwrap();
+        Circuit {
+            instructions: circuit.instructions[..end].to_vec(),
+            ..circuit.clone()
+        }
+    };
+    assert!(equivalent(
+        &unitary(&prefix(&circuit)),
+        &unitary(&prefix(&optimized))
+    ));
+}
@@ -0,0 +1,681 @@
+use super::resynth::u_angles;
+use super::ResynthesisOptions;
+use circuit::{Circuit, Instruction, Operation};
+use expr::Expr;
+use matrix::{Complex, Matrix};
+use std::collections::{BTreeMap, HashSet};
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4};
+
+/// A block of gates on two qubits: the indices of its instructions, and the
+/// qubits, with the first as the most significant qubit of the unitaries.
+struct Block {
+    qubits: [usize; 2],
+    instructions: Vec<usize>,
+    /// The index of its first two-qubit gate. The single-qubit gates before
+    /// it can be moved up to it, but other gates could have used the other
+    /// qubit in between, so the new gates go here.
+    start: usize,
+}
+
+/// Replaces each block of gates on a pair of qubits with the fewest `CX`
+/// gates that have the same effect, and `U` gates around them.
+///
+/// A block is as many `U`, `CX`, and gates on one or two qubits defined as
+/// in `qelib1.inc`, in a row on a pair of qubits as possible, starting at a
+/// gate on both of them. Gates with conditions, or parameters that aren't
+/// numbers, end blocks, as other operations on the qubits do.
+///
+/// The unitary of each block is split into single-qubit gates and a
+/// canonical gate `exp(i(x XX + y YY + z ZZ))` by the KAK decomposition,
+/// which is made with:
+///
+/// * no `CX` gates, when the block acts on each qubit on its own,
+/// * one `CX`, when the canonical gate is `CX` up to single-qubit gates,
+/// * two `CX` gates, when one of `x`, `y` and `z` is a multiple of pi/2,
+/// * otherwise, three `CX` gates.
+///
+/// The block is replaced when this takes fewer `CX` gates than the block,
+/// with its gates inlined, or as many and fewer gates. The new gates take
+/// the place of the first gate in the block, and the `U` gates' angles are
+/// numbers, with `U` gates that do nothing left out. The options' tolerance
+/// is used to decide whether angles are 0 or pi/4.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::optimize::{self, ResynthesisOptions};
+/// use qasm::Stats;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// cx q[0], q[1];
+/// cx q[1], q[0];
+/// cx q[0], q[1];
+/// h q[0];
+/// h q[1];
+/// cx q[0], q[1];
+/// h q[0];
+/// h q[1];
+/// cz q[0], q[1];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let resynthesized = optimize::resynthesize_blocks(&circuit, &ResynthesisOptions::default());
+///
+/// let cx = |circuit| Stats::from_circuit(circuit).unwrap().inlined_counts["CX"];
+/// assert_eq!(cx(&circuit), 5);
+/// assert_eq!(cx(&resynthesized), 2);
+/// ```
+pub fn resynthesize_blocks(circuit: &Circuit, options: &ResynthesisOptions) -> Circuit {
+    let standard = circuit.standard_gates();
+    // The blocks, the blocks that haven't ended yet by qubit, and the
+    // single-qubit gates that could start one by qubit
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut open: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut pending: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+
+    for (i, instruction) in circuit.instructions.iter().enumerate() {
+        let qubits = match instruction.operation {
+            Operation::Gate(ref name, ref params, ref qubits)
+                if instruction.condition.is_none() && is_known(&standard, name, params, qubits) =>
+            {
+                qubits
+            }
+            ref operation => {
+                for qubit in operation_qubits(operation) {
+                    pending.remove(&qubit);
+                    if let Some(b) = open.remove(&qubit) {
+                        open.retain(|_, &mut other| other != b);
+                    }
+                }
+                continue;
+            }
+        };
+
+        if let [qubit] = qubits[..] {
+            match open.get(&qubit) {
+                Some(&b) => blocks[b].instructions.push(i),
+                None => pending.entry(qubit).or_default().push(i),
+            }
+            continue;
+        }
+
+        let (a, b) = (qubits[0], qubits[1]);
+        match (open.get(&a), open.get(&b)) {
+            (Some(&first), Some(&second)) if first == second => {
+                blocks[first].instructions.push(i);
+                continue;
+            }
+            _ => {}
+        }
+        for qubit in &[a, b] {
+            if let Some(b) = open.remove(qubit) {
+                open.retain(|_, &mut other| other != b);
+            }
+        }
+        let mut instructions: Vec<usize> = pending
+            .remove(&a)
+            .into_iter()
+            .chain(pending.remove(&b))
+            .flatten()
+            .collect();
+        instructions.sort_unstable();
+        instructions.push(i);
+        open.insert(a, blocks.len());
+        open.insert(b, blocks.len());
+        blocks.push(Block {
+            qubits: [a, b],
+            instructions,
+            start: i,
+        });
+    }
+
+    // Blocks share no instructions, so they can be replaced in any order
+    let mut instructions: Vec<Vec<Instruction>> = circuit
+        .instructions
+        .iter()
+        .map(|i| vec![i.clone()])
+        .collect();
+    for block in blocks {
+        replace(circuit, &mut instructions, &block, options.tolerance);
+    }
+
+    Circuit {
+        instructions: instructions.into_iter().flatten().collect(),
+        ..circuit.clone()
+    }
+}
+
+/// Whether a gate can be part of a block: it is `U`, `CX`, or defined as in
+/// `qelib1.inc`, on one or two qubits, with parameters that are numbers.
+fn is_known(standard: &HashSet<&str>, name: &str, params: &[Expr], qubits: &[usize]) -> bool {
+    (name == "U" || name == "CX" || standard.contains(name))
+        && (qubits.len() == 1 || qubits.len() == 2)
+        && params
+            .iter()
+            .all(|p| p.eval().is_ok_and(|value| value.is_finite()))
+}
+
+/// The qubits that an operation uses.
+fn operation_qubits(operation: &Operation) -> Vec<usize> {
+    match *operation {
+        Operation::Gate(_, _, ref qubits) | Operation::Barrier(ref qubits) => qubits.clone(),
+        Operation::Measure(q, _) | Operation::Reset(q) => vec![q],
+    }
+}
+
+/// The position of a qubit in a block's qubits.
+fn index(block: &Block, qubit: usize) -> usize {
+    block
+        .qubits
+        .iter()
+        .position(|&q| q == qubit)
+        .expect("the qubit is in the block")
+}
+
+/// Replaces the instructions of a block with the gates of its KAK
+/// decomposition, if that takes fewer `CX` gates, or as many and fewer gates.
+fn replace(
+    circuit: &Circuit,
+    instructions: &mut [Vec<Instruction>],
+    block: &Block,
+    tolerance: f64,
+) {
+    let mut unitary = Matrix::identity(4);
+    let mut cx = 0;
+    for &i in &block.instructions {
+        if let Operation::Gate(ref name, ref params, ref qubits) = circuit.instructions[i].operation
+        {
+            let values: Vec<_> = params
+                .iter()
+                .map(|p| p.eval().expect("the parameters are numbers"))
+                .collect();
+            let positions: Vec<_> = qubits.iter().map(|&q| index(block, q)).collect();
+            let gate = circuit
+                .unitary(name, &values)
+                .expect("the gate is defined as in qelib1.inc");
+            unitary = gate.embed(&positions, 2).mul(&unitary);
+            circuit
+                .expand(name, params, qubits, &|_| false, &mut |name, _, _| {
+                    if name == "CX" {
+                        cx += 1;
+                    }
+                    Ok(())
+                })
+                .expect("the gate is defined as in qelib1.inc");
+        }
+    }
+
+    let operations = match synthesize(&unitary, block.qubits, tolerance) {
+        Some(operations) => operations,
+        None => return,
+    };
+    let new_cx = operations
+        .iter()
+        .filter(|o| matches!(**o, Operation::Gate(ref name, ..) if name == "CX"))
+        .count();
+    if (new_cx, operations.len()) >= (cx, block.instructions.len()) {
+        return;
+    }
+
+    for &i in &block.instructions {
+        instructions[i].clear();
+    }
+    instructions[block.start] = operations
+        .into_iter()
+        .map(|operation| Instruction {
+            operation,
+            condition: None,
+        })
+        .collect();
+}
+
+/// The KAK decomposition of a two-qubit unitary: up to a global phase, it is
+/// `left`, times `exp(i(x XX + y YY + z ZZ))`, times `right`, where `left`
+/// and `right` act on each qubit on its own.
+struct Kak {
+    left: Matrix,
+    coefficients: [f64; 3],
+    right: Matrix,
+}
+
+/// The Pauli X, Y and Z matrices.
+fn paulis() -> [Matrix; 3] {
+    let (o, l, i) = (Complex::ZERO, Complex::ONE, Complex::new(0.0, 1.0));
+    [
+        Matrix::from_rows(vec![vec![o, l], vec![l, o]]),
+        Matrix::from_rows(vec![vec![o, -i], vec![i, o]]),
+        Matrix::from_rows(vec![vec![l, o], vec![o, -l]]),
+    ]
+}
+
+/// `exp(i angle P)`, for a Pauli matrix `P`.
+fn rotation(pauli: &Matrix, angle: f64) -> Matrix {
+    let (cos, sin) = (
+        Complex::new(angle.cos(), 0.0),
+        Complex::new(0.0, angle.sin()),
+    );
+    let mut rotation = pauli.scale(sin);
+    rotation.data[0] = rotation.data[0] + cos;
+    rotation.data[3] = rotation.data[3] + cos;
+    rotation
+}
+
+/// The magic basis, as columns. Single-qubit gates on each qubit are real
+/// orthogonal matrices in this basis, and `XX`, `YY` and `ZZ` are diagonal.
+fn magic() -> Matrix {
+    let (o, l, i) = (
+        Complex::ZERO,
+        Complex::new(FRAC_1_SQRT_2, 0.0),
+        Complex::new(0.0, FRAC_1_SQRT_2),
+    );
+    Matrix::from_rows(vec![
+        vec![l, o, o, i],
+        vec![o, i, l, o],
+        vec![o, i, -l, o],
+        vec![l, o, o, -i],
+    ])
+}
+
+impl Kak {
+    /// Decomposes a two-qubit unitary, or returns `None` if it couldn't be
+    /// diagonalized.
+    fn new(unitary: &Matrix) -> Option<Kak> {
+        // Scale the unitary so that its determinant is 1, and change to the
+        // magic basis, where it is K1 D K2, with K1 and K2 real orthogonal,
+        // and D diagonal
+        let det = unitary.det();
+        let unitary = unitary.scale(Complex::exp_i(-det.arg() / 4.0).scale(det.norm().powf(-0.25)));
+        let magic = magic();
+        let u = magic.adjoint().mul(&unitary).mul(&magic);
+
+        // The transpose times the unitary is K2^T D^2 K2, so K2 diagonalizes it
+        let square = u.transpose().mul(&u);
+        let mut k2 = diagonalize(&square)?;
+        if k2.det().re < 0.0 {
+            for row in 0..4 {
+                k2.data[row * 4] = -k2.data[row * 4];
+            }
+        }
+        let diagonal = k2.transpose().mul(&square).mul(&k2);
+
+        // Pick the square roots of D^2 so that the determinant of D is 1
+        let mut d: Vec<f64> = (0..4).map(|k| diagonal.get(k, k).arg() / 2.0).collect();
+        let turns = (d.iter().sum::<f64>() / (2.0 * FRAC_PI_2)).round() as i64;
+        for angle in d.iter_mut().take(turns.unsigned_abs() as usize) {
+            *angle -= 2.0 * FRAC_PI_2 * turns.signum() as f64;
+        }
+        let mut root = Matrix::identity(4);
+        for (k, &angle) in d.iter().enumerate() {
+            root.data[k * 5] = Complex::exp_i(-angle);
+        }
+        let k1 = u.mul(&k2).mul(&root);
+
+        // D is exp(i(x XX + y YY + z ZZ)), where XX, YY and ZZ have diagonals
+        // of 1 and -1 in the magic basis
+        let mut coefficients = [0.0; 3];
+        for (coefficient, pauli) in coefficients.iter_mut().zip(paulis().iter()) {
+            let diagonal = magic.adjoint().mul(&pauli.kron(pauli)).mul(&magic);
+            *coefficient = (0..4).map(|k| d[k] * diagonal.get(k, k).re).sum::<f64>() / 4.0;
+        }
+
+        Some(Kak {
+            left: magic.mul(&k1).mul(&magic.adjoint()),
+            coefficients,
+            right: magic.mul(&k2.transpose()).mul(&magic.adjoint()),
+        })
+    }
+}
+
+/// A real orthogonal matrix whose columns are eigenvectors of a complex
+/// symmetric unitary, or `None` if none is found.
+///
+/// The real and imaginary parts of the matrix are real symmetric matrices
+/// that commute, so they have the same eigenvectors. The eigenvectors of a
+/// combination of them are found, and checked.
+fn diagonalize(matrix: &Matrix) -> Option<Matrix> {
+    for &angle in &[0.4f64, 1.3, 2.2, 2.9, 0.9] {
+        let mut real = [[0.0; 4]; 4];
+        for (row, values) in real.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                let a = matrix.get(row, col);
+                *value = a.re * angle.cos() + a.im * angle.sin();
+            }
+        }
+        let vectors = jacobi(real);
+        let vectors = Matrix::from_rows(
+            vectors
+                .iter()
+                .map(|row| row.iter().map(|&a| Complex::new(a, 0.0)).collect())
+                .collect(),
+        );
+        let diagonal = vectors.transpose().mul(matrix).mul(&vectors);
+        let diagonalized =
+            (0..4).all(|row| (0..4).all(|col| row == col || diagonal.get(row, col).norm() < 1e-9));
+        if diagonalized {
+            return Some(vectors);
+        }
+    }
+    None
+}
+
+/// The eigenvectors of a real symmetric matrix, as the columns of an
+/// orthogonal matrix, by Jacobi rotations.
+fn jacobi(mut a: [[f64; 4]; 4]) -> [[f64; 4]; 4] {
+    let mut vectors = [[0.0; 4]; 4];
+    for (i, row) in vectors.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    for _ in 0..64 {
+        let off: f64 = (0..4)
+            .flat_map(|p| (0..4).filter(move |&q| q != p).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q])
+            .sum();
+        if off < 1e-30 {
+            break;
+        }
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // Rotate the p and q axes so that a[p][q] becomes 0
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                let (row_p, row_q) = (a[p], a[q]);
+                for (k, (pk, qk)) in row_p.iter().zip(&row_q).enumerate() {
+                    a[p][k] = c * pk - s * qk;
+                    a[q][k] = s * pk + c * qk;
+                }
+                for row in vectors.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+            }
+        }
+    }
+    vectors
+}
+
+/// A step of a two-qubit circuit: single-qubit gates on both qubits, as the
+/// 4x4 unitary of their tensor product, or a `CX` gate, with the first or
+/// second qubit as its control.
+enum Step {
+    Local(Matrix),
+    Cx(bool),
+}
+
+/// The `U` and `CX` gates, on two qubits, of the KAK decomposition of a
+/// unitary, with as few `CX` gates as possible.
+fn synthesize(unitary: &Matrix, qubits: [usize; 2], tolerance: f64) -> Option<Vec<Operation>> {
+    let Kak {
+        mut left,
+        coefficients,
+        mut right,
+    } = Kak::new(unitary)?;
+    let [x, y, z] = paulis();
+    let i = Matrix::identity(2);
+    let pair = |a: &Matrix, b: &Matrix| a.kron(b);
+
+    // exp(i (c + k pi/2) PP) is exp(i c PP) times (i PP)^k, so each
+    // coefficient is moved into [-pi/4, pi/4], and PP into the right gates
+    let mut coefficients = coefficients;
+    for (coefficient, pauli) in coefficients.iter_mut().zip([&x, &y, &z].iter()) {
+        let turns = (*coefficient / FRAC_PI_2).round();
+        *coefficient -= turns * FRAC_PI_2;
+        if turns as i64 % 2 != 0 {
+            right = pair(pauli, pauli).mul(&right);
+        }
+    }
+
+    // Conjugating by C = H Sdg on both qubits takes X to Y, Y to Z and Z to
+    // X, so exp(i(x XX + y YY + z ZZ)) is (C C) exp(i(y XX + z YY + x ZZ)) (C C)^-1
+    let h = Matrix::u(FRAC_PI_2, 0.0, 2.0 * FRAC_PI_2);
+    let s = Matrix::u(0.0, 0.0, FRAC_PI_2);
+    let c = h.mul(&s.adjoint());
+    let cc = pair(&c, &c);
+    let mut rotate = |coefficients: &mut [f64; 3]| {
+        coefficients.rotate_left(1);
+        left = left.mul(&cc);
+        right = cc.adjoint().mul(&right);
+    };
+
+    let zero = |c: f64| c.abs() < tolerance;
+    let zeros = coefficients.iter().filter(|&&c| zero(c)).count();
+    let middle = if zeros == 3 {
+        Vec::new()
+    } else if zeros == 2
+        && coefficients
+            .iter()
+            .any(|&c| (c.abs() - FRAC_PI_4).abs() < tolerance)
+    {
+        while zero(coefficients[0]) {
+            rotate(&mut coefficients);
+        }
+        // exp(-i pi/4 XX) is exp(i pi/4 XX) times -i XX, and
+        // exp(i pi/4 XX) is (H exp(i pi/4 Z), exp(i pi/4 X)) CX (H, I), up to a phase
+        if coefficients[0] < 0.0 {
+            right = pair(&x, &x).mul(&right);
+        }
+        vec![
+            Step::Local(pair(&h, &i)),
+            Step::Cx(true),
+            Step::Local(pair(
+                &h.mul(&rotation(&z, FRAC_PI_4)),
+                &rotation(&x, FRAC_PI_4),
+            )),
+        ]
+    } else if zeros >= 1 {
+        while !zero(coefficients[1]) {
+            rotate(&mut coefficients);
+        }
+        // exp(i(x XX + z ZZ)) is CX (exp(i x X), exp(i z Z)) CX
+        vec![
+            Step::Cx(true),
+            Step::Local(pair(
+                &rotation(&x, coefficients[0]),
+                &rotation(&z, coefficients[2]),
+            )),
+            Step::Cx(true),
+        ]
+    } else {
+        // exp(i(x XX + y YY + z ZZ)) is, up to a phase, (I, S) Q (I, Sdg) (Sdg, S),
+        // where Q is CX10 (exp(i a Z), exp(i b Y)) CX01 (I, exp(i c Y)) CX10,
+        // with a = z - pi/4, b = pi/4 - x and c = y - pi/4
+        let (a, b, c) = (
+            coefficients[2] - FRAC_PI_4,
+            FRAC_PI_4 - coefficients[0],
+            coefficients[1] - FRAC_PI_4,
+        );
+        vec![
+            Step::Local(pair(&s.adjoint(), &s).mul(&pair(&i, &s.adjoint()))),
+            Step::Cx(false),
+            Step::Local(pair(&i, &rotation(&y, c))),
+            Step::Cx(true),
+            Step::Local(pair(&rotation(&z, a), &rotation(&y, b))),
+            Step::Cx(false),
+            Step::Local(pair(&i, &s)),
+        ]
+    };
+
+    // The steps in the order they are run, with neighbouring single-qubit
+    // gates multiplied together
+    let mut steps = vec![Step::Local(right)];
+    for step in middle.into_iter().chain(Some(Step::Local(left))) {
+        match (steps.last_mut(), step) {
+            (Some(&mut Step::Local(ref mut before)), Step::Local(after)) => {
+                *before = after.mul(before)
+            }
+            (_, step) => steps.push(step),
+        }
+    }
+
+    let mut operations = Vec::new();
+    for step in steps {
+        match step {
+            Step::Cx(forward) => {
+                let (control, target) = if forward {
+                    (qubits[0], qubits[1])
+                } else {
+                    (qubits[1], qubits[0])
+                };
+                operations.push(Operation::Gate(
+                    "CX".to_string(),
+                    Vec::new(),
+                    vec![control, target],
+                ));
+            }
+            Step::Local(local) => {
+                let (a, b) = factor(&local);
+                for (gate, &qubit) in [a, b].iter().zip(qubits.iter()) {
+                    let (theta, phi, lambda) = u_angles(gate, tolerance);
+                    let identity = theta.abs() < tolerance && {
+                        let turn = (phi + lambda) / (4.0 * FRAC_PI_2);
+                        (turn - turn.round()).abs() * 4.0 * FRAC_PI_2 < tolerance
+                    };
+                    if !identity {
+                        operations.push(Operation::Gate(
+                            "U".to_string(),
+                            vec![Expr::Real(theta), Expr::Real(phi), Expr::Real(lambda)],
+                            vec![qubit],
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    Some(operations)
+}
+
+/// The single-qubit unitaries whose tensor product is a 4x4 unitary that
+/// acts on each qubit on its own, up to a phase.
+fn factor(local: &Matrix) -> (Matrix, Matrix) {
+    // Each 2x2 block of the matrix is an entry of the first unitary times
+    // the second
+    let block = |row: usize, col: usize| {
+        Matrix::from_rows(vec![
+            vec![local.get(2 * row, 2 * col), local.get(2 * row, 2 * col + 1)],
+            vec![
+                local.get(2 * row + 1, 2 * col),
+                local.get(2 * row + 1, 2 * col + 1),
+            ],
+        ])
+    };
+    let size = |m: &Matrix| m.data.iter().map(|a| a.norm() * a.norm()).sum::<f64>();
+    let largest = (0..4)
+        .map(|k| block(k / 2, k % 2))
+        .max_by(|a, b| size(a).total_cmp(&size(b)))
+        .expect("there are four blocks");
+    let second = largest.scale(Complex::new((2.0 / size(&largest)).sqrt(), 0.0));
+
+    let mut first = Matrix::identity(2);
+    for row in 0..2 {
+        for col in 0..2 {
+            let product = second.adjoint().mul(&block(row, col));
+            first.data[row * 2 + col] = (product.get(0, 0) + product.get(1, 1)).scale(0.5);
+        }
+    }
+    (first, second)
+}
+
+#[test]
+fn kak_test() {
+    use super::unitary::{equivalent, unitary};
+    use circuit::tests::flatten;
+    use stats::Stats;
+
+    let cx = |circuit: &Circuit| {
+        Stats::from_circuit(circuit)
+            .unwrap()
+            .inlined_counts
+            .get("CX")
+            .cloned()
+            .unwrap_or(0)
+    };
+    let check = |body: &str, expected: usize| {
+        let source = format!(
+            "OPENQASM 2.0; include \"tests/source/qelib1.inc\"; qreg q[3]; {}",
+            body
+        );
+        let circuit = flatten(&source);
+        let resynthesized = resynthesize_blocks(&circuit, &ResynthesisOptions::default());
+        assert_eq!(cx(&resynthesized), expected, "{}", body);
+        assert!(
+            equivalent(&unitary(&circuit), &unitary(&resynthesized)),
+            "{}",
+            body
+        );
+    };
+
+    check(
+        "cx q[0], q[1]; h q[0]; h q[1]; cx q[1], q[0]; h q[0]; h q[1];",
+        0,
+    );
+    check("cx q[0], q[1]; u1(0.3) q[0]; x q[1]; cx q[0], q[1];", 0);
+    check("h q[0]; cz q[0], q[1]; rx(0.4) q[1];", 1);
+    check("cx q[0], q[1]; cx q[1], q[0]; cz q[1], q[0];", 2);
+    check("cu1(0.7) q[0], q[1]; crz(0.3) q[1], q[0];", 2);
+    check(
+        "cx q[0], q[1]; cx q[1], q[0]; rx(0.4) q[1]; cx q[0], q[1];",
+        3,
+    );
+    check(
+        "ch q[0], q[1]; cu3(0.1, 0.2, 0.3) q[1], q[0]; cy q[0], q[1];",
+        3,
+    );
+    check(
+        "cx q[0], q[1]; cx q[1], q[0]; cx q[0], q[1]; cu1(pi) q[1], q[0]; cx q[1], q[0]; cx q[0], q[1]; cx q[1], q[0];",
+        1,
+    );
+    // The gates replacing a block go after other gates on its qubits
+    check(
+        "h q[0]; cx q[1], q[2]; cx q[0], q[1]; h q[1]; cx q[0], q[1]; h q[1];",
+        2,
+    );
+
+    // Every decomposition is the unitary it came from
+    let gates = [
+        Matrix::cx(),
+        Matrix::cx().embed(&[1, 0], 2),
+        Matrix::u(0.3, 0.2, 0.1).kron(&Matrix::u(1.3, -0.2, 2.1)),
+    ];
+    for n in 0..60 {
+        let mut u = Matrix::identity(4);
+        for k in 0..(n % 7) {
+            let angle = 0.37 * (n * 7 + k) as f64;
+            let layer =
+                Matrix::u(angle, 2.0 * angle, -angle).kron(&Matrix::u(angle.sin(), 1.0, 0.5));
+            u = gates[(n + k) % 3].mul(&layer).mul(&u);
+        }
+        let operations = synthesize(&u, [0, 1], 1e-9).unwrap();
+        let circuit = Circuit {
+            qregs: vec![::Register {
+                name: "q".to_string(),
+                size: 2,
+                offset: 0,
+            }],
+            instructions: operations
+                .into_iter()
+                .map(|operation| Instruction {
+                    operation,
+                    condition: None,
+                })
+                .collect(),
+            ..Circuit::default()
+        };
+        assert!(cx(&circuit) <= 3);
+        assert!(equivalent(&u, &unitary(&circuit)), "{}", n);
+    }
+}
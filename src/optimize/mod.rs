@@ -0,0 +1,215 @@
+//! # Optimisation
+//!
+//! Optimisation passes over flattened programs.
+//!
+//! Each pass takes a [Circuit](../struct.Circuit.html) and returns one that
+//! has the same effect, up to a global phase. Only `U`, `CX` and the gates of
+//! `qelib1.inc` are changed, and only when the program defines them as
+//! `qelib1.inc` does. Barriers are never moved or removed, so gates are never
+//! combined across them.
+//!
+//! * [peephole](fn.peephole.html) combines gates that are next to each other.
+//! * [commutative_cancellation](fn.commutative_cancellation.html) also
+//!   combines gates that are separated by gates they commute with, as found
+//!   by [Commutation](struct.Commutation.html).
+//! * [resynthesize](fn.resynthesize.html) replaces each run of single-qubit
+//!   gates on a qubit with one gate.
+//! * [resynthesize_blocks](fn.resynthesize_blocks.html) replaces each block of
+//!   gates on a pair of qubits with as few `CX` gates as it can.
+
+mod commute;
+mod kak;
+mod peephole;
+mod resynth;
+
+pub use self::commute::{commutative_cancellation, Commutation};
+pub use self::kak::resynthesize_blocks;
+pub use self::peephole::peephole;
+pub use self::resynth::{resynthesize, ResynthesisOptions};
+
+use circuit::Operation;
+use exact::exact;
+use expr::{Expr, Op};
+use std::collections::HashSet;
+use std::f64::consts::PI;
+
+/// How close an angle has to be to a whole number of turns to be left out.
+const TOLERANCE: f64 = 1e-12;
+
+/// The gate that a gate is, when it is `U`, `CX` or a gate defined as in
+/// `qelib1.inc`. `CX` is `cx`.
+fn standard_name<'a>(standard: &HashSet<&str>, name: &'a str) -> Option<&'a str> {
+    match name {
+        "U" => Some("U"),
+        "CX" => Some("cx"),
+        "rz" if standard.contains(name) => Some("u1"),
+        _ if standard.contains(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// The operation that two gates in a row can be replaced by, or nothing if
+/// they cancel, when they combine.
+fn combine(
+    standard: &HashSet<&str>,
+    first: &Operation,
+    second: &Operation,
+) -> Option<Vec<Operation>> {
+    let (first_name, first_params, qubits, second_name, second_params) = match (first, second) {
+        (Operation::Gate(a, a_params, a_qubits), Operation::Gate(b, b_params, b_qubits))
+            if a_qubits == b_qubits =>
+        {
+            (a, a_params, a_qubits, b, b_params)
+        }
+        _ => return None,
+    };
+    let gate = |name: &str, params| {
+        Some(vec![Operation::Gate(
+            name.to_string(),
+            params,
+            qubits.clone(),
+        )])
+    };
+
+    match (
+        standard_name(standard, first_name)?,
+        standard_name(standard, second_name)?,
+    ) {
+        (a, b) if a == b && ["x", "y", "z", "h", "cx", "cz", "cy", "ch", "ccx"].contains(&a) => {
+            Some(Vec::new())
+        }
+        ("s", "sdg") | ("sdg", "s") | ("t", "tdg") | ("tdg", "t") => Some(Vec::new()),
+        (a, b) if a == b && ["u1", "rx", "ry", "cu1", "crz"].contains(&a) => {
+            // rz is u1, so keep the name when both gates have it
+            let name = if first_name == second_name {
+                first_name
+            } else {
+                "u1"
+            };
+            gate(name, vec![add(&first_params[0], &second_params[0])])
+        }
+        ("U", "U") | ("U", "u3") | ("u3", "U") | ("u3", "u3") => {
+            let (t1, p1, l1) = (&first_params[0], &first_params[1], &first_params[2]);
+            let (t2, p2, l2) = (&second_params[0], &second_params[1], &second_params[2]);
+            // U(0, phi, lambda) is a rotation about Z by phi + lambda, which
+            // adds to lambda of the gate after it, or phi of the gate before it
+            if is_zero(t1) {
+                gate(
+                    second_name,
+                    vec![t2.clone(), p2.clone(), add(l2, &add(p1, l1))],
+                )
+            } else if is_zero(t2) {
+                gate(
+                    first_name,
+                    vec![t1.clone(), add(p1, &add(p2, l2)), l1.clone()],
+                )
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether an operation is a rotation by a whole number of turns, which does
+/// nothing, up to a global phase.
+fn is_identity(standard: &HashSet<&str>, operation: &Operation) -> bool {
+    let (name, params) = match *operation {
+        Operation::Gate(ref name, ref params, _) => (name, params),
+        _ => return false,
+    };
+    match standard_name(standard, name) {
+        Some("u1") | Some("rx") | Some("ry") | Some("cu1") => turns(&params[0], 1.0),
+        // crz is a rotation by half the angle, with a phase that depends on the control
+        Some("crz") => turns(&params[0], 2.0),
+        Some("U") | Some("u3") => {
+            turns(&params[0], 1.0) && turns(&add(&params[1], &params[2]), 1.0)
+        }
+        _ => false,
+    }
+}
+
+/// Whether an angle is a whole number of `period` turns.
+fn turns(angle: &Expr, period: f64) -> bool {
+    let period = 2.0 * PI * period;
+    angle.eval().is_ok_and(|value| {
+        let remainder = value - (value / period).round() * period;
+        remainder.abs() < TOLERANCE
+    })
+}
+
+/// Whether an angle is 0.
+fn is_zero(angle: &Expr) -> bool {
+    angle.eval().is_ok_and(|value| value.abs() < TOLERANCE)
+}
+
+/// The sum of two angles, simplified when both are rational multiples of pi,
+/// or both are numbers.
+fn add(a: &Expr, b: &Expr) -> Expr {
+    if let (Some(a), Some(b)) = (number(a), number(b)) {
+        return Expr::Real(a + b);
+    }
+    if let Some(sum) = exact(a)
+        .and_then(|a| exact(b).and_then(|b| a.add(b)))
+        .and_then(|sum| sum.to_expr())
+    {
+        return sum;
+    }
+    match (a, b) {
+        _ if is_zero(a) => b.clone(),
+        _ if is_zero(b) => a.clone(),
+        _ => Expr::Binary(Op::Add, Box::new(a.clone()), Box::new(b.clone())),
+    }
+}
+
+/// The value of a number, or a negated number.
+fn number(expr: &Expr) -> Option<f64> {
+    match *expr {
+        Expr::Real(n) => Some(n),
+        Expr::Int(n) => Some(f64::from(n)),
+        Expr::Neg(ref e) => number(e).map(|n| -n),
+        _ => None,
+    }
+}
+
+/// The unitary of the gates without conditions in a circuit, and whether two
+/// unitaries are the same up to a global phase, for testing passes.
+#[cfg(test)]
+pub(crate) mod unitary {
+    use circuit::{Circuit, Operation};
+    use matrix::Matrix;
+
+    pub fn unitary(circuit: &Circuit) -> Matrix {
+        let n = circuit.num_qubits();
+        circuit
+            .instructions
+            .iter()
+            .filter(|i| i.condition.is_none())
+            .fold(
+                Matrix::identity(1 << n),
+                |unitary, instruction| match instruction.operation {
+                    Operation::Gate(ref name, ref params, ref qubits) => {
+                        let params: Vec<_> = params.iter().map(|p| p.eval().unwrap()).collect();
+                        let gate = circuit.unitary(name, &params).unwrap();
+                        gate.embed(qubits, n).mul(&unitary)
+                    }
+                    _ => unitary,
+                },
+            )
+    }
+
+    /// |tr(A* B)| is the size of the matrices when they are equal up to a phase
+    pub fn equivalent(a: &Matrix, b: &Matrix) -> bool {
+        let (re, im) = a
+            .data
+            .iter()
+            .zip(&b.data)
+            .fold((0.0, 0.0), |(re, im), (a, b)| {
+                (
+                    re + a.re * b.re + a.im * b.im,
+                    im + a.re * b.im - a.im * b.re,
+                )
+            });
+        (re.hypot(im) / a.size as f64 - 1.0).abs() < 1e-9
+    }
+}
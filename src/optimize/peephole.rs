@@ -0,0 +1,181 @@
+use super::{combine, is_identity};
+use circuit::{Circuit, Instruction};
+use dag::{Dag, NodeIndex};
+
+/// Cancels and merges neighbouring gates, and removes rotations by nothing.
+///
+/// * Pairs of the same self-inverse gate, such as `h`, `x`, `cx` or `ccx`,
+///   on the same qubits cancel, as do `s` and `sdg`, and `t` and `tdg`.
+///   `CX` and `cx` are the same gate.
+/// * Neighbouring `u1`, `rz`, `rx`, `ry`, `cu1` and `crz` gates on the same
+///   qubits are merged into one, adding their angles. `u1` and `rz` are the
+///   same gate.
+/// * `U` and `u3` gates are merged when either one is a rotation about Z,
+///   with `theta` of 0.
+/// * Rotations by a whole number of turns are removed.
+///
+/// Gates are neighbours when nothing else uses their qubits between them, and
+/// they have the same condition. Angles are added as expressions, and
+/// simplified when they are rational multiples of pi, such as `pi/4 + pi/4`,
+/// or plain numbers.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{optimize, Expr, Operation};
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// h q[0];
+/// h q[0];
+/// u1(pi/4) q[1];
+/// rz(pi/4) q[1];
+/// cx q[0], q[1];
+/// t q[1];
+/// tdg q[1];
+/// cx q[0], q[1];
+/// barrier q;
+/// cx q[0], q[1];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let optimized = optimize::peephole(&circuit);
+///
+/// let operations: Vec<_> = optimized.instructions.into_iter().map(|i| i.operation).collect();
+/// assert_eq!(
+///     operations,
+///     vec![
+///         Operation::Gate("u1".to_string(), vec![Expr::parse("pi/2").unwrap()], vec![1]),
+///         Operation::Barrier(vec![0, 1]),
+///         Operation::Gate("cx".to_string(), vec![], vec![0, 1]),
+///     ]
+/// );
+/// ```
+pub fn peephole(circuit: &Circuit) -> Circuit {
+    let standard = circuit.standard_gates();
+    let mut dag = Dag::from_circuit(circuit);
+
+    for node in dag.topological() {
+        if is_identity(&standard, &dag.instruction(node).operation) {
+            dag.remove(node);
+            continue;
+        }
+
+        // Combine the node with the one before it, for as long as they combine
+        let mut node = node;
+        while let Some(previous) = neighbour(&dag, node) {
+            let combined = match combine(
+                &standard,
+                &dag.instruction(previous).operation,
+                &dag.instruction(node).operation,
+            ) {
+                Some(combined) => combined,
+                None => break,
+            };
+            let condition = dag.remove(node).condition;
+            let instructions = combined
+                .into_iter()
+                .filter(|operation| !is_identity(&standard, operation))
+                .map(|operation| Instruction {
+                    operation,
+                    condition: condition.clone(),
+                })
+                .collect();
+            let nodes = dag
+                .substitute(previous, instructions)
+                .expect("the combined gate uses the same qubits");
+            match nodes.first() {
+                Some(&combined) => node = combined,
+                None => break,
+            }
+        }
+    }
+    dag.to_circuit()
+}
+
+/// The node just before a node on all of its wires, when it uses the same
+/// wires, and has the same condition.
+fn neighbour(dag: &Dag, node: NodeIndex) -> Option<NodeIndex> {
+    let wires = dag.wires(node);
+    let previous = dag.predecessor(node, *wires.first()?)?;
+    if dag.wires(previous) == wires
+        && wires
+            .iter()
+            .all(|&w| dag.predecessor(node, w) == Some(previous))
+        && dag.instruction(previous).condition == dag.instruction(node).condition
+    {
+        Some(previous)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn peephole_test() {
+    use super::unitary::{equivalent, unitary};
+    use circuit::tests::flatten;
+    use matrix::Matrix;
+
+    let source = "OPENQASM 2.0;
+        include \"tests/source/qelib1.inc\";
+        qreg q[3];
+        creg c[1];
+        gate g a { h a; }
+        x q[0];
+        cx q[1], q[2];
+        x q[0];
+        cx q[1], q[2];
+        ccx q[0], q[1], q[2];
+        ccx q[0], q[1], q[2];
+        g q[0];
+        g q[0];
+        CX q[0], q[1];
+        cx q[0], q[1];
+        u1(0.5) q[2];
+        rz(0.25) q[2];
+        U(0, 0.125, 0.125) q[1];
+        u3(0.3, 0.4, 0.5) q[1];
+        U(0.6, 0.7, 0.8) q[1];
+        U(0, -1, 1) q[1];
+        rx(pi) q[0];
+        rx(pi) q[0];
+        crz(2*pi) q[0], q[2];
+        cu1(pi) q[0], q[2];
+        cu1(pi) q[0], q[2];
+        if(c==1) s q[1];
+        if(c==1) sdg q[1];
+        if(c==0) t q[2];
+        tdg q[2];";
+    let circuit = flatten(source);
+    let optimized = peephole(&circuit);
+
+    let statements: Vec<_> = optimized
+        .to_ast()
+        .into_iter()
+        .skip_while(|node| !matches!(*node, ::AstNode::CReg(..)))
+        .skip(1)
+        .map(|node| format!("{:?}", node))
+        .collect();
+    assert_eq!(
+        statements,
+        vec![
+            r#"ApplyGate("g", [Qubit("q", 0)], [])"#,
+            r#"ApplyGate("g", [Qubit("q", 0)], [])"#,
+            r#"ApplyGate("u1", [Qubit("q", 2)], ["0.75"])"#,
+            r#"ApplyGate("u3", [Qubit("q", 1)], ["0.3", "0.4", "0.75"])"#,
+            r#"U(Qubit("q", 1), ["0.6", "0.7", "0.8"])"#,
+            r#"ApplyGate("crz", [Qubit("q", 0), Qubit("q", 2)], ["2*pi"])"#,
+            r#"If("c", 0, ApplyGate("t", [Qubit("q", 2)], []))"#,
+            r#"ApplyGate("tdg", [Qubit("q", 2)], [])"#,
+        ]
+    );
+
+    // The unitaries of the gates without conditions are the same
+    let before = unitary(&circuit);
+    assert!(equivalent(&before, &unitary(&optimized)));
+    assert!(!equivalent(&before, &Matrix::identity(8)));
+}
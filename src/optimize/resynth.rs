@@ -0,0 +1,363 @@
+use circuit::{Circuit, Instruction, Operation};
+use expr::Expr;
+use matrix::Matrix;
+use std::collections::{BTreeMap, HashSet};
+use std::f64::consts::PI;
+
+/// How to resynthesize runs of single-qubit gates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResynthesisOptions {
+    /// How close angles and the entries of unitaries have to be to be treated
+    /// as equal, such as when deciding whether a run does nothing.
+    pub tolerance: f64,
+}
+
+impl Default for ResynthesisOptions {
+    fn default() -> ResynthesisOptions {
+        ResynthesisOptions { tolerance: 1e-10 }
+    }
+}
+
+/// A run of single-qubit gates on a qubit: the indices of its instructions,
+/// and the unitary of the gates so far.
+struct Run {
+    instructions: Vec<usize>,
+    unitary: Matrix,
+}
+
+/// Replaces each run of single-qubit gates on a qubit with one gate.
+///
+/// A run is as many `U` gates, and single-qubit gates defined as in
+/// `qelib1.inc`, in a row on a qubit as possible, with the same condition.
+/// Their unitaries are multiplied together, and the product is written as
+/// `U(theta, phi, lambda)`, up to a global phase. The run is replaced by:
+///
+/// * nothing, if the product is the identity,
+/// * `u1(lambda)` if `theta` is 0, or `u2(phi, lambda)` if `theta` is pi/2,
+///   when the program defines them as `qelib1.inc` does,
+/// * `u3(theta, phi, lambda)` if the program defines it as `qelib1.inc` does,
+/// * otherwise, `U(theta, phi, lambda)`.
+///
+/// The new gate takes the place of the first gate in the run, and its angles
+/// are numbers, with `phi` and `lambda` in `(-pi, pi]`. Runs of one gate are
+/// only changed when they do nothing. Gates with parameters that aren't
+/// numbers end runs, as other operations on the qubit do.
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::optimize::{self, ResynthesisOptions};
+/// use qasm::{Expr, Operation};
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// h q[0];
+/// t q[0];
+/// t q[0];
+/// h q[1];
+/// h q[1];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let resynthesized = optimize::resynthesize(&circuit, &ResynthesisOptions::default());
+///
+/// // h, then s, is u2(pi/2, pi)
+/// let operations: Vec<_> = resynthesized.instructions.into_iter().map(|i| i.operation).collect();
+/// match operations[..] {
+///     [Operation::Gate(ref name, ref params, ref qubits)] => {
+///         assert_eq!((name.as_str(), qubits.as_slice()), ("u2", &[0][..]));
+///         let params: Vec<_> = params.iter().map(|p| p.eval().unwrap()).collect();
+///         assert!((params[0] - 1.5707963267948966).abs() < 1e-9);
+///         assert!((params[1] - 3.141592653589793).abs() < 1e-9);
+///     }
+///     _ => panic!("expected one gate, got {:?}", operations),
+/// }
+/// ```
+pub fn resynthesize(circuit: &Circuit, options: &ResynthesisOptions) -> Circuit {
+    let standard = circuit.standard_gates();
+    let mut instructions: Vec<Option<Instruction>> =
+        circuit.instructions.iter().cloned().map(Some).collect();
+    // The runs that haven't ended yet, by qubit
+    let mut runs: BTreeMap<usize, Run> = BTreeMap::new();
+
+    for (i, instruction) in circuit.instructions.iter().enumerate() {
+        if let Some((qubit, unitary)) = single_qubit(circuit, &standard, instruction) {
+            let continues = runs.get(&qubit).is_some_and(|run| {
+                circuit.instructions[run.instructions[0]].condition == instruction.condition
+            });
+            if !continues {
+                if let Some(run) = runs.remove(&qubit) {
+                    end(&standard, &mut instructions, run, options.tolerance);
+                }
+                runs.insert(
+                    qubit,
+                    Run {
+                        instructions: Vec::new(),
+                        unitary: Matrix::identity(2),
+                    },
+                );
+            }
+            let run = runs.get_mut(&qubit).expect("the run was just started");
+            run.instructions.push(i);
+            run.unitary = unitary.mul(&run.unitary);
+            continue;
+        }
+
+        // Runs end at other operations on their qubit, and measurements into
+        // a bit of their condition
+        let ended: Vec<usize> = runs
+            .iter()
+            .filter(|&(&qubit, run)| {
+                uses(&instruction.operation, qubit)
+                    || match (
+                        &instruction.operation,
+                        &circuit.instructions[run.instructions[0]].condition,
+                    ) {
+                        (&Operation::Measure(_, bit), Some(condition)) => {
+                            let register = &condition.register;
+                            register.offset <= bit && bit < register.offset + register.size
+                        }
+                        _ => false,
+                    }
+            })
+            .map(|(&qubit, _)| qubit)
+            .collect();
+        for qubit in ended {
+            let run = runs.remove(&qubit).expect("the run hasn't ended");
+            end(&standard, &mut instructions, run, options.tolerance);
+        }
+    }
+    for (_, run) in runs {
+        end(&standard, &mut instructions, run, options.tolerance);
+    }
+
+    Circuit {
+        instructions: instructions.into_iter().flatten().collect(),
+        ..circuit.clone()
+    }
+}
+
+/// The qubit and unitary of a `U` gate, or a single-qubit gate defined as in
+/// `qelib1.inc`, with parameters that are numbers.
+fn single_qubit(
+    circuit: &Circuit,
+    standard: &HashSet<&str>,
+    instruction: &Instruction,
+) -> Option<(usize, Matrix)> {
+    match instruction.operation {
+        Operation::Gate(ref name, ref params, ref qubits)
+            if qubits.len() == 1 && (name == "U" || standard.contains(name.as_str())) =>
+        {
+            let params = params
+                .iter()
+                .map(|p| p.eval().ok().filter(|v| v.is_finite()))
+                .collect::<Option<Vec<_>>>()?;
+            Some((qubits[0], circuit.unitary(name, &params).ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Whether an operation acts on a qubit.
+fn uses(operation: &Operation, qubit: usize) -> bool {
+    match *operation {
+        Operation::Gate(_, _, ref qubits) | Operation::Barrier(ref qubits) => {
+            qubits.contains(&qubit)
+        }
+        Operation::Measure(q, _) | Operation::Reset(q) => q == qubit,
+    }
+}
+
+/// Replaces the instructions of a run that has ended with one gate.
+fn end(
+    standard: &HashSet<&str>,
+    instructions: &mut [Option<Instruction>],
+    run: Run,
+    tolerance: f64,
+) {
+    let first = run.instructions[0];
+    let qubit = match instructions[first] {
+        Some(Instruction {
+            operation: Operation::Gate(_, _, ref qubits),
+            ..
+        }) => qubits[0],
+        _ => unreachable!("runs are made of gates"),
+    };
+    let operation = synthesize(standard, &run.unitary, tolerance).map(|(name, params)| {
+        Operation::Gate(
+            name.to_string(),
+            params.into_iter().map(Expr::Real).collect(),
+            vec![qubit],
+        )
+    });
+    if run.instructions.len() == 1 && operation.is_some() {
+        return;
+    }
+
+    let condition = instructions[first]
+        .as_ref()
+        .and_then(|i| i.condition.clone());
+    for &i in &run.instructions {
+        instructions[i] = None;
+    }
+    instructions[first] = operation.map(|operation| Instruction {
+        operation,
+        condition,
+    });
+}
+
+/// The cheapest gate with a unitary, up to a global phase, or nothing if it
+/// is the identity.
+fn synthesize(
+    standard: &HashSet<&str>,
+    unitary: &Matrix,
+    tolerance: f64,
+) -> Option<(&'static str, Vec<f64>)> {
+    let (theta, phi, lambda) = u_angles(unitary, tolerance);
+    if theta.abs() < tolerance {
+        let lambda = wrap(phi + lambda, tolerance);
+        if lambda.abs() < tolerance {
+            None
+        } else if standard.contains("u1") {
+            Some(("u1", vec![lambda]))
+        } else {
+            Some(("U", vec![0.0, 0.0, lambda]))
+        }
+    } else if (theta - PI / 2.0).abs() < tolerance && standard.contains("u2") {
+        Some(("u2", vec![phi, lambda]))
+    } else if standard.contains("u3") {
+        Some(("u3", vec![theta, phi, lambda]))
+    } else {
+        Some(("U", vec![theta, phi, lambda]))
+    }
+}
+
+/// The angles `theta`, `phi` and `lambda` of the `U` gate with a unitary, up
+/// to a global phase. `theta` is in `[0, pi]`, and `phi` and `lambda` are in
+/// `(-pi, pi]`.
+///
+/// Entries smaller than the tolerance are treated as 0. When `theta` is 0,
+/// `phi` is 0, and when it is pi, `lambda` is 0.
+pub(super) fn u_angles(unitary: &Matrix, tolerance: f64) -> (f64, f64, f64) {
+    // U(theta, phi, lambda) is e^(i alpha) times
+    // [[cos(theta/2), -e^(i lambda) sin(theta/2)],
+    //  [e^(i phi) sin(theta/2), e^(i (phi + lambda)) cos(theta/2)]]
+    let (a, b, c, d) = (
+        unitary.get(0, 0),
+        unitary.get(0, 1),
+        unitary.get(1, 0),
+        unitary.get(1, 1),
+    );
+    let theta = 2.0 * c.norm().atan2(a.norm());
+    if c.norm() < tolerance {
+        (0.0, 0.0, wrap(d.arg() - a.arg(), tolerance))
+    } else if a.norm() < tolerance {
+        (PI, wrap(c.arg() - (-b).arg(), tolerance), 0.0)
+    } else {
+        (
+            theta,
+            wrap(c.arg() - a.arg(), tolerance),
+            wrap((-b).arg() - a.arg(), tolerance),
+        )
+    }
+}
+
+/// An angle, plus or minus a whole number of turns, in `(-pi, pi]`. Angles
+/// within the tolerance of 0 or pi are made exactly 0 or pi.
+fn wrap(angle: f64, tolerance: f64) -> f64 {
+    let angle = angle % (2.0 * PI);
+    let angle = if angle > PI {
+        angle - 2.0 * PI
+    } else if angle <= -PI {
+        angle + 2.0 * PI
+    } else {
+        angle
+    };
+    if angle.abs() < tolerance {
+        0.0
+    } else if PI - angle.abs() < tolerance {
+        PI
+    } else {
+        angle
+    }
+}
+
+#[test]
+fn resynthesize_test() {
+    use super::unitary::{equivalent, unitary};
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        include \"tests/source/qelib1.inc\";
+        qreg q[3];
+        creg c[1];
+        h q[0];
+        t q[0];
+        ry(0.3) q[0];
+        cx q[0], q[1];
+        U(0.1, 0.2, 0.3) q[0];
+        x q[1];
+        y q[1];
+        z q[1];
+        rx(0.5) q[2];
+        rx(-0.5) q[2];
+        rz(0.000001) q[2];
+        if(c==1) x q[2];
+        if(c==1) x q[2];
+        h q[2];
+        measure q[1] -> c[0];
+        if(c==1) h q[2];";
+    let circuit = flatten(source);
+    let resynthesized = resynthesize(&circuit, &ResynthesisOptions::default());
+
+    let names: Vec<_> = resynthesized
+        .instructions
+        .iter()
+        .map(|i| match i.operation {
+            Operation::Gate(ref name, _, ref qubits) => format!("{} {:?}", name, qubits),
+            ref operation => format!("{:?}", operation),
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "u3 [0]",
+            "cx [0, 1]",
+            "U [0]",
+            "u1 [2]",
+            "h [2]",
+            "Measure(1, 0)",
+            "h [2]",
+        ]
+    );
+
+    // The unitaries up to the measurement are the same
+    let prefix = |circuit: &Circuit| {
+        let end = circuit
+            .instructions
+            .iter()
+            .position(|i| matches!(i.operation, Operation::Measure(..)))
+            .unwrap();
+        Circuit {
+            instructions: circuit.instructions[..end].to_vec(),
+            ..circuit.clone()
+        }
+    };
+    assert!(equivalent(
+        &unitary(&prefix(&circuit)),
+        &unitary(&prefix(&resynthesized))
+    ));
+
+    // A looser tolerance leaves out the small rotation
+    let loose = resynthesize(&circuit, &ResynthesisOptions { tolerance: 1e-5 });
+    assert_eq!(loose.instructions.len(), names.len() - 1);
+
+    for &(theta, phi, lambda) in &[(0.0, 0.0, 1.0), (PI, 0.5, 0.0), (1.0, -2.0, 3.0)] {
+        let (t, p, l) = u_angles(&Matrix::u(theta, phi, lambda), 1e-12);
+        assert!((t - theta).abs() < 1e-9 && (p - phi).abs() < 1e-9 && (l - lambda).abs() < 1e-9);
+    }
+}
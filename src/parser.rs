@@ -2,43 +2,126 @@
 //! Most methods are not documented, and should only be accessed
 //! indirectly from the `parse` method.
 
+use std::cell::Cell;
+use std::io;
 use std::iter::Peekable;
+use std::mem;
+use std::rc::Rc;
 use token::Token;
 use error::Error;
 use ast::{Argument, AstNode};
 use std::result;
 
-const SUPPORTED_VERSIONS: [f32; 1] = [
+const SUPPORTED_VERSIONS: [f64; 1] = [
     2.0,
 ];
 
-type TokenStream<'a> = Peekable<std::slice::Iter<'a, Token>>;
+/// A stream of tokens. The tokens can store their identifiers as any
+/// string type, so both owned and borrowed tokens can be parsed.
+type TokenStream<I> = Peekable<I>;
 type Result<T> = result::Result<T, Error>;
 
-pub fn parse(tokens: &mut TokenStream) -> Result<Vec<AstNode>> {
-    let mut nodes = vec![];
+pub fn parse<S, I>(tokens: I) -> Result<Vec<AstNode>>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    Statements::new(tokens)?.collect()
+}
+
+/// An iterator that parses one statement at a time.
+///
+/// The version header is checked when the iterator is created, then each
+/// call to `next` consumes only the tokens of the next statement. This means
+/// that the whole program never has to be held in memory.
+///
+/// After the first error, no more statements are returned.
+pub struct Statements<I: Iterator> {
+    tokens: TokenStream<I>,
+    io_error: Option<Rc<Cell<Option<io::ErrorKind>>>>,
+    done: bool,
+}
+
+impl<S, I> Statements<I>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    /// Reads the version header from the tokens, and checks that it is supported.
+    pub fn new(tokens: I) -> Result<Statements<I>> {
+        let mut tokens = tokens.peekable();
+
+        // Check that the version is first, and that it is version 2.0
+        if !SUPPORTED_VERSIONS.contains(&version(&mut tokens)?) {
+            return Err(Error::UnsupportedVersion);
+        }
 
-    // Check that the version is first, and that it is version 2.0
-    if !SUPPORTED_VERSIONS.contains(&version(tokens)?) {
-        return Err(Error::UnsupportedVersion);
+        Ok(Statements {
+            tokens,
+            io_error: None,
+            done: false,
+        })
     }
 
-    while let Some(token) = tokens.peek() {
-        let node = parse_node(tokens)?;
-        nodes.push(node);
+    /// Like `new`, but reports an `IoError` when reading the tokens failed.
+    pub(crate) fn with_io_error(
+        tokens: I,
+        io_error: Rc<Cell<Option<io::ErrorKind>>>,
+    ) -> Result<Statements<I>> {
+        match Statements::new(tokens) {
+            Ok(statements) => Ok(Statements {
+                io_error: Some(io_error),
+                ..statements
+            }),
+            Err(e) => Err(io_error.get().map_or(e, Error::IoError)),
+        }
     }
+}
+
+impl<S, I> Iterator for Statements<I>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    type Item = Result<AstNode>;
+
+    fn next(&mut self) -> Option<Result<AstNode>> {
+        if self.done {
+            return None;
+        }
+
+        // A failed read looks like the end of the tokens, so the
+        // reader is checked for an error whenever parsing stops.
+        let io_error = |statements: &Statements<I>| {
+            statements.io_error.as_ref().and_then(|e| e.get())
+        };
+
+        if self.tokens.peek().is_none() {
+            self.done = true;
+            return io_error(self).map(|kind| Err(Error::IoError(kind)));
+        }
 
-    Ok(nodes)
+        let result = parse_node(&mut self.tokens);
+        if let Err(e) = result {
+            self.done = true;
+            return Some(Err(io_error(self).map_or(e, Error::IoError)));
+        }
+        Some(result)
+    }
 }
 
-fn parse_node(tokens: &mut TokenStream) -> Result<AstNode> {
-    match tokens.next().ok_or(Error::SourceError)?.clone() {
+fn parse_node<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    match tokens.next().ok_or(Error::SourceError)? {
         Token::QReg => qreg(tokens),
         Token::CReg => creg(tokens),
         Token::Barrier => barrier(tokens),
         Token::Reset => reset(tokens),
         Token::Measure => measure(tokens),
-        Token::Id(i) => application(tokens, i),
+        Token::Id(i) => application(tokens, i.as_ref().to_string()),
         Token::Opaque => opaque(tokens),
         Token::Gate => gate(tokens),
         Token::If => if_(tokens),
@@ -47,7 +130,11 @@ fn parse_node(tokens: &mut TokenStream) -> Result<AstNode> {
     }
 }
 
-pub fn version(tokens: &mut TokenStream) -> Result<f32> {
+pub fn version<S, I>(tokens: &mut TokenStream<I>) -> Result<f64>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     match_token(tokens, Token::OpenQASM)
         .map_err(|_| Error::MissingVersion)?;
     let version = match_real(tokens)?;
@@ -56,7 +143,11 @@ pub fn version(tokens: &mut TokenStream) -> Result<f32> {
     Ok(version)
 }
 
-pub fn qreg(tokens: &mut TokenStream) -> Result<AstNode> {
+pub fn qreg<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     // QReg -> Identifier -> Left Square Bracket -> Int -> Right Square Bracket -> Semicolon
     let identifier = match_identifier(tokens)?;
     match_token(tokens, Token::LSParen)?;
@@ -67,7 +158,11 @@ pub fn qreg(tokens: &mut TokenStream) -> Result<AstNode> {
     Ok(AstNode::QReg(identifier, num))
 }
 
-pub fn creg(tokens: &mut TokenStream) -> Result<AstNode> {
+pub fn creg<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     // CReg -> Identifier -> Left Square Bracket -> Int -> Right Square Bracket -> Semicolon
     let identifier = match_identifier(tokens)?;
     match_token(tokens, Token::LSParen)?;
@@ -78,7 +173,11 @@ pub fn creg(tokens: &mut TokenStream) -> Result<AstNode> {
     Ok(AstNode::CReg(identifier, num))
 }
 
-pub fn if_(tokens: &mut TokenStream) -> Result<AstNode> {
+pub fn if_<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     match_token(tokens, Token::LParen)?;
     let id = match_identifier(tokens)?;
     match_token(tokens, Token::Equals)?;
@@ -89,15 +188,23 @@ pub fn if_(tokens: &mut TokenStream) -> Result<AstNode> {
     Ok(AstNode::If(id, val, Box::new(node)))
 }
 
-pub fn barrier(tokens: &mut TokenStream) -> Result<AstNode> {
-    // Barrier -> Argument -> Semicolon
-    let argument = match_argument(tokens)?;
+pub fn barrier<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    // Barrier -> Argument list -> Semicolon
+    let arguments = match_argument_list(tokens)?;
     match_semicolon(tokens)?;
 
-    Ok(AstNode::Barrier(argument))
+    Ok(AstNode::Barrier(arguments))
 }
 
-pub fn reset(tokens: &mut TokenStream) -> Result<AstNode> {
+pub fn reset<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     // reset -> Argument -> Semicolon
     let argument = match_argument(tokens)?;
     match_semicolon(tokens)?;
@@ -105,7 +212,11 @@ pub fn reset(tokens: &mut TokenStream) -> Result<AstNode> {
     Ok(AstNode::Reset(argument))
 }
 
-pub fn measure(tokens: &mut TokenStream) -> Result<AstNode> {
+pub fn measure<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     // Measure -> Argument -> Arrow -> Argument -> Semicolon
     let arg_1 = match_argument(tokens)?;
     match_token(tokens, Token::Arrow)?;
@@ -115,7 +226,11 @@ pub fn measure(tokens: &mut TokenStream) -> Result<AstNode> {
     Ok(AstNode::Measure(arg_1, arg_2))
 }
 
-pub fn application(tokens: &mut TokenStream, id: String) -> Result<AstNode> {
+pub fn application<S, I>(tokens: &mut TokenStream<I>, id: String) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     // id -> argument list -> Semicolon;
     // id -> () -> argument list -> Semicolon;
     // id -> ( Expr list ) ->
@@ -136,14 +251,49 @@ pub fn application(tokens: &mut TokenStream, id: String) -> Result<AstNode> {
     let list = match_argument_list(tokens)?;
     match_semicolon(tokens)?;
 
-    Ok(AstNode::ApplyGate(id, list, params))
+    match id.as_str() {
+        "U" => builtin_u(list, params),
+        "CX" => builtin_cx(list, params),
+        _ => Ok(AstNode::ApplyGate(id, list, params)),
+    }
+}
+
+fn builtin_u(mut list: Vec<Argument>, params: Vec<String>) -> Result<AstNode> {
+    // U -> ( Expr, Expr, Expr ) -> argument -> Semicolon
+    if list.len() != 1 {
+        return Err(Error::WrongArgumentCount("U".to_string()));
+    }
+    let mut params = params.into_iter();
+    match (params.next(), params.next(), params.next(), params.next()) {
+        (Some(theta), Some(phi), Some(lambda), None) => {
+            Ok(AstNode::U(list.remove(0), [theta, phi, lambda]))
+        }
+        _ => Err(Error::WrongParameterCount("U".to_string())),
+    }
+}
+
+fn builtin_cx(mut list: Vec<Argument>, params: Vec<String>) -> Result<AstNode> {
+    // CX -> argument -> Comma -> argument -> Semicolon
+    if !params.is_empty() {
+        return Err(Error::WrongParameterCount("CX".to_string()));
+    }
+    if list.len() != 2 {
+        return Err(Error::WrongArgumentCount("CX".to_string()));
+    }
+    let target = list.remove(1);
+    let control = list.remove(0);
+    Ok(AstNode::CX(control, target))
 }
 
-pub fn opaque(tokens: &mut TokenStream) -> Result<AstNode> {
+pub fn opaque<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     // opaque -> id -> argument list -> Semicolon;
     // opaque -> id -> () -> argument list -> Semicolon;
     // opaque -> id -> ( Expr list ) ->
-    let id = match_identifier(tokens)?;
+    let id = match_gate_name(tokens)?;
 
     let params = if let Some(Token::LParen) = tokens.peek() {
         tokens.next();
@@ -165,11 +315,15 @@ pub fn opaque(tokens: &mut TokenStream) -> Result<AstNode> {
     Ok(AstNode::Opaque(id, list, params))
 }
 
-pub fn gate(tokens: &mut TokenStream) -> Result<AstNode> {
-    // gate -> id -> argument list -> { -> list of applications -> }
+pub fn gate<S, I>(tokens: &mut TokenStream<I>) -> Result<AstNode>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    // gate -> id -> argument list -> { -> list of applications and barriers -> }
     // gate -> id -> () -> argument list ->{ -> list of applications -> }
     // gate -> id -> ( Expr list ) -> { -> list of applications -> }
-    let id = match_identifier(tokens)?;
+    let id = match_gate_name(tokens)?;
 
     let params = if let Some(Token::LParen) = tokens.peek() {
         tokens.next();
@@ -188,7 +342,7 @@ pub fn gate(tokens: &mut TokenStream) -> Result<AstNode> {
     let list = match_id_list(tokens)?;
     match_token(tokens, Token::LCParen)?;
 
-    let applications = if tokens.peek().ok_or(Error::SourceError)? != &&Token::RCParen {
+    let applications = if !matches!(tokens.peek().ok_or(Error::SourceError)?, Token::RCParen) {
         match_application_list(tokens)?
     } else {
         vec![]
@@ -202,20 +356,35 @@ pub fn gate(tokens: &mut TokenStream) -> Result<AstNode> {
 //////////////////////////////////////////////////////////////////////
 // Terminals
 //////////////////////////////////////////////////////////////////////
-pub fn match_application_list(tokens: &mut TokenStream) -> Result<Vec<AstNode>> {
-    let id = match_identifier(tokens)?;
-    let head = application(tokens, id)?;
-    let mut args = vec![head];
+pub fn match_application_list<S, I>(tokens: &mut TokenStream<I>) -> Result<Vec<AstNode>>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    let mut args = vec![];
+
+    loop {
+        let node = match tokens.next().ok_or(Error::SourceError)? {
+            Token::Id(id) => application(tokens, id.as_ref().to_string())?,
+            Token::Barrier => barrier(tokens)?,
+            _ => return Err(Error::SourceError),
+        };
+        args.push(node);
 
-    while let &Token::Id(id) = tokens.peek().ok_or(Error::SourceError)? {
-        let tail = application(tokens, id.clone())?;
-        args.push(tail);
+        match tokens.peek().ok_or(Error::SourceError)? {
+            Token::Id(_) | Token::Barrier => {}
+            _ => break,
+        }
     }
 
     Ok(args)
 }
 
-pub fn match_argument_list(tokens: &mut TokenStream) -> Result<Vec<Argument>> {
+pub fn match_argument_list<S, I>(tokens: &mut TokenStream<I>) -> Result<Vec<Argument>>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     let head = match_argument(tokens)?;
     let mut args = vec![head];
 
@@ -228,7 +397,11 @@ pub fn match_argument_list(tokens: &mut TokenStream) -> Result<Vec<Argument>> {
     Ok(args)
 }
 
-pub fn match_mathexpr_list(tokens: &mut TokenStream) -> Result<Vec<String>> {
+pub fn match_mathexpr_list<S, I>(tokens: &mut TokenStream<I>) -> Result<Vec<String>>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     let head = match_mathexpr(tokens)?;
     let mut args = vec![head];
 
@@ -241,7 +414,11 @@ pub fn match_mathexpr_list(tokens: &mut TokenStream) -> Result<Vec<String>> {
     Ok(args)
 }
 
-pub fn match_id_list(tokens: &mut TokenStream) -> Result<Vec<String>> {
+pub fn match_id_list<S, I>(tokens: &mut TokenStream<I>) -> Result<Vec<String>>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     let head = match_identifier(tokens)?;
     let mut args = vec![head];
 
@@ -254,8 +431,12 @@ pub fn match_id_list(tokens: &mut TokenStream) -> Result<Vec<String>> {
     Ok(args)
 }
 
-pub fn match_mathexpr(tokens: &mut TokenStream) -> Result<String> {
-    if let None = tokens.peek() {
+pub fn match_mathexpr<S, I>(tokens: &mut TokenStream<I>) -> Result<String>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    if tokens.peek().is_none() {
         return Err(Error::SourceError);
     }
 
@@ -263,11 +444,12 @@ pub fn match_mathexpr(tokens: &mut TokenStream) -> Result<String> {
     let mut num_open_paren = 0;
 
     // Parse until we find a comma, semicolon or a non matching paren
-    while let Some(token) = tokens.peek().cloned() {
-        let string: String = match token.clone() {
-            Token::Real(f) => f.to_string(),
+    while let Some(token) = tokens.peek() {
+        let string: String = match *token {
+            // Debug keeps the decimal point, and uses an exponent for very large or small numbers
+            Token::Real(f) => format!("{:?}", f),
             Token::NNInteger(n) => n.to_string(),
-            Token::Id(i) => i,
+            Token::Id(ref i) => i.as_ref().to_string(),
             Token::Pi => String::from("pi"),
             Token::LParen => {
                 num_open_paren += 1;
@@ -284,7 +466,8 @@ pub fn match_mathexpr(tokens: &mut TokenStream) -> Result<String> {
             Token::Minus => String::from("-"),
             Token::Times => String::from("*"),
             Token::Divide => String::from("/"),
-            Token::Exp => String::from("^"),
+            Token::Power => String::from("^"),
+            Token::Exp => String::from("exp"),
             Token::Sin => String::from("sin"),
             Token::Cos => String::from("cos"),
             Token::Tan => String::from("tan"),
@@ -294,15 +477,19 @@ pub fn match_mathexpr(tokens: &mut TokenStream) -> Result<String> {
         };
 
         tokens.next();
-        expr_string.push_str(" ");
+        expr_string.push(' ');
         expr_string.push_str(&string);
-        expr_string.push_str(" ");
+        expr_string.push(' ');
     }
 
     Ok(expr_string)
 }
 
-pub fn match_argument(tokens: &mut TokenStream) -> Result<Argument> {
+pub fn match_argument<S, I>(tokens: &mut TokenStream<I>) -> Result<Argument>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     let id = match_identifier(tokens)?;
 
     if let Some(Token::LSParen) = tokens.peek() {
@@ -315,51 +502,90 @@ pub fn match_argument(tokens: &mut TokenStream) -> Result<Argument> {
     }
 }
 
-pub fn match_real(tokens: &mut TokenStream) -> Result<f32> {
+pub fn match_real<S, I>(tokens: &mut TokenStream<I>) -> Result<f64>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     match tokens.next() {
-        Some(Token::Real(n)) => Ok(*n),
+        Some(Token::Real(n)) => Ok(n),
         Some(_) => Err(Error::MissingReal),
         None => Err(Error::SourceError),
     }
 }
 
-pub fn match_nninteger(tokens: &mut TokenStream) -> Result<i32> {
+pub fn match_nninteger<S, I>(tokens: &mut TokenStream<I>) -> Result<i32>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     match tokens.next() {
-        Some(Token::NNInteger(n)) => Ok(*n),
+        Some(Token::NNInteger(n)) => Ok(n),
         Some(_) => Err(Error::MissingInt),
         None => Err(Error::SourceError),
     }
 }
 
-pub fn match_identifier(tokens: &mut TokenStream) -> Result<String> {
-    if let None = tokens.peek() {
+pub fn match_identifier<S, I>(tokens: &mut TokenStream<I>) -> Result<String>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    if tokens.peek().is_none() {
         return Err(Error::SourceError);
     }
     match tokens.next() {
-        Some(Token::Id(s)) => Ok(s.clone()),
+        Some(Token::Id(s)) => Ok(s.as_ref().to_string()),
         Some(_) => Err(Error::MissingIdentifier),
         None => Err(Error::SourceError),
     }
 }
 
-pub fn match_token(tokens: &mut TokenStream, eq_token: Token) -> Result<()> {
+/// Matches the name of a gate being declared, which can't be a built in gate.
+pub fn match_gate_name<S, I>(tokens: &mut TokenStream<I>) -> Result<String>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    let id = match_identifier(tokens)?;
+    match id.as_str() {
+        "U" | "CX" => Err(Error::Redefinition(id)),
+        _ => Ok(id),
+    }
+}
+
+// Only ever used to match tokens without data, so comparing
+// the variants is enough.
+pub fn match_token<S, I>(tokens: &mut TokenStream<I>, eq_token: Token<S>) -> Result<()>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     match tokens.next() {
-        Some(token) if &eq_token == token => Ok(()),
+        Some(ref token) if mem::discriminant(&eq_token) == mem::discriminant(token) => Ok(()),
         _ => Err(Error::SourceError),
     }
 }
 
 #[allow(dead_code)]
-pub fn match_token_peek(tokens: &mut TokenStream, eq_token: Token) -> Result<()> {
-    match tokens.peek().copied() {
-        Some(token) if &eq_token == token => Ok(()),
+pub fn match_token_peek<S, I>(tokens: &mut TokenStream<I>, eq_token: Token<S>) -> Result<()>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
+    match tokens.peek() {
+        Some(token) if mem::discriminant(&eq_token) == mem::discriminant(token) => Ok(()),
         _ => Err(Error::SourceError),
     }
 }
 
-pub fn match_semicolon(tokens: &mut TokenStream) -> Result<()> {
+pub fn match_semicolon<S, I>(tokens: &mut TokenStream<I>) -> Result<()>
+where
+    S: AsRef<str>,
+    I: Iterator<Item = Token<S>>,
+{
     match tokens.next() {
-        Some(&Token::Semicolon) => Ok(()),
+        Some(Token::Semicolon) => Ok(()),
         Some(_) => Err(Error::MissingSemicolon),
         _ => Err(Error::SourceError),
     }
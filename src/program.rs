@@ -0,0 +1,271 @@
+use ast::{Argument, AstNode};
+use circuit::Circuit;
+use error::Error;
+use semantic;
+use std::collections::HashMap;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// A quantum or classical register in a [Program](struct.Program.html).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Register {
+    /// The identifier of the register.
+    pub name: String,
+    /// The number of qubits or bits in the register.
+    pub size: usize,
+    /// The global index of the register's first qubit or bit. The registers
+    /// are laid out one after another, in the order they are declared.
+    pub offset: usize,
+}
+
+/// A gate definition in a [Program](struct.Program.html).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Gate {
+    /// The name of the gate.
+    pub name: String,
+    /// The names of the qubit arguments.
+    pub qubits: Vec<String>,
+    /// The names of the parameters.
+    pub params: Vec<String>,
+    /// The applications that make up the gate.
+    pub body: Vec<AstNode>,
+}
+
+/// An opaque gate declaration in a [Program](struct.Program.html).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Opaque {
+    /// The name of the gate.
+    pub name: String,
+    /// The names of the qubit arguments.
+    pub qubits: Vec<String>,
+    /// The names of the parameters.
+    pub params: Vec<String>,
+}
+
+/// A parsed program, with its declarations separated from the statements that are run.
+///
+/// This is returned by [parse_str](fn.parse_str.html) and [parse_file](fn.parse_file.html),
+/// and can be built from any list of AST nodes with `Program::from_ast`.
+/// Registers, gates and opaque gates can be looked up by name.
+///
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// qreg a[2];
+/// qreg b[3];
+/// creg c[2];
+/// gate g x, y { CX x, y; }
+/// g a[0], b[1];
+/// "#;
+///
+/// let program = qasm::parse_str(source, &env::current_dir().unwrap()).unwrap();
+///
+/// assert_eq!(program.num_qubits(), 5);
+/// assert_eq!(program.num_clbits(), 2);
+/// assert_eq!(program.qreg("b").unwrap().offset, 2);
+/// assert_eq!(program.qubit_index("b", 1), Some(3));
+/// assert_eq!(program.gate("g").unwrap().qubits, vec!["x", "y"]);
+/// assert_eq!(program.statements().len(), 1);
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Program {
+    qregs: Vec<Register>,
+    cregs: Vec<Register>,
+    gates: Vec<Gate>,
+    opaques: Vec<Opaque>,
+    statements: Vec<AstNode>,
+    // Indexes into the vectors above, by name
+    qreg_index: HashMap<String, usize>,
+    creg_index: HashMap<String, usize>,
+    gate_index: HashMap<String, usize>,
+    opaque_index: HashMap<String, usize>,
+}
+
+impl Program {
+    /// Sorts a list of AST nodes into declarations and statements.
+    ///
+    /// This returns a `Redefinition` error if a name is declared twice, and a
+    /// `SourceError` if a register has a negative size.
+    pub fn from_ast(nodes: Vec<AstNode>) -> Result<Program> {
+        let mut program = Program::default();
+
+        for node in nodes {
+            match node {
+                AstNode::QReg(_, size) | AstNode::CReg(_, size) if size < 0 => {
+                    return Err(Error::SourceError)
+                }
+                AstNode::QReg(name, size) => program.add_qreg(name, size as usize)?,
+                AstNode::CReg(name, size) => program.add_creg(name, size as usize)?,
+                AstNode::Gate(name, qubits, params, body) => program.add_gate(Gate {
+                    name,
+                    qubits,
+                    params,
+                    body,
+                })?,
+                AstNode::Opaque(name, qubits, params) => program.add_opaque(Opaque {
+                    name,
+                    qubits: qubits.iter().map(argument_name).collect(),
+                    params,
+                })?,
+                _ => program.statements.push(node),
+            }
+        }
+
+        Ok(program)
+    }
+
+    /// The quantum registers, in the order they are declared.
+    pub fn qregs(&self) -> &[Register] {
+        &self.qregs
+    }
+
+    /// The classical registers, in the order they are declared.
+    pub fn cregs(&self) -> &[Register] {
+        &self.cregs
+    }
+
+    /// The gate definitions, in the order they are declared.
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    /// The opaque gate declarations, in the order they are declared.
+    pub fn opaques(&self) -> &[Opaque] {
+        &self.opaques
+    }
+
+    /// Every node that isn't a declaration, in the order they are run.
+    pub fn statements(&self) -> &[AstNode] {
+        &self.statements
+    }
+
+    /// Finds a quantum register by name.
+    pub fn qreg(&self, name: &str) -> Option<&Register> {
+        self.qreg_index.get(name).map(|&i| &self.qregs[i])
+    }
+
+    /// Finds a classical register by name.
+    pub fn creg(&self, name: &str) -> Option<&Register> {
+        self.creg_index.get(name).map(|&i| &self.cregs[i])
+    }
+
+    /// Finds a gate definition by name.
+    pub fn gate(&self, name: &str) -> Option<&Gate> {
+        self.gate_index.get(name).map(|&i| &self.gates[i])
+    }
+
+    /// Finds an opaque gate declaration by name.
+    pub fn opaque(&self, name: &str) -> Option<&Opaque> {
+        self.opaque_index.get(name).map(|&i| &self.opaques[i])
+    }
+
+    /// The total number of qubits in all quantum registers.
+    pub fn num_qubits(&self) -> usize {
+        self.qregs.last().map_or(0, |r| r.offset + r.size)
+    }
+
+    /// The total number of bits in all classical registers.
+    pub fn num_clbits(&self) -> usize {
+        self.cregs.last().map_or(0, |r| r.offset + r.size)
+    }
+
+    /// The global index of a qubit, or `None` if it doesn't exist.
+    pub fn qubit_index(&self, register: &str, index: usize) -> Option<usize> {
+        self.qreg(register)
+            .and_then(|r| if index < r.size { Some(r.offset + index) } else { None })
+    }
+
+    /// The global index of a bit, or `None` if it doesn't exist.
+    pub fn clbit_index(&self, register: &str, index: usize) -> Option<usize> {
+        self.creg(register)
+            .and_then(|r| if index < r.size { Some(r.offset + index) } else { None })
+    }
+
+    /// Checks that the program makes sense, as well as being syntactically correct.
+    ///
+    /// This makes sure that every register and gate used has been declared,
+    /// that every index is in range, and that gates are given the right
+    /// number of arguments and parameters.
+    pub fn check(&self) -> Result<()> {
+        semantic::check(self)
+    }
+
+    /// Checks the program, then flattens its registers into a [Circuit](struct.Circuit.html),
+    /// where every operation acts on global qubit and bit indices.
+    pub fn flatten(&self) -> Result<Circuit> {
+        Circuit::from_program(self)
+    }
+
+    fn check_unused(&self, name: &str) -> Result<()> {
+        let used = self.qreg_index.contains_key(name)
+            || self.creg_index.contains_key(name)
+            || self.gate_index.contains_key(name)
+            || self.opaque_index.contains_key(name);
+
+        if used {
+            Err(Error::Redefinition(name.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_qreg(&mut self, name: String, size: usize) -> Result<()> {
+        self.check_unused(&name)?;
+        let offset = self.num_qubits();
+        self.qreg_index.insert(name.clone(), self.qregs.len());
+        self.qregs.push(Register { name, size, offset });
+        Ok(())
+    }
+
+    fn add_creg(&mut self, name: String, size: usize) -> Result<()> {
+        self.check_unused(&name)?;
+        let offset = self.num_clbits();
+        self.creg_index.insert(name.clone(), self.cregs.len());
+        self.cregs.push(Register { name, size, offset });
+        Ok(())
+    }
+
+    fn add_gate(&mut self, gate: Gate) -> Result<()> {
+        self.check_unused(&gate.name)?;
+        self.gate_index.insert(gate.name.clone(), self.gates.len());
+        self.gates.push(gate);
+        Ok(())
+    }
+
+    fn add_opaque(&mut self, opaque: Opaque) -> Result<()> {
+        self.check_unused(&opaque.name)?;
+        self.opaque_index.insert(opaque.name.clone(), self.opaques.len());
+        self.opaques.push(opaque);
+        Ok(())
+    }
+}
+
+fn argument_name(arg: &Argument) -> String {
+    match *arg {
+        Argument::Register(ref id) | Argument::Qubit(ref id, _) => id.clone(),
+    }
+}
+
+#[test]
+fn redefinition_test() {
+    let nodes = vec![
+        AstNode::QReg("q".to_string(), 2),
+        AstNode::CReg("q".to_string(), 2),
+    ];
+    assert_eq!(Program::from_ast(nodes), Err(Error::Redefinition("q".to_string())));
+}
+
+#[test]
+fn negative_size_test() {
+    let nodes = vec![
+        AstNode::QReg("q".to_string(), -1),
+        AstNode::QReg("r".to_string(), 2),
+    ];
+    assert_eq!(Program::from_ast(nodes), Err(Error::SourceError));
+    let nodes = vec![AstNode::CReg("c".to_string(), -1)];
+    assert_eq!(Program::from_ast(nodes), Err(Error::SourceError));
+}
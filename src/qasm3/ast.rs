@@ -0,0 +1,126 @@
+use expr::Function;
+
+/// A classical type, with its size in bits if one was given.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Bit(Option<Expression>),
+    Bool,
+    Int(Option<Expression>),
+    Uint(Option<Expression>),
+    Float(Option<Expression>),
+    Angle(Option<Expression>),
+}
+
+/// A binary operator in an OpenQASM 3 expression.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    And,
+    Or,
+}
+
+/// An OpenQASM 3 expression. Unlike OpenQASM 2.0 parameters, these can
+/// refer to variables and bits, and include comparisons.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    /// An integer
+    Int(i32),
+    /// A real number
+    Real(f64),
+    /// The constant pi
+    Pi,
+    /// `true` or `false`
+    Bool(bool),
+    /// A variable, parameter or register
+    Id(String),
+    /// An element of a register, such as `c[0]`
+    Index(String, Box<Expression>),
+    /// Unary minus
+    Neg(Box<Expression>),
+    /// Logical not, `!`
+    Not(Box<Expression>),
+    /// A binary operation
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    /// A function applied to an expression
+    Call(Function, Box<Expression>),
+}
+
+/// A qubit or bit operand: a whole register, or one element of it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operand {
+    /// A whole register, or a single qubit declared without a size
+    Register(String),
+    /// One element of a register. The index can be any expression, such as a loop variable.
+    Index(String, Expression),
+}
+
+/// A modifier applied to a gate with `@`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Modifier {
+    /// `ctrl @` or `ctrl(n) @`, adding `n` control qubits
+    Ctrl(Expression),
+    /// `negctrl @` or `negctrl(n) @`, adding `n` control qubits that are active when `|0>`
+    NegCtrl(Expression),
+    /// `inv @`, inverting the gate
+    Inv,
+    /// `pow(k) @`, raising the gate to a power
+    Pow(Expression),
+}
+
+/// The values a `for` loop iterates over.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Iterable {
+    /// `[start:end]` or `[start:step:end]`. Both ends are included.
+    Range(Expression, Option<Expression>, Expression),
+    /// `{a, b, c}`
+    Set(Vec<Expression>),
+}
+
+/// OpenQASM 3 statements. These can be pattern matched to evaluate the program.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    /// An include statement that hasn't been resolved.
+    Include(String),
+    /// Declares qubits. The expression is the size of the register, if it has one.
+    /// `qreg` declarations are also returned as this.
+    QubitDecl(String, Option<Expression>),
+    /// Declares bits. The expression is the size of the register, if it has one.
+    /// `creg` declarations are also returned as this.
+    BitDecl(String, Option<Expression>),
+    /// Declares a classical variable, with an optional initial value.
+    ClassicalDecl(Type, String, Option<Expression>),
+    /// Declares a constant.
+    Const(Type, String, Expression),
+    /// Declares an input parameter of the program.
+    Input(Type, String),
+    /// Defines a gate, with its name, parameters, qubits and body.
+    GateDef(String, Vec<String>, Vec<String>, Vec<Statement>),
+    /// Applies a gate, with its modifiers, name, parameters and qubits.
+    /// The built in `U` and `gphase` gates are also returned as this.
+    Gate(Vec<Modifier>, String, Vec<Expression>, Vec<Operand>),
+    /// Measures qubits, storing the result in bits if they are given.
+    /// Both `c = measure q;` and `measure q -> c;` are returned as this.
+    Measure(Operand, Option<Operand>),
+    /// Resets qubits.
+    Reset(Operand),
+    /// A barrier to a list of qubits, or every qubit if it's empty.
+    Barrier(Vec<Operand>),
+    /// Assigns a value to a classical variable or bit.
+    Assign(Operand, Expression),
+    /// A `for` loop, with its variable type and name, what it iterates over, and its body.
+    For(Type, String, Iterable, Vec<Statement>),
+    /// A `while` loop, with its condition and body.
+    While(Expression, Vec<Statement>),
+    /// An `if` statement, with its condition, body, and `else` body.
+    If(Expression, Vec<Statement>, Vec<Statement>),
+}
@@ -0,0 +1,122 @@
+//! # OpenQASM 3
+//!
+//! A front-end for the core of OpenQASM 3. It shares the [Lexer](../struct.Lexer.html)
+//! with OpenQASM 2.0, but produces its own AST of [Statement](enum.Statement.html)s.
+//!
+//! It covers:
+//!
+//! * `qubit[n] q;` and `bit[n] c;` declarations, as well as `qreg` and `creg`
+//! * `const` and `input` declarations, and classical variables
+//! * `c = measure q;`, `reset` and `barrier`
+//! * Gate definitions, and gates with the `ctrl @`, `negctrl @`, `inv @` and `pow(k) @` modifiers
+//! * `for` and `while` loops, and `if` / `else` blocks
+//! * The standard library, `stdgates.inc`, which is built in
+//!
+//! OpenQASM 2.0 programs can be translated to OpenQASM 3 with [translate](fn.translate.html).
+//!
+//! ```rust
+//! extern crate qasm;
+//! use qasm::qasm3::{self, Operand, Statement};
+//! use std::env;
+//!
+//! let source = r#"
+//! OPENQASM 3;
+//! include "stdgates.inc";
+//! qubit[2] q;
+//! bit[2] c;
+//! h q[0];
+//! ctrl @ x q[0], q[1];
+//! c = measure q;
+//! "#;
+//!
+//! let program = qasm3::parse_str(source, &env::current_dir().unwrap()).unwrap();
+//! let statements: Vec<_> = program
+//!     .into_iter()
+//!     .filter(|s| !matches!(*s, Statement::GateDef(..)))
+//!     .collect();
+//!
+//! assert_eq!(
+//!     statements[4],
+//!     Statement::Measure(
+//!         Operand::Register("q".to_string()),
+//!         Some(Operand::Register("c".to_string()))
+//!     )
+//! );
+//! ```
+
+mod ast;
+mod parser;
+mod translate;
+
+pub use self::ast::{BinaryOp, Expression, Iterable, Modifier, Operand, Statement, Type};
+pub use self::translate::translate;
+
+use error::Error;
+use lexer::Lexer;
+use regex::Regex;
+use std::path::Path;
+use std::result;
+use token::Token;
+
+type Result<T> = result::Result<T, Error>;
+
+/// The source of the standard library, `stdgates.inc`.
+pub const STDGATES: &str = include_str!("stdgates.inc");
+
+/// Removes comments from an OpenQASM 3 source, and resolves include statements.
+///
+/// Both `//` and `/* */` comments are removed. `stdgates.inc` is built in,
+/// other files are read relative to `cwd`. Returns an `IncludeError` when an
+/// included file couldn't be read.
+pub fn process(input: &str, cwd: &Path) -> Result<String> {
+    let comment_regex = Regex::new(r"(?s)//[^\n]*|/\*.*?\*/").unwrap();
+    let cleaned = comment_regex.replace_all(input, "");
+
+    let include_regex = Regex::new(r#"include\s*"(?P<s>.*)"\s*;"#).unwrap();
+
+    ::resolve_includes(&cleaned, &comment_regex, &include_regex, |file| {
+        if file == "stdgates.inc" {
+            Ok(STDGATES.to_string())
+        } else {
+            ::read_file(&cwd.join(file)).map_err(|_| Error::IncludeError(file.to_string()))
+        }
+    })
+}
+
+/// Takes an OpenQASM 3 source string with no comments, and returns the tokens.
+pub fn lex(input: &str) -> Vec<Token> {
+    Lexer::new_qasm3(input).map(Token::into_owned).collect()
+}
+
+/// Parses an OpenQASM 3 source string with no comments into a list of statements.
+///
+/// Include statements are returned as `Statement::Include`, use
+/// [parse_str](fn.parse_str.html) to resolve them.
+pub fn parse(source: &str) -> Result<Vec<Statement>> {
+    let mut tokens = Lexer::new_qasm3(source).peekable();
+    parser::parse(&mut tokens)
+}
+
+/// Processes and parses an OpenQASM 3 source string.
+///
+/// The `cwd` is the directory that include statements are resolved from.
+pub fn parse_str(source: &str, cwd: &Path) -> Result<Vec<Statement>> {
+    parse(&process(source, cwd)?)
+}
+
+/// Reads, processes and parses an OpenQASM 3 file.
+///
+/// Include statements are resolved from the directory that the file is in.
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Vec<Statement>> {
+    let path = path.as_ref();
+    let source = ::read_file(path).map_err(|e| Error::IoError(e.kind()))?;
+    let cwd = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_str(&source, cwd)
+}
+
+#[test]
+fn stdgates_test() {
+    let statements = parse_str(STDGATES, Path::new("")).unwrap();
+    assert_eq!(statements.len(), 32);
+    assert!(statements.iter().all(|s| matches!(*s, Statement::GateDef(..))));
+}
@@ -0,0 +1,516 @@
+//! This module implements the parsing methods for OpenQASM 3.
+//! Like the OpenQASM 2.0 parser, most methods should only be
+//! accessed indirectly from the `parse` method.
+
+use super::ast::{BinaryOp, Expression, Iterable, Modifier, Operand, Statement, Type};
+use error::Error;
+use expr::Function;
+use lexer::Lexer;
+use parser::{match_id_list, match_identifier, match_semicolon, match_token};
+use std::iter::Peekable;
+use std::result;
+use token::Token;
+
+type TokenStream<'a> = Peekable<Lexer<'a>>;
+type Result<T> = result::Result<T, Error>;
+
+pub fn parse(tokens: &mut TokenStream) -> Result<Vec<Statement>> {
+    version(tokens)?;
+
+    let mut statements = vec![];
+    while tokens.peek().is_some() {
+        statements.push(statement(tokens)?);
+    }
+
+    Ok(statements)
+}
+
+/// The version statement is optional, but if it's given it must be for version 3.
+pub fn version(tokens: &mut TokenStream) -> Result<()> {
+    if let Some(&Token::OpenQASM) = tokens.peek() {
+        tokens.next();
+        let version = match tokens.next() {
+            Some(Token::NNInteger(n)) => f64::from(n),
+            Some(Token::Real(n)) => n,
+            Some(_) => return Err(Error::MissingVersion),
+            None => return Err(Error::SourceError),
+        };
+        if version.trunc() != 3.0 {
+            return Err(Error::UnsupportedVersion);
+        }
+        match_semicolon(tokens)?;
+    }
+
+    Ok(())
+}
+
+fn statement(tokens: &mut TokenStream) -> Result<Statement> {
+    let statement = match *tokens.peek().ok_or(Error::SourceError)? {
+        Token::Include => {
+            tokens.next();
+            match tokens.next() {
+                Some(Token::Str(file)) => Statement::Include(file.to_string()),
+                _ => return Err(Error::SourceError),
+            }
+        }
+        Token::Qubit => {
+            tokens.next();
+            let size = designator(tokens)?;
+            Statement::QubitDecl(match_identifier(tokens)?, size)
+        }
+        Token::Bit => {
+            tokens.next();
+            let size = designator(tokens)?;
+            let id = match_identifier(tokens)?;
+            if let Some(&Token::Assign) = tokens.peek() {
+                tokens.next();
+                Statement::ClassicalDecl(Type::Bit(size), id, Some(expression(tokens)?))
+            } else {
+                Statement::BitDecl(id, size)
+            }
+        }
+        Token::QReg | Token::CReg => {
+            // Old style declarations, with the size after the name
+            let quantum = tokens.next() == Some(Token::QReg);
+            let id = match_identifier(tokens)?;
+            let size = designator(tokens)?;
+            if quantum {
+                Statement::QubitDecl(id, size)
+            } else {
+                Statement::BitDecl(id, size)
+            }
+        }
+        Token::Bool | Token::Int | Token::Uint | Token::Float | Token::Angle => {
+            let ty = type_(tokens)?;
+            let id = match_identifier(tokens)?;
+            let value = if let Some(&Token::Assign) = tokens.peek() {
+                tokens.next();
+                Some(expression(tokens)?)
+            } else {
+                None
+            };
+            Statement::ClassicalDecl(ty, id, value)
+        }
+        Token::Const => {
+            tokens.next();
+            let ty = type_(tokens)?;
+            let id = match_identifier(tokens)?;
+            match_token(tokens, Token::Assign)?;
+            Statement::Const(ty, id, expression(tokens)?)
+        }
+        Token::Input => {
+            tokens.next();
+            let ty = type_(tokens)?;
+            Statement::Input(ty, match_identifier(tokens)?)
+        }
+        Token::Measure => {
+            tokens.next();
+            let qubits = operand(tokens)?;
+            let bits = if let Some(&Token::Arrow) = tokens.peek() {
+                tokens.next();
+                Some(operand(tokens)?)
+            } else {
+                None
+            };
+            Statement::Measure(qubits, bits)
+        }
+        Token::Reset => {
+            tokens.next();
+            Statement::Reset(operand(tokens)?)
+        }
+        Token::Barrier => {
+            tokens.next();
+            let operands = if let Some(&Token::Semicolon) = tokens.peek() {
+                vec![]
+            } else {
+                operand_list(tokens)?
+            };
+            Statement::Barrier(operands)
+        }
+        Token::Id(_) => {
+            let id = match_identifier(tokens)?;
+            match tokens.peek() {
+                Some(&Token::Assign) | Some(&Token::LSParen) => assignment(tokens, id)?,
+                _ => gate_call(tokens, vec![], id)?,
+            }
+        }
+        Token::Ctrl | Token::NegCtrl | Token::Inv | Token::Pow => {
+            let modifiers = modifiers(tokens)?;
+            let id = match_identifier(tokens)?;
+            gate_call(tokens, modifiers, id)?
+        }
+        // Statements with blocks don't end with a semicolon
+        Token::Gate => return gate_def(tokens),
+        Token::For => return for_loop(tokens),
+        Token::While => {
+            tokens.next();
+            let condition = condition(tokens)?;
+            return Ok(Statement::While(condition, block(tokens)?));
+        }
+        Token::If => {
+            tokens.next();
+            let condition = condition(tokens)?;
+            let body = block(tokens)?;
+            let else_body = if let Some(&Token::Else) = tokens.peek() {
+                tokens.next();
+                block(tokens)?
+            } else {
+                vec![]
+            };
+            return Ok(Statement::If(condition, body, else_body));
+        }
+        _ => return Err(Error::SourceError),
+    };
+
+    match_semicolon(tokens)?;
+    Ok(statement)
+}
+
+/// A block in braces, or a single statement.
+fn block(tokens: &mut TokenStream) -> Result<Vec<Statement>> {
+    if let Some(&Token::LCParen) = tokens.peek() {
+        tokens.next();
+        let mut statements = vec![];
+        while tokens.peek() != Some(&Token::RCParen) {
+            statements.push(statement(tokens)?);
+        }
+        tokens.next();
+        Ok(statements)
+    } else {
+        Ok(vec![statement(tokens)?])
+    }
+}
+
+fn condition(tokens: &mut TokenStream) -> Result<Expression> {
+    match_token(tokens, Token::LParen)?;
+    let condition = expression(tokens)?;
+    match_token(tokens, Token::RParen)?;
+    Ok(condition)
+}
+
+fn gate_def(tokens: &mut TokenStream) -> Result<Statement> {
+    // gate -> id -> ( id list ) -> id list -> { -> statements -> }
+    tokens.next();
+    let id = match_identifier(tokens)?;
+
+    let params = if let Some(&Token::LParen) = tokens.peek() {
+        tokens.next();
+        if let Some(&Token::RParen) = tokens.peek() {
+            tokens.next();
+            vec![]
+        } else {
+            let p = match_id_list(tokens)?;
+            match_token(tokens, Token::RParen)?;
+            p
+        }
+    } else {
+        vec![]
+    };
+
+    let qubits = match_id_list(tokens)?;
+    if tokens.peek() != Some(&Token::LCParen) {
+        return Err(Error::SourceError);
+    }
+
+    Ok(Statement::GateDef(id, params, qubits, block(tokens)?))
+}
+
+fn for_loop(tokens: &mut TokenStream) -> Result<Statement> {
+    // for -> type -> id -> in -> iterable -> block
+    tokens.next();
+    let ty = match tokens.peek() {
+        Some(&Token::Id(_)) => Type::Int(None),
+        _ => type_(tokens)?,
+    };
+    let id = match_identifier(tokens)?;
+    match_token(tokens, Token::In)?;
+
+    let iterable = match tokens.next() {
+        Some(Token::LSParen) => {
+            let start = expression(tokens)?;
+            match_token(tokens, Token::Colon)?;
+            let second = expression(tokens)?;
+            let range = if let Some(&Token::Colon) = tokens.peek() {
+                tokens.next();
+                Iterable::Range(start, Some(second), expression(tokens)?)
+            } else {
+                Iterable::Range(start, None, second)
+            };
+            match_token(tokens, Token::RSParen)?;
+            range
+        }
+        Some(Token::LCParen) => {
+            let set = expression_list(tokens)?;
+            match_token(tokens, Token::RCParen)?;
+            Iterable::Set(set)
+        }
+        _ => return Err(Error::SourceError),
+    };
+
+    Ok(Statement::For(ty, id, iterable, block(tokens)?))
+}
+
+fn assignment(tokens: &mut TokenStream, id: String) -> Result<Statement> {
+    // operand -> = -> measure -> operand
+    // operand -> = -> expression
+    let target = operand_after(tokens, id)?;
+    match_token(tokens, Token::Assign)?;
+
+    if let Some(&Token::Measure) = tokens.peek() {
+        tokens.next();
+        Ok(Statement::Measure(operand(tokens)?, Some(target)))
+    } else {
+        Ok(Statement::Assign(target, expression(tokens)?))
+    }
+}
+
+fn modifiers(tokens: &mut TokenStream) -> Result<Vec<Modifier>> {
+    let mut modifiers = vec![];
+
+    loop {
+        let modifier = match tokens.peek() {
+            Some(&Token::Ctrl) | Some(&Token::NegCtrl) => {
+                let negated = tokens.next() == Some(Token::NegCtrl);
+                let count = if let Some(&Token::LParen) = tokens.peek() {
+                    tokens.next();
+                    let count = expression(tokens)?;
+                    match_token(tokens, Token::RParen)?;
+                    count
+                } else {
+                    Expression::Int(1)
+                };
+                if negated {
+                    Modifier::NegCtrl(count)
+                } else {
+                    Modifier::Ctrl(count)
+                }
+            }
+            Some(&Token::Inv) => {
+                tokens.next();
+                Modifier::Inv
+            }
+            Some(&Token::Pow) => {
+                tokens.next();
+                match_token(tokens, Token::LParen)?;
+                let power = expression(tokens)?;
+                match_token(tokens, Token::RParen)?;
+                Modifier::Pow(power)
+            }
+            _ => return Ok(modifiers),
+        };
+        match_token(tokens, Token::At)?;
+        modifiers.push(modifier);
+    }
+}
+
+fn gate_call(tokens: &mut TokenStream, modifiers: Vec<Modifier>, id: String) -> Result<Statement> {
+    // modifiers -> id -> ( expression list ) -> operand list
+    let params = if let Some(&Token::LParen) = tokens.peek() {
+        tokens.next();
+        if let Some(&Token::RParen) = tokens.peek() {
+            tokens.next();
+            vec![]
+        } else {
+            let p = expression_list(tokens)?;
+            match_token(tokens, Token::RParen)?;
+            p
+        }
+    } else {
+        vec![]
+    };
+
+    // Global phase gates have no operands
+    let operands = if let Some(&Token::Semicolon) = tokens.peek() {
+        vec![]
+    } else {
+        operand_list(tokens)?
+    };
+
+    Ok(Statement::Gate(modifiers, id, params, operands))
+}
+
+fn type_(tokens: &mut TokenStream) -> Result<Type> {
+    let ty = match tokens.next() {
+        Some(Token::Bool) => return Ok(Type::Bool),
+        Some(Token::Bit) => Type::Bit,
+        Some(Token::Int) => Type::Int,
+        Some(Token::Uint) => Type::Uint,
+        Some(Token::Float) => Type::Float,
+        Some(Token::Angle) => Type::Angle,
+        Some(_) => return Err(Error::SourceError),
+        None => return Err(Error::SourceError),
+    };
+    Ok(ty(designator(tokens)?))
+}
+
+/// An optional size in square brackets.
+fn designator(tokens: &mut TokenStream) -> Result<Option<Expression>> {
+    if let Some(&Token::LSParen) = tokens.peek() {
+        tokens.next();
+        let size = expression(tokens)?;
+        match_token(tokens, Token::RSParen)?;
+        Ok(Some(size))
+    } else {
+        Ok(None)
+    }
+}
+
+fn operand(tokens: &mut TokenStream) -> Result<Operand> {
+    let id = match_identifier(tokens)?;
+    operand_after(tokens, id)
+}
+
+fn operand_after(tokens: &mut TokenStream, id: String) -> Result<Operand> {
+    match designator(tokens)? {
+        Some(index) => Ok(Operand::Index(id, index)),
+        None => Ok(Operand::Register(id)),
+    }
+}
+
+fn operand_list(tokens: &mut TokenStream) -> Result<Vec<Operand>> {
+    let mut operands = vec![operand(tokens)?];
+    while let Some(&Token::Comma) = tokens.peek() {
+        tokens.next();
+        operands.push(operand(tokens)?);
+    }
+    Ok(operands)
+}
+
+fn expression_list(tokens: &mut TokenStream) -> Result<Vec<Expression>> {
+    let mut expressions = vec![expression(tokens)?];
+    while let Some(&Token::Comma) = tokens.peek() {
+        tokens.next();
+        expressions.push(expression(tokens)?);
+    }
+    Ok(expressions)
+}
+
+//////////////////////////////////////////////////////////////////////
+// Expressions, from the loosest binding to the tightest
+//////////////////////////////////////////////////////////////////////
+pub fn expression(tokens: &mut TokenStream) -> Result<Expression> {
+    binary(tokens, 0)
+}
+
+/// The binary operators at each level of precedence.
+fn binary_op(token: &Token<&str>, level: usize) -> Option<BinaryOp> {
+    match (level, token) {
+        (0, &Token::Or) => Some(BinaryOp::Or),
+        (1, &Token::And) => Some(BinaryOp::And),
+        (2, &Token::Equals) => Some(BinaryOp::Equals),
+        (2, &Token::NotEquals) => Some(BinaryOp::NotEquals),
+        (3, &Token::Less) => Some(BinaryOp::Less),
+        (3, &Token::LessEquals) => Some(BinaryOp::LessEquals),
+        (3, &Token::Greater) => Some(BinaryOp::Greater),
+        (3, &Token::GreaterEquals) => Some(BinaryOp::GreaterEquals),
+        (4, &Token::Plus) => Some(BinaryOp::Add),
+        (4, &Token::Minus) => Some(BinaryOp::Sub),
+        (5, &Token::Times) => Some(BinaryOp::Mul),
+        (5, &Token::Divide) => Some(BinaryOp::Div),
+        _ => None,
+    }
+}
+
+const UNARY_LEVEL: usize = 6;
+
+fn binary(tokens: &mut TokenStream, level: usize) -> Result<Expression> {
+    if level == UNARY_LEVEL {
+        return unary(tokens);
+    }
+
+    let mut lhs = binary(tokens, level + 1)?;
+    while let Some(op) = tokens.peek().and_then(|t| binary_op(t, level)) {
+        tokens.next();
+        let rhs = binary(tokens, level + 1)?;
+        lhs = Expression::Binary(op, Box::new(lhs), Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn unary(tokens: &mut TokenStream) -> Result<Expression> {
+    match tokens.peek() {
+        Some(&Token::Minus) => {
+            tokens.next();
+            Ok(Expression::Neg(Box::new(unary(tokens)?)))
+        }
+        Some(&Token::Not) => {
+            tokens.next();
+            Ok(Expression::Not(Box::new(unary(tokens)?)))
+        }
+        _ => power(tokens),
+    }
+}
+
+fn power(tokens: &mut TokenStream) -> Result<Expression> {
+    // `**` is right associative, and binds tighter than unary minus
+    let base = atom(tokens)?;
+    if let Some(&Token::Power) = tokens.peek() {
+        tokens.next();
+        let exponent = unary(tokens)?;
+        return Ok(Expression::Binary(BinaryOp::Pow, Box::new(base), Box::new(exponent)));
+    }
+    Ok(base)
+}
+
+fn atom(tokens: &mut TokenStream) -> Result<Expression> {
+    let function = match tokens.next().ok_or(Error::SourceError)? {
+        Token::NNInteger(n) => return Ok(Expression::Int(n)),
+        Token::Real(n) => return Ok(Expression::Real(n)),
+        Token::Pi => return Ok(Expression::Pi),
+        Token::True => return Ok(Expression::Bool(true)),
+        Token::False => return Ok(Expression::Bool(false)),
+        Token::Id(id) => {
+            return match designator(tokens)? {
+                Some(index) => Ok(Expression::Index(id.to_string(), Box::new(index))),
+                None => Ok(Expression::Id(id.to_string())),
+            };
+        }
+        Token::LParen => {
+            let e = expression(tokens)?;
+            match_token(tokens, Token::RParen)?;
+            return Ok(e);
+        }
+        Token::Sin => Function::Sin,
+        Token::Cos => Function::Cos,
+        Token::Tan => Function::Tan,
+        Token::Exp => Function::Exp,
+        Token::Ln => Function::Ln,
+        Token::Sqrt => Function::Sqrt,
+        _ => return Err(Error::SourceError),
+    };
+
+    match_token(tokens, Token::LParen)?;
+    let e = expression(tokens)?;
+    match_token(tokens, Token::RParen)?;
+    Ok(Expression::Call(function, Box::new(e)))
+}
+
+#[test]
+fn expression_test() {
+    let mut tokens = Lexer::new_qasm3("c[0] == 1 && -2**2 < x + 1").peekable();
+    let e = |e| Box::new(e);
+    assert_eq!(
+        expression(&mut tokens),
+        Ok(Expression::Binary(
+            BinaryOp::And,
+            e(Expression::Binary(
+                BinaryOp::Equals,
+                e(Expression::Index("c".to_string(), e(Expression::Int(0)))),
+                e(Expression::Int(1)),
+            )),
+            e(Expression::Binary(
+                BinaryOp::Less,
+                e(Expression::Neg(e(Expression::Binary(
+                    BinaryOp::Pow,
+                    e(Expression::Int(2)),
+                    e(Expression::Int(2)),
+                )))),
+                e(Expression::Binary(
+                    BinaryOp::Add,
+                    e(Expression::Id("x".to_string())),
+                    e(Expression::Int(1)),
+                )),
+            )),
+        ))
+    );
+}
@@ -0,0 +1,281 @@
+use ast::{Argument, AstNode};
+use error::Error;
+use expr::{Expr, Syntax};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+use std::result;
+
+use super::{parse, process, Statement, STDGATES};
+
+type Result<T> = result::Result<T, Error>;
+
+/// Words that are reserved in OpenQASM 3, but can be identifiers in OpenQASM 2.0.
+const KEYWORDS: [&str; 37] = [
+    "qubit", "bit", "bool", "int", "uint", "float", "angle", "complex", "duration", "stretch",
+    "const", "input", "output", "let", "def", "defcal", "cal", "extern", "return", "box",
+    "delay", "ctrl", "negctrl", "inv", "pow", "gphase", "for", "in", "while", "else",
+    "break", "continue", "switch", "true", "false", "log", "array",
+];
+
+/// The names of the gates defined in `stdgates.inc`.
+fn stdgate_names() -> HashSet<String> {
+    process(STDGATES, Path::new(""))
+        .and_then(|source| parse(&source))
+        .expect("stdgates.inc is valid")
+        .into_iter()
+        .filter_map(|s| match s {
+            Statement::GateDef(name, ..) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Picks new names for identifiers that can't be used in OpenQASM 3.
+struct Names {
+    reserved: HashSet<String>,
+    renamed: HashMap<String, String>,
+}
+
+impl Names {
+    /// Gives `name` a new name if it's reserved, adding underscores until it's unique.
+    fn declare(&mut self, name: &str) {
+        if !self.reserved.contains(name) {
+            self.reserved.insert(name.to_string());
+            return;
+        }
+        let mut new_name = format!("{}_", name);
+        while self.reserved.contains(&new_name) {
+            new_name.push('_');
+        }
+        self.reserved.insert(new_name.clone());
+        self.renamed.insert(name.to_string(), new_name);
+    }
+
+    fn get<'a>(&'a self, name: &'a str) -> &'a str {
+        self.renamed.get(name).map_or(name, String::as_str)
+    }
+}
+
+/// Translates an OpenQASM 2.0 program, as returned by [parse](../fn.parse.html),
+/// into OpenQASM 3 source.
+///
+/// * `qreg` and `creg` declarations become `qubit[n]` and `bit[n]` declarations.
+/// * `measure a -> b;` becomes `b = measure a;`.
+/// * `if(c==n)` becomes a comparison of the bit array `c` with the integer `n`.
+/// * Gates defined as in `qelib1.inc` are taken from `stdgates.inc` when it
+///   has a gate of the same name, other gate definitions are translated.
+///
+/// Identifiers that are reserved in OpenQASM 3, or that clash with a gate from
+/// `stdgates.inc`, are renamed by adding underscores. Opaque gates have no
+/// equivalent in OpenQASM 3, and return an `Unsupported` error.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{parse_tokens, qasm3, Lexer};
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// qreg q[2];
+/// creg c[2];
+/// U(pi/2, 0, pi) q[0];
+/// CX q[0], q[1];
+/// measure q -> c;
+/// "#;
+///
+/// let ast = parse_tokens(Lexer::new(source)).unwrap();
+/// assert_eq!(
+///     qasm3::translate(&ast).unwrap(),
+///     r#"OPENQASM 3.0;
+/// include "stdgates.inc";
+/// qubit[2] q;
+/// bit[2] c;
+/// U(pi/2, 0, pi) q[0];
+/// cx q[0], q[1];
+/// c = measure q;
+/// "#
+/// );
+/// ```
+pub fn translate(nodes: &[AstNode]) -> Result<String> {
+    let stdgates = stdgate_names();
+    let qelib1 = ::qelib1_gates();
+
+    let mut reserved: HashSet<String> = KEYWORDS.iter().map(|k| k.to_string()).collect();
+    reserved.extend(stdgates.iter().cloned());
+    let mut names = Names {
+        reserved,
+        renamed: HashMap::new(),
+    };
+
+    // Definitions that are the same as in qelib1.inc are replaced by stdgates.inc
+    let is_standard = |node: &AstNode| match *node {
+        AstNode::Gate(ref name, ..) => stdgates.contains(name) && qelib1.contains(node),
+        _ => false,
+    };
+
+    let mut out = String::new();
+    writeln!(out, "OPENQASM 3.0;").unwrap();
+    writeln!(out, "include \"stdgates.inc\";").unwrap();
+
+    for node in nodes {
+        match *node {
+            AstNode::QReg(ref name, _) | AstNode::CReg(ref name, _) => names.declare(name),
+            AstNode::Gate(ref name, ..) if !is_standard(node) => names.declare(name),
+            AstNode::Opaque(ref name, ..) => {
+                return Err(Error::Unsupported(format!("opaque {}", name)))
+            }
+            _ => {}
+        }
+
+        if !is_standard(node) {
+            statement(&mut out, node, &names)?;
+        }
+    }
+
+    Ok(out)
+}
+
+fn statement(out: &mut String, node: &AstNode, names: &Names) -> Result<()> {
+    let line = match *node {
+        AstNode::QReg(ref name, size) => format!("qubit[{}] {};", size, names.get(name)),
+        AstNode::CReg(ref name, size) => format!("bit[{}] {};", size, names.get(name)),
+        AstNode::Gate(ref name, ref qubits, ref params, ref body) => {
+            return gate_def(out, names.get(name), qubits, params, body, names);
+        }
+        AstNode::Measure(ref qubit, ref bit) => {
+            format!("{} = measure {};", argument(bit, names), argument(qubit, names))
+        }
+        AstNode::Reset(ref qubit) => format!("reset {};", argument(qubit, names)),
+        AstNode::If(ref creg, value, ref node) => {
+            write!(out, "if ({} == {}) ", names.get(creg), value).unwrap();
+            return statement(out, node, names);
+        }
+        _ => {
+            let params = HashMap::new();
+            format!("{};", operation(node, names, &params, &|a| argument(a, names))?)
+        }
+    };
+    writeln!(out, "{}", line).unwrap();
+    Ok(())
+}
+
+fn gate_def(
+    out: &mut String,
+    name: &str,
+    qubits: &[String],
+    params: &[String],
+    body: &[AstNode],
+    names: &Names,
+) -> Result<()> {
+    // Only reserved words need renaming in a gate, its arguments are local
+    let mut locals = HashMap::new();
+    for id in qubits.iter().chain(params) {
+        if KEYWORDS.contains(&id.as_str()) {
+            locals.insert(id.clone(), format!("{}_", id));
+        }
+    }
+    let local = |id: &str| locals.get(id).cloned().unwrap_or_else(|| id.to_string());
+
+    let params: Vec<_> = params.iter().map(|p| local(p)).collect();
+    let qubits: Vec<_> = qubits.iter().map(|q| local(q)).collect();
+    if params.is_empty() {
+        write!(out, "gate {} {} {{", name, qubits.join(", ")).unwrap();
+    } else {
+        write!(out, "gate {}({}) {} {{", name, params.join(", "), qubits.join(", ")).unwrap();
+    }
+
+    let params: HashMap<_, _> = locals
+        .iter()
+        .map(|(id, new_id)| (id.clone(), Expr::Id(new_id.clone())))
+        .collect();
+    let body = body
+        .iter()
+        .map(|node| operation(node, names, &params, &|a| match *a {
+            Argument::Register(ref id) => local(id),
+            Argument::Qubit(ref id, i) => format!("{}[{}]", local(id), i),
+        }))
+        .collect::<Result<Vec<_>>>()?;
+
+    if body.is_empty() {
+        writeln!(out, "}}").unwrap();
+    } else {
+        writeln!(out).unwrap();
+        for op in body {
+            writeln!(out, "  {};", op).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+    Ok(())
+}
+
+/// A gate application or barrier, without its semicolon.
+fn operation(
+    node: &AstNode,
+    names: &Names,
+    params: &HashMap<String, Expr>,
+    argument: &dyn Fn(&Argument) -> String,
+) -> Result<String> {
+    let arguments = |args: &[Argument]| args.iter().map(argument).collect::<Vec<_>>().join(", ");
+    let apply = |name: &str, exprs: &[String], args: &[Argument]| -> Result<String> {
+        if exprs.is_empty() {
+            return Ok(format!("{} {}", name, arguments(args)));
+        }
+        let exprs = exprs
+            .iter()
+            .map(|e| Ok(Expr::parse(e)?.substitute(params).to_string_with(&Syntax::QASM3)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!("{}({}) {}", name, exprs.join(", "), arguments(args)))
+    };
+
+    match *node {
+        AstNode::U(ref qubit, ref exprs) => apply("U", exprs, ::std::slice::from_ref(qubit)),
+        AstNode::CX(ref control, ref target) => {
+            apply("cx", &[], &[control.clone(), target.clone()])
+        }
+        AstNode::ApplyGate(ref name, ref args, ref exprs) => apply(names.get(name), exprs, args),
+        AstNode::Barrier(ref args) => Ok(format!("barrier {}", arguments(args))),
+        _ => Err(Error::SourceError),
+    }
+}
+
+fn argument(arg: &Argument, names: &Names) -> String {
+    match *arg {
+        Argument::Register(ref id) => names.get(id).to_string(),
+        Argument::Qubit(ref id, i) => format!("{}[{}]", names.get(id), i),
+    }
+}
+
+#[test]
+fn translate_test() {
+    let source = "OPENQASM 2.0;
+        gate h a { U(pi/2,0,pi) a; }
+        gate in(lambda) a, b { CX a,b; U(0,0,lambda^2) b; }
+        qreg q[2];
+        creg bit[2];
+        h q[0];
+        in(pi) q[0], q[1];
+        measure q[1] -> bit[1];
+        if(bit==2) reset q;";
+    let ast = ::parse_tokens(::Lexer::new(source)).unwrap();
+
+    assert_eq!(
+        translate(&ast).unwrap(),
+        "OPENQASM 3.0;
+include \"stdgates.inc\";
+gate h_ a {
+  U(pi/2, 0, pi) a;
+}
+gate in_(lambda) a, b {
+  cx a, b;
+  U(0, 0, lambda**2) b;
+}
+qubit[2] q;
+bit[2] bit_;
+h_ q[0];
+in_(pi) q[0], q[1];
+bit_[1] = measure q[1];
+if (bit_ == 2) reset q;
+"
+    );
+}
@@ -0,0 +1,323 @@
+//! # QIR
+//!
+//! Converts programs to LLVM IR in the text format, following the base profile
+//! of the [Quantum Intermediate Representation](https://github.com/qir-alliance/qir-spec).
+//!
+//! Programs are flattened with [Program::flatten](../struct.Program.html#method.flatten)
+//! first, then written with [emit](fn.emit.html).
+
+use circuit::{Circuit, Operation};
+use error::Error;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// The gates of `qelib1.inc` with a QIR function, and the function's name.
+/// `u1` is a `rz` rotation, up to a global phase.
+fn standard_gate(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "CX" | "cx" => "cnot",
+        "x" => "x",
+        "y" => "y",
+        "z" => "z",
+        "h" => "h",
+        "s" => "s",
+        "sdg" => "s__adj",
+        "t" => "t",
+        "tdg" => "t__adj",
+        "rx" => "rx",
+        "ry" => "ry",
+        "rz" | "u1" => "rz",
+        "cz" => "cz",
+        "ccx" => "ccx",
+        _ => return None,
+    })
+}
+
+struct Emitter {
+    body: String,
+    /// The functions that are called, with the types of their arguments, in
+    /// the order they are first used
+    declarations: Vec<(String, &'static str)>,
+}
+
+impl Emitter {
+    fn call(&mut self, function: &str, types: &'static str, args: &[String]) {
+        writeln!(self.body, "  call void @{}({})", function, args.join(", ")).unwrap();
+        if !self.declarations.iter().any(|(f, _)| f == function) {
+            self.declarations.push((function.to_string(), types));
+        }
+    }
+
+    fn gate(&mut self, function: &str, params: &[f64], qubits: &[usize]) {
+        let function = format!("__quantum__qis__{}__body", function);
+        let args: Vec<_> = params
+            .iter()
+            .map(|&p| format!("double {}", double(p)))
+            .chain(qubits.iter().map(|&q| qubit(q)))
+            .collect();
+        let types = match (params.len(), qubits.len()) {
+            (0, 1) => "%Qubit*",
+            (0, 2) => "%Qubit*, %Qubit*",
+            (0, 3) => "%Qubit*, %Qubit*, %Qubit*",
+            _ => "double, %Qubit*",
+        };
+        self.call(&function, types, &args);
+    }
+}
+
+/// Converts a flattened program into LLVM IR following the QIR base profile.
+///
+/// * Qubits are numbered by their global index, and each bit of the
+///   classical registers is a result, also numbered by its global index.
+/// * Gates defined as in `qelib1.inc` that QIR has call their function, such
+///   as `__quantum__qis__h__body`. `U` is written as `rz`, `ry` and `rz`
+///   rotations, `id` is left out, and other gates are replaced by the gates
+///   in their definition.
+/// * Measurements call `__quantum__qis__mz__body`, and at the end the results
+///   are recorded, with an array for each classical register, in a tuple.
+///
+/// The base profile has no control flow, and qubits can't be used after they
+/// are measured, so these return an `Unsupported` error, as do opaque gates
+/// and measuring into a bit twice. Barriers are left out.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// creg c[2];
+/// h q[0];
+/// cx q[0], q[1];
+/// measure q -> c;
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let ir = qasm::qir::emit(&circuit).unwrap();
+///
+/// assert!(ir.contains(
+///     "  call void @__quantum__qis__h__body(%Qubit* null)
+///   call void @__quantum__qis__cnot__body(%Qubit* null, %Qubit* inttoptr (i64 1 to %Qubit*))
+/// "
+/// ));
+/// assert!(ir.contains("\"required_num_results\"=\"2\""));
+/// ```
+pub fn emit(circuit: &Circuit) -> Result<String> {
+    let qelib1 = ::qelib1();
+    let standard: HashSet<&str> = circuit
+        .gates
+        .iter()
+        .filter(|g| {
+            (g.name == "id" || standard_gate(&g.name).is_some()) && qelib1.gate(&g.name) == Some(*g)
+        })
+        .map(|g| g.name.as_str())
+        .collect();
+
+    let mut emitter = Emitter {
+        body: String::new(),
+        declarations: Vec::new(),
+    };
+    emitter.call(
+        "__quantum__rt__initialize",
+        "i8*",
+        &["i8* null".to_string()],
+    );
+
+    let mut measured = vec![false; circuit.num_qubits()];
+    let mut written = vec![false; circuit.num_clbits()];
+    for instruction in &circuit.instructions {
+        if instruction.condition.is_some() {
+            return Err(Error::Unsupported("if".to_string()));
+        }
+        let unmeasured = |qubits: &[usize]| {
+            if qubits.iter().any(|&q| measured[q]) {
+                Err(Error::Unsupported(
+                    "operation after measurement".to_string(),
+                ))
+            } else {
+                Ok(())
+            }
+        };
+
+        match instruction.operation {
+            Operation::Gate(ref name, ref params, ref qubits) => {
+                unmeasured(qubits)?;
+                circuit.expand(
+                    name,
+                    params,
+                    qubits,
+                    &|name| standard.contains(name),
+                    &mut |name, params, qubits| {
+                        match (name, standard_gate(name)) {
+                            ("U", _) => {
+                                let (theta, phi, lambda) = (params[0], params[1], params[2]);
+                                for &(function, angle) in
+                                    &[("rz", lambda), ("ry", theta), ("rz", phi)]
+                                {
+                                    if angle != 0.0 {
+                                        emitter.gate(function, &[angle], qubits);
+                                    }
+                                }
+                            }
+                            ("id", _) => {}
+                            (_, Some(function)) => emitter.gate(function, params, qubits),
+                            (_, None) => {
+                                return Err(Error::Unsupported(format!("opaque {}", name)))
+                            }
+                        }
+                        Ok(())
+                    },
+                )?;
+            }
+            Operation::Measure(q, b) => {
+                unmeasured(&[q])?;
+                if written[b] {
+                    return Err(Error::Unsupported("measuring into a bit twice".to_string()));
+                }
+                measured[q] = true;
+                written[b] = true;
+                emitter.call(
+                    "__quantum__qis__mz__body",
+                    "%Qubit*, %Result* writeonly",
+                    &[qubit(q), result(b)],
+                );
+            }
+            Operation::Reset(q) => {
+                unmeasured(&[q])?;
+                emitter.gate("reset", &[], &[q]);
+            }
+            Operation::Barrier(_) => {}
+        }
+    }
+
+    if !circuit.cregs.is_empty() {
+        let count = |n: usize| format!("i64 {}", n);
+        let label = "i8* null".to_string();
+        emitter.call(
+            "__quantum__rt__tuple_record_output",
+            "i64, i8*",
+            &[count(circuit.cregs.len()), label.clone()],
+        );
+        for register in &circuit.cregs {
+            emitter.call(
+                "__quantum__rt__array_record_output",
+                "i64, i8*",
+                &[count(register.size), label.clone()],
+            );
+            for b in register.offset..register.offset + register.size {
+                emitter.call(
+                    "__quantum__rt__result_record_output",
+                    "%Result*, i8*",
+                    &[result(b), label.clone()],
+                );
+            }
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str("; ModuleID = 'qasm'\nsource_filename = \"qasm\"\n\n");
+    out.push_str("%Qubit = type opaque\n%Result = type opaque\n\n");
+    out.push_str("define void @main() #0 {\nentry:\n");
+    out.push_str(&emitter.body);
+    out.push_str("  ret void\n}\n\n");
+    for &(ref function, types) in &emitter.declarations {
+        let irreversible = if function == "__quantum__qis__mz__body" {
+            " #1"
+        } else {
+            ""
+        };
+        writeln!(out, "declare void @{}({}){}", function, types, irreversible).unwrap();
+    }
+    writeln!(
+        out,
+        "\nattributes #0 = {{ \"entry_point\" \"output_labeling_schema\" \
+         \"qir_profiles\"=\"base_profile\" \"required_num_qubits\"=\"{}\" \
+         \"required_num_results\"=\"{}\" }}",
+        circuit.num_qubits(),
+        circuit.num_clbits()
+    )
+    .unwrap();
+    out.push_str("attributes #1 = { \"irreversible\" }\n\n");
+    out.push_str("!llvm.module.flags = !{!0, !1, !2, !3}\n\n");
+    out.push_str("!0 = !{i32 1, !\"qir_major_version\", i32 1}\n");
+    out.push_str("!1 = !{i32 7, !\"qir_minor_version\", i32 0}\n");
+    out.push_str("!2 = !{i32 1, !\"dynamic_qubit_management\", i1 false}\n");
+    out.push_str("!3 = !{i32 1, !\"dynamic_result_management\", i1 false}\n");
+    Ok(out)
+}
+
+/// A pointer to a qubit, given by its index.
+fn qubit(index: usize) -> String {
+    pointer("%Qubit*", index)
+}
+
+/// A pointer to a result, given by its index.
+fn result(index: usize) -> String {
+    pointer("%Result*", index)
+}
+
+fn pointer(ty: &str, index: usize) -> String {
+    if index == 0 {
+        format!("{} null", ty)
+    } else {
+        format!("{} inttoptr (i64 {} to {})", ty, index, ty)
+    }
+}
+
+/// A double in LLVM's syntax, which needs a decimal point.
+fn double(value: f64) -> String {
+    let s = format!("{:e}", value);
+    if s.contains('.') {
+        s
+    } else {
+        s.replacen('e', ".0e", 1)
+    }
+}
+
+#[test]
+fn emit_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        qreg q[2];
+        creg c[1];
+        gate g(t) a, b { U(t, 0, pi) a; CX a, b; }
+        reset q[1];
+        g(pi/2) q[1], q[0];
+        measure q[0] -> c[0];";
+    let circuit = flatten(source);
+    let ir = emit(&circuit).unwrap();
+
+    assert!(ir.contains(
+        "  call void @__quantum__qis__reset__body(%Qubit* inttoptr (i64 1 to %Qubit*))
+  call void @__quantum__qis__rz__body(double 3.141592653589793e0, %Qubit* inttoptr (i64 1 to %Qubit*))
+  call void @__quantum__qis__ry__body(double 1.5707963267948966e0, %Qubit* inttoptr (i64 1 to %Qubit*))
+  call void @__quantum__qis__cnot__body(%Qubit* inttoptr (i64 1 to %Qubit*), %Qubit* null)
+  call void @__quantum__qis__mz__body(%Qubit* null, %Result* null)
+  call void @__quantum__rt__tuple_record_output(i64 1, i8* null)
+  call void @__quantum__rt__array_record_output(i64 1, i8* null)
+  call void @__quantum__rt__result_record_output(%Result* null, i8* null)
+  ret void
+"
+    ));
+    assert!(ir.contains("declare void @__quantum__qis__rz__body(double, %Qubit*)\n"));
+    assert!(ir.contains("declare void @__quantum__qis__mz__body(%Qubit*, %Result* writeonly) #1\n"));
+    assert_eq!(double(1.0), "1.0e0");
+    assert_eq!(double(-2.5e-7), "-2.5e-7");
+
+    let reused = flatten("OPENQASM 2.0; qreg q[1]; creg c[1]; measure q -> c; U(0, 0, 0) q;");
+    assert_eq!(
+        emit(&reused),
+        Err(Error::Unsupported(
+            "operation after measurement".to_string()
+        ))
+    );
+}
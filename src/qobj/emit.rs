@@ -0,0 +1,238 @@
+use super::{hex, Conditional, Config, Experiment, Header, Instruction, Register};
+use circuit::{Circuit, Condition, Operation};
+use error::Error;
+use expr::Expr;
+use program;
+use std::collections::HashSet;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+struct Emitter<'a> {
+    circuit: &'a Circuit,
+    /// The gates that are passed on by name, as their definitions match `qelib1.inc`
+    standard: HashSet<&'a str>,
+    /// Whether measurements also need to be stored in the register slots
+    conditional: bool,
+    instructions: Vec<Instruction>,
+    /// The number of register slots used by `bfunc`s
+    slots: usize,
+    /// The condition that the last `bfunc` checked, while the memory is unchanged
+    checked: Option<(&'a Condition, usize)>,
+}
+
+/// Converts a flattened program into Qobj experiment JSON.
+///
+/// * Qubits and memory slots are numbered by their global index, and the
+///   header has the names and sizes of the registers.
+/// * `U` is written as `u3`, and `CX` as `cx`. Gates defined as in
+///   `qelib1.inc`, and opaque gates, are passed on by name. Other gates are
+///   replaced by the gates in their definition, as Qobj can't define gates.
+/// * Parameters are evaluated to numbers.
+/// * `if` statements add a `bfunc` instruction, which compares the register
+///   to the value and stores the result in a register slot after the memory.
+///   The instructions are `conditional` on that slot.
+///
+/// This returns an `InvalidExpression` error if a parameter isn't a finite number.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[1];
+/// creg c[1];
+/// rz(pi/2) q[0];
+/// measure q[0] -> c[0];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+/// let json = qasm::qobj::emit(&circuit).unwrap();
+///
+/// assert!(json.contains(r#""name": "rz","#));
+/// assert!(json.contains(r#""memory": ["#));
+/// ```
+pub fn emit(circuit: &Circuit) -> Result<String> {
+    let qelib1 = ::qelib1();
+    let standard = circuit
+        .gates
+        .iter()
+        .filter(|g| qelib1.gate(&g.name) == Some(*g))
+        .map(|g| g.name.as_str())
+        .collect();
+
+    let mut emitter = Emitter {
+        circuit,
+        standard,
+        conditional: circuit.instructions.iter().any(|i| i.condition.is_some()),
+        instructions: Vec::new(),
+        slots: 0,
+        checked: None,
+    };
+    for instruction in &circuit.instructions {
+        let conditional = instruction.condition.as_ref().map(|c| emitter.check(c));
+        emitter.operation(&instruction.operation, conditional)?;
+    }
+
+    let labels = |registers: &[program::Register]| -> Vec<(String, usize)> {
+        registers
+            .iter()
+            .flat_map(|r| (0..r.size).map(move |i| (r.name.clone(), i)))
+            .collect()
+    };
+    let sizes = |registers: &[program::Register]| -> Vec<(String, usize)> {
+        registers.iter().map(|r| (r.name.clone(), r.size)).collect()
+    };
+    let experiment = Experiment {
+        header: Header {
+            n_qubits: circuit.num_qubits(),
+            memory_slots: circuit.num_clbits(),
+            qubit_labels: labels(&circuit.qregs),
+            clbit_labels: labels(&circuit.cregs),
+            qreg_sizes: sizes(&circuit.qregs),
+            creg_sizes: sizes(&circuit.cregs),
+        },
+        config: Config {
+            n_qubits: circuit.num_qubits(),
+            memory_slots: circuit.num_clbits(),
+        },
+        instructions: emitter.instructions,
+    };
+
+    Ok(::serde_json::to_string_pretty(&experiment).expect("experiments can be serialized"))
+}
+
+impl<'a> Emitter<'a> {
+    /// Adds a `bfunc` for a condition, unless the last one still holds,
+    /// and returns the register slot with its result.
+    fn check(&mut self, condition: &'a Condition) -> usize {
+        if let Some((checked, slot)) = self.checked {
+            if checked == condition {
+                return slot;
+            }
+        }
+
+        let register = &condition.register;
+        let mask: Vec<_> = (register.offset..register.offset + register.size).collect();
+        let value: Vec<_> = (0..register.size)
+            .filter(|&i| condition.value.checked_shr(i as u32).unwrap_or(0) & 1 == 1)
+            .map(|i| register.offset + i)
+            .collect();
+        let slot = self.circuit.num_clbits() + self.slots;
+        self.slots += 1;
+
+        self.instructions.push(Instruction {
+            name: "bfunc".to_string(),
+            register: Some(Register::Slot(slot)),
+            mask: Some(hex(&mask)),
+            relation: Some("==".to_string()),
+            val: Some(hex(&value)),
+            ..Instruction::default()
+        });
+        self.checked = Some((condition, slot));
+        slot
+    }
+
+    fn operation(&mut self, operation: &Operation, conditional: Option<usize>) -> Result<()> {
+        let instruction = |name: &str, qubits: Vec<usize>| Instruction {
+            name: name.to_string(),
+            qubits,
+            conditional: conditional.map(Conditional::Register),
+            ..Instruction::default()
+        };
+
+        match *operation {
+            Operation::Gate(ref name, ref params, ref qubits) => {
+                return self.gate(name, params, qubits, conditional)
+            }
+            Operation::Measure(qubit, bit) => {
+                let mut measure = instruction("measure", vec![qubit]);
+                measure.memory = vec![bit];
+                if self.conditional {
+                    measure.register = Some(Register::Slots(vec![bit]));
+                }
+                self.instructions.push(measure);
+                // The memory has changed, so conditions must be checked again
+                self.checked = None;
+            }
+            Operation::Reset(qubit) => self.instructions.push(instruction("reset", vec![qubit])),
+            Operation::Barrier(ref qubits) => self
+                .instructions
+                .push(instruction("barrier", qubits.clone())),
+        }
+        Ok(())
+    }
+
+    fn gate(
+        &mut self,
+        name: &str,
+        params: &[Expr],
+        qubits: &[usize],
+        conditional: Option<usize>,
+    ) -> Result<()> {
+        let standard = &self.standard;
+        let instructions = &mut self.instructions;
+        self.circuit.expand(
+            name,
+            params,
+            qubits,
+            &|name| standard.contains(name),
+            &mut |name, params, qubits| {
+                let name = match name {
+                    "U" => "u3",
+                    "CX" => "cx",
+                    name => name,
+                };
+                instructions.push(Instruction {
+                    name: name.to_string(),
+                    qubits: qubits.to_vec(),
+                    params: params.to_vec(),
+                    conditional: conditional.map(Conditional::Register),
+                    ..Instruction::default()
+                });
+                Ok(())
+            },
+        )
+    }
+}
+
+#[test]
+fn emit_test() {
+    use circuit::tests::flatten;
+
+    let source = "OPENQASM 2.0;
+        qreg q[2];
+        creg a[1];
+        creg b[2];
+        gate g(t) x, y { U(t, 0, -t) y; CX x, y; }
+        measure q[0] -> a[0];
+        if(b==2) g(pi) q[1], q[0];
+        if(b==2) CX q[0], q[1];";
+    let circuit = flatten(source);
+    let json: ::serde_json::Value = ::serde_json::from_str(&emit(&circuit).unwrap()).unwrap();
+    let instructions = json["instructions"].as_array().unwrap();
+
+    assert_eq!(instructions.len(), 5);
+    assert_eq!(instructions[0]["register"], ::serde_json::json!([0]));
+    assert_eq!(instructions[1]["mask"], "0x6");
+    assert_eq!(instructions[1]["val"], "0x4");
+    assert_eq!(instructions[1]["register"], 3);
+    assert_eq!(instructions[2]["name"], "u3");
+    assert_eq!(instructions[2]["qubits"], ::serde_json::json!([0]));
+    assert_eq!(instructions[3]["qubits"], ::serde_json::json!([1, 0]));
+    assert_eq!(instructions[4]["conditional"], 3);
+    assert_eq!(
+        json["header"]["creg_sizes"],
+        ::serde_json::json!([["a", 1], ["b", 2]])
+    );
+
+    // Bits past the width of the value are zero
+    let wide = flatten("OPENQASM 2.0; qreg q[1]; creg c[40]; if(c==1) U(0, 0, 0) q[0];");
+    let json: ::serde_json::Value = ::serde_json::from_str(&emit(&wide).unwrap()).unwrap();
+    assert_eq!(json["instructions"][0]["val"], "0x1");
+}
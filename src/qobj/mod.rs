@@ -0,0 +1,130 @@
+//! # Qobj
+//!
+//! Converts programs to and from the experiment JSON of a
+//! [Qobj](https://arxiv.org/abs/1809.03452), the format that Qiskit used to
+//! submit circuits to IBM's quantum computers. This needs the `qobj` feature.
+//!
+//! Programs are flattened with [Program::flatten](../struct.Program.html#method.flatten)
+//! first, so that every qubit is given by its global index, then written as an
+//! experiment with [emit](fn.emit.html). Gate parameters are evaluated, as the
+//! format only has numbers.
+//!
+//! Experiments are parsed into AST nodes with [parse](fn.parse.html).
+
+mod emit;
+mod parse;
+
+pub use self::emit::emit;
+pub use self::parse::parse;
+
+/// A register's name and size, or a qubit or bit's register and index.
+type Label = (String, usize);
+
+#[derive(Serialize, Deserialize, Default)]
+struct Experiment {
+    #[serde(default)]
+    header: Header,
+    #[serde(default)]
+    config: Config,
+    instructions: Vec<Instruction>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Header {
+    n_qubits: usize,
+    memory_slots: usize,
+    qubit_labels: Vec<Label>,
+    clbit_labels: Vec<Label>,
+    qreg_sizes: Vec<Label>,
+    creg_sizes: Vec<Label>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Config {
+    n_qubits: usize,
+    memory_slots: usize,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Instruction {
+    name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    qubits: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    params: Vec<f64>,
+    /// The memory slots that measurements are stored in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    memory: Vec<usize>,
+    /// The register slots that measurements are stored in, or the register
+    /// slot that a `bfunc` stores its result in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    register: Option<Register>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conditional: Option<Conditional>,
+    // The comparison of a `bfunc`, which checks `registers & mask relation val`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mask: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    val: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Register {
+    Slot(usize),
+    Slots(Vec<usize>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Conditional {
+    /// The register slot set by an earlier `bfunc`
+    Register(usize),
+    /// The older form, which compares the memory to a value directly
+    Mask {
+        mask: String,
+        #[serde(rename = "type")]
+        relation: String,
+        val: String,
+    },
+}
+
+/// Writes a set of bits as a hexadecimal number, such as `0x6`.
+fn hex(bits: &[usize]) -> String {
+    let digits = bits.iter().max().map_or(1, |&b| b / 4 + 1);
+    let mut nibbles = vec![0; digits];
+    for &b in bits {
+        nibbles[b / 4] |= 1 << (b % 4);
+    }
+    let hex: String = nibbles
+        .iter()
+        .rev()
+        .map(|&n| ::std::char::from_digit(n, 16).unwrap())
+        .collect();
+    format!("0x{}", hex)
+}
+
+/// Reads the bits that are set in a hexadecimal number.
+fn bits(hex: &str) -> Option<Vec<usize>> {
+    let digits = hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X"))?;
+    let mut bits = Vec::new();
+    for (i, c) in digits.chars().rev().enumerate() {
+        let n = c.to_digit(16)?;
+        bits.extend((0..4).filter(|b| n & (1 << b) != 0).map(|b| i * 4 + b));
+    }
+    Some(bits)
+}
+
+#[test]
+fn hex_test() {
+    assert_eq!(hex(&[]), "0x0");
+    assert_eq!(hex(&[1, 2]), "0x6");
+    assert_eq!(hex(&[0, 68]), "0x100000000000000001");
+    assert_eq!(bits("0x100000000000000001"), Some(vec![0, 68]));
+    assert_eq!(bits("0xC"), Some(vec![2, 3]));
+    assert_eq!(bits("12"), None);
+}
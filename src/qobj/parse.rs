@@ -0,0 +1,345 @@
+use super::{bits, Conditional, Experiment, Instruction, Label, Register};
+use ast::{Argument, AstNode};
+use error::Error;
+use std::collections::HashMap;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// Instructions that have no equivalent in OpenQASM 2.0.
+const UNSUPPORTED: [&str; 4] = ["snapshot", "initialize", "roerror", "delay"];
+
+/// Parses Qobj experiment JSON into AST nodes.
+///
+/// The nodes start with the gate definitions from `qelib1.inc`, as if it was
+/// included, followed by an `opaque` declaration for every other gate used,
+/// besides `U` and `CX`. The registers are taken from the `qreg_sizes` and
+/// `creg_sizes` of the header. Without them, the qubits are put in a single
+/// register, `q`, and the memory slots in `c`.
+///
+/// * Gates are applied by name, with their parameters written as numbers.
+/// * `measure`, `reset` and `barrier` instructions are supported.
+/// * `conditional` instructions become `if` statements. The condition can be
+///   the register slot set by a `bfunc` that compares with `==`, or the older
+///   `mask` and `val` form. Either must compare a whole classical register.
+///
+/// Other instructions, such as `snapshot`, return an `Unsupported` error, and
+/// JSON that isn't an experiment returns a `SourceError`.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{Argument, AstNode};
+///
+/// let source = r#"{
+///     "header": { "qreg_sizes": [["q", 2]], "creg_sizes": [["c", 2]] },
+///     "instructions": [
+///         { "name": "h", "qubits": [0] },
+///         { "name": "measure", "qubits": [0], "memory": [1] },
+///         { "name": "bfunc", "mask": "0x3", "relation": "==", "val": "0x2", "register": 2 },
+///         { "name": "u1", "qubits": [1], "params": [0.5], "conditional": 2 }
+///     ]
+/// }"#;
+///
+/// let nodes = qasm::qobj::parse(source).unwrap();
+/// let statements = &nodes[nodes.len() - 3..];
+/// assert_eq!(statements[1], AstNode::Measure(
+///     Argument::Qubit("q".to_string(), 0),
+///     Argument::Qubit("c".to_string(), 1)
+/// ));
+/// assert_eq!(statements[2], AstNode::If(
+///     "c".to_string(),
+///     2,
+///     Box::new(AstNode::ApplyGate(
+///         "u1".to_string(),
+///         vec![Argument::Qubit("q".to_string(), 1)],
+///         vec!["0.5".to_string()]
+///     ))
+/// ));
+/// ```
+pub fn parse(source: &str) -> Result<Vec<AstNode>> {
+    let experiment: Experiment = ::serde_json::from_str(source).map_err(|_| Error::SourceError)?;
+    let header = &experiment.header;
+    let instructions = &experiment.instructions;
+
+    let used = |slots: &dyn Fn(&Instruction) -> &[usize]| {
+        instructions
+            .iter()
+            .flat_map(|i| slots(i).iter().map(|&s| s + 1))
+            .max()
+            .unwrap_or(0)
+    };
+    let registers = |sizes: &[Label], name: &str, size: usize| {
+        if sizes.is_empty() && size > 0 {
+            vec![(name.to_string(), size)]
+        } else {
+            sizes.to_vec()
+        }
+    };
+    let qregs = registers(
+        &header.qreg_sizes,
+        "q",
+        header
+            .n_qubits
+            .max(experiment.config.n_qubits)
+            .max(used(&|i| &i.qubits)),
+    );
+    let cregs = registers(
+        &header.creg_sizes,
+        "c",
+        header
+            .memory_slots
+            .max(experiment.config.memory_slots)
+            .max(used(&|i| &i.memory)),
+    );
+
+    let mut nodes = ::qelib1_gates();
+    let mut opaques: Vec<String> = Vec::new();
+    for instruction in instructions {
+        let name = &instruction.name;
+        let builtin = match name.as_str() {
+            "U" | "CX" | "measure" | "reset" | "barrier" | "bfunc" => true,
+            _ => UNSUPPORTED.contains(&name.as_str()),
+        };
+        let defined = nodes.iter().any(|node| match *node {
+            AstNode::Gate(ref gate, ..) => gate == name,
+            _ => false,
+        });
+        if !builtin && !defined && !opaques.contains(name) {
+            opaques.push(name.clone());
+            nodes.push(AstNode::Opaque(
+                name.clone(),
+                (0..instruction.qubits.len())
+                    .map(|i| Argument::Register(format!("a{}", i)))
+                    .collect(),
+                (0..instruction.params.len())
+                    .map(|i| format!("p{}", i))
+                    .collect(),
+            ));
+        }
+    }
+    nodes.extend(
+        qregs
+            .iter()
+            .map(|&(ref name, size)| AstNode::QReg(name.clone(), size as i32)),
+    );
+    nodes.extend(
+        cregs
+            .iter()
+            .map(|&(ref name, size)| AstNode::CReg(name.clone(), size as i32)),
+    );
+
+    // The bits that each `bfunc` compares, and the value they must have
+    let mut functions: HashMap<usize, (Vec<usize>, Vec<usize>)> = HashMap::new();
+    for instruction in instructions {
+        if instruction.name == "bfunc" {
+            let relation = instruction.relation.as_ref().map_or("", |r| r.as_str());
+            if relation != "==" {
+                return Err(Error::Unsupported(format!("bfunc relation `{}`", relation)));
+            }
+            let slot = match instruction.register {
+                Some(Register::Slot(slot)) => slot,
+                _ => return Err(Error::SourceError),
+            };
+            functions.insert(slot, (hex(&instruction.mask)?, hex(&instruction.val)?));
+            continue;
+        }
+
+        let condition = match instruction.conditional {
+            None => None,
+            Some(Conditional::Register(slot)) => {
+                let (mask, val) = functions.get(&slot).ok_or(Error::SourceError)?;
+                Some(condition(&cregs, mask, val)?)
+            }
+            Some(Conditional::Mask {
+                ref mask,
+                ref relation,
+                ref val,
+            }) => {
+                if relation != "equals" {
+                    return Err(Error::Unsupported(format!(
+                        "conditional type `{}`",
+                        relation
+                    )));
+                }
+                Some(condition(
+                    &cregs,
+                    &hex(&Some(mask.clone()))?,
+                    &hex(&Some(val.clone()))?,
+                )?)
+            }
+        };
+
+        for node in statements(instruction, &qregs, &cregs)? {
+            nodes.push(match condition {
+                Some((ref register, value)) => AstNode::If(register.clone(), value, Box::new(node)),
+                None => node,
+            });
+        }
+    }
+
+    Ok(nodes)
+}
+
+/// The statements for an instruction, other than a `bfunc`.
+fn statements(instruction: &Instruction, qregs: &[Label], cregs: &[Label]) -> Result<Vec<AstNode>> {
+    let name = instruction.name.as_str();
+    let qubits = instruction
+        .qubits
+        .iter()
+        .map(|&q| argument(qregs, q))
+        .collect::<Result<Vec<_>>>()?;
+    let params: Vec<_> = instruction
+        .params
+        .iter()
+        .map(|p| format!("{:?}", p))
+        .collect();
+
+    Ok(match name {
+        "measure" => {
+            if instruction.memory.len() != qubits.len() {
+                return Err(Error::SourceError);
+            }
+            let bits = instruction
+                .memory
+                .iter()
+                .map(|&c| argument(cregs, c))
+                .collect::<Result<Vec<_>>>()?;
+            qubits
+                .into_iter()
+                .zip(bits)
+                .map(|(q, c)| AstNode::Measure(q, c))
+                .collect()
+        }
+        "reset" => qubits.into_iter().map(AstNode::Reset).collect(),
+        "barrier" => vec![AstNode::Barrier(qubits)],
+        "U" if params.len() == 3 && qubits.len() == 1 => vec![AstNode::U(
+            qubits[0].clone(),
+            [params[0].clone(), params[1].clone(), params[2].clone()],
+        )],
+        "CX" if params.is_empty() && qubits.len() == 2 => {
+            vec![AstNode::CX(qubits[0].clone(), qubits[1].clone())]
+        }
+        "U" | "CX" => return Err(Error::WrongArgumentCount(name.to_string())),
+        _ if UNSUPPORTED.contains(&name) => return Err(Error::Unsupported(name.to_string())),
+        _ => vec![AstNode::ApplyGate(name.to_string(), qubits, params)],
+    })
+}
+
+/// The qubit or bit with a global index, given the sizes of the registers.
+fn argument(registers: &[Label], index: usize) -> Result<Argument> {
+    let mut offset = 0;
+    for &(ref name, size) in registers {
+        if index < offset + size {
+            return Ok(Argument::Qubit(name.clone(), (index - offset) as i32));
+        }
+        offset += size;
+    }
+    let name = registers.last().map_or("", |r| r.0.as_str());
+    Err(Error::IndexOutOfRange(name.to_string(), index as i32))
+}
+
+/// The register and value of a comparison of a mask of bits to a value.
+fn condition(cregs: &[Label], mask: &[usize], val: &[usize]) -> Result<(String, i32)> {
+    let mut offset = 0;
+    for &(ref name, size) in cregs {
+        let bits: Vec<_> = (offset..offset + size).collect();
+        if mask == &bits[..] && val.iter().all(|b| bits.contains(b)) {
+            // The value of a condition is an `i32`, so it can't use the higher bits
+            let value = val
+                .iter()
+                .map(|b| match b - offset {
+                    shift if shift < 31 => Ok(1 << shift),
+                    _ => Err(Error::Unsupported(
+                        "condition on bit 31 or above".to_string(),
+                    )),
+                })
+                .sum::<Result<i32>>()?;
+            return Ok((name.clone(), value));
+        }
+        offset += size;
+    }
+    Err(Error::Unsupported(
+        "conditional on part of a register".to_string(),
+    ))
+}
+
+fn hex(value: &Option<String>) -> Result<Vec<usize>> {
+    value
+        .as_ref()
+        .and_then(|v| bits(v))
+        .ok_or(Error::SourceError)
+}
+
+#[test]
+fn parse_test() {
+    let source = r#"{
+        "header": { "qreg_sizes": [["a", 1], ["b", 2]], "creg_sizes": [["c", 1], ["d", 2]] },
+        "instructions": [
+            { "name": "U", "qubits": [2], "params": [1, -0.5, 3.25] },
+            { "name": "cz", "qubits": [0, 1], "conditional": { "mask": "0x6", "type": "equals", "val": "0x2" } },
+            { "name": "magic", "qubits": [1, 2], "params": [2] },
+            { "name": "measure", "qubits": [0, 2], "memory": [2, 0] }
+        ]
+    }"#;
+    let nodes = parse(source).unwrap();
+    let statements = &nodes[nodes.len() - 5..];
+    let qubit = |r: &str, i| Argument::Qubit(r.to_string(), i);
+
+    assert!(nodes.contains(&AstNode::Opaque(
+        "magic".to_string(),
+        vec![
+            Argument::Register("a0".to_string()),
+            Argument::Register("a1".to_string())
+        ],
+        vec!["p0".to_string()]
+    )));
+    assert_eq!(
+        statements[0],
+        AstNode::U(
+            qubit("b", 1),
+            ["1.0".to_string(), "-0.5".to_string(), "3.25".to_string()]
+        )
+    );
+    assert_eq!(
+        statements[1],
+        AstNode::If(
+            "d".to_string(),
+            1,
+            Box::new(AstNode::ApplyGate(
+                "cz".to_string(),
+                vec![qubit("a", 0), qubit("b", 0)],
+                vec![]
+            ))
+        )
+    );
+    assert_eq!(
+        statements[4],
+        AstNode::Measure(qubit("b", 1), qubit("c", 0))
+    );
+
+    let partial = r#"{ "instructions": [
+        { "name": "bfunc", "mask": "0x1", "relation": "==", "val": "0x1", "register": 2 },
+        { "name": "x", "qubits": [0], "conditional": 2 },
+        { "name": "measure", "qubits": [0], "memory": [1] }
+    ] }"#;
+    assert_eq!(
+        parse(partial),
+        Err(Error::Unsupported(
+            "conditional on part of a register".to_string()
+        ))
+    );
+
+    let wide = r#"{
+        "header": { "qreg_sizes": [["q", 1]], "creg_sizes": [["c", 40]] },
+        "instructions": [
+            { "name": "x", "qubits": [0], "conditional": { "mask": "0xffffffffff", "type": "equals", "val": "0x800000000" } }
+        ]
+    }"#;
+    assert_eq!(
+        parse(wide),
+        Err(Error::Unsupported(
+            "condition on bit 31 or above".to_string()
+        ))
+    );
+}
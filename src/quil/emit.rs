@@ -0,0 +1,405 @@
+use ast::{Argument, AstNode};
+use circuit::{Circuit, Condition, Instruction, Operation};
+use error::Error;
+use expr::{Expr, Function, Syntax};
+use matrix::{Complex, Matrix};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+const QUIL: Syntax = Syntax {
+    pow: "^",
+    ln: "ln",
+    id_prefix: "",
+};
+
+/// Parameters of a `DEFCIRCUIT` are written with a `%`.
+const QUIL_PARAMS: Syntax = Syntax {
+    pow: "^",
+    ln: "ln",
+    id_prefix: "%",
+};
+
+/// Gates without parameters on at most this many qubits are defined by their matrix.
+const MAX_DEFGATE_QUBITS: usize = 3;
+
+/// The Quil name of a `qelib1.inc` gate, with the modifiers it needs.
+fn standard_gate(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "CX" | "cx" => "CNOT",
+        "id" => "I",
+        "x" => "X",
+        "y" => "Y",
+        "z" => "Z",
+        "h" => "H",
+        "s" => "S",
+        "sdg" => "DAGGER S",
+        "t" => "T",
+        "tdg" => "DAGGER T",
+        "rx" => "RX",
+        "ry" => "RY",
+        "rz" => "RZ",
+        "u1" => "PHASE",
+        "cz" => "CZ",
+        "cy" => "CONTROLLED Y",
+        "ch" => "CONTROLLED H",
+        "ccx" => "CCNOT",
+        "crz" => "CONTROLLED RZ",
+        "cu1" => "CPHASE",
+        _ => return None,
+    })
+}
+
+struct Emitter<'a> {
+    circuit: &'a Circuit,
+    /// The gates that are named as in Quil, as their definitions match `qelib1.inc`
+    standard: HashSet<&'a str>,
+    defined: HashSet<String>,
+    /// The gates whose definitions are being added, which can't be used
+    defining: Vec<&'a str>,
+    definitions: String,
+}
+
+/// Converts a flattened program into Quil.
+///
+/// * Qubits are numbered by their global index, and every classical register
+///   is stored in `ro`, which is declared as `DECLARE ro BIT[n]`.
+/// * Gates defined as in `qelib1.inc` are given their Quil names, such as `H`,
+///   `CNOT` and `RZ`. `U` is written as `RZ`, `RY` and `RZ` rotations.
+/// * Other gates are defined with `DEFGATE` when they have no parameters and
+///   act on at most 3 qubits, and with `DEFCIRCUIT` otherwise.
+/// * `if` statements jump past the operation with `JUMP-WHEN` and `JUMP-UNLESS`,
+///   unless every bit of the register has the value that's compared to.
+///
+/// Barriers have no equivalent in Quil and are left out. Opaque gates, and
+/// gate parameters that use `tan` or `ln`, return an `Unsupported` error.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[2];
+/// creg c[1];
+/// h q[0];
+/// cx q[0], q[1];
+/// measure q[1] -> c[0];
+/// if(c==1) rz(pi/2) q[0];
+/// "#;
+///
+/// let circuit = qasm::parse_str(source, &env::current_dir().unwrap())
+///     .and_then(|p| p.flatten())
+///     .unwrap();
+///
+/// assert_eq!(
+///     qasm::quil::emit(&circuit).unwrap(),
+///     "DECLARE ro BIT[1]
+/// H 0
+/// CNOT 0 1
+/// MEASURE 1 ro[0]
+/// JUMP-UNLESS @skip0 ro[0]
+/// RZ(pi/2) 0
+/// LABEL @skip0
+/// "
+/// );
+/// ```
+pub fn emit(circuit: &Circuit) -> Result<String> {
+    let qelib1 = ::qelib1();
+    let standard = circuit
+        .gates
+        .iter()
+        .filter(|g| standard_gate(&g.name).is_some() && qelib1.gate(&g.name) == Some(*g))
+        .map(|g| g.name.as_str())
+        .collect();
+
+    let mut emitter = Emitter {
+        circuit,
+        standard,
+        defined: HashSet::new(),
+        defining: Vec::new(),
+        definitions: String::new(),
+    };
+
+    let mut body = String::new();
+    let mut labels = 0;
+    for instruction in &circuit.instructions {
+        emitter.instruction(&mut body, instruction, &mut labels)?;
+    }
+
+    let mut out = String::new();
+    if circuit.num_clbits() > 0 {
+        writeln!(out, "DECLARE ro BIT[{}]", circuit.num_clbits()).unwrap();
+    }
+    out.push_str(&emitter.definitions);
+    out.push_str(&body);
+    Ok(out)
+}
+
+impl<'a> Emitter<'a> {
+    fn instruction(
+        &mut self,
+        out: &mut String,
+        instruction: &Instruction,
+        labels: &mut usize,
+    ) -> Result<()> {
+        let label = match instruction.condition {
+            Some(Condition {
+                ref register,
+                value,
+            }) => {
+                // A value that doesn't fit in the register can never be equal to it
+                if value < 0 || (register.size < 31 && value >= 1 << register.size) {
+                    return Ok(());
+                }
+                let label = format!("@skip{}", *labels);
+                *labels += 1;
+                for i in 0..register.size {
+                    let jump = if value.checked_shr(i as u32).unwrap_or(0) & 1 == 1 {
+                        "JUMP-UNLESS"
+                    } else {
+                        "JUMP-WHEN"
+                    };
+                    writeln!(out, "{} {} ro[{}]", jump, label, register.offset + i).unwrap();
+                }
+                Some(label)
+            }
+            None => None,
+        };
+
+        match instruction.operation {
+            Operation::Gate(ref name, ref params, ref qubits) => {
+                let qubits: Vec<_> = qubits.iter().map(|q| q.to_string()).collect();
+                let gate = self.gate(name, params, &qubits, &QUIL)?;
+                out.push_str(&gate);
+            }
+            Operation::Measure(qubit, bit) => {
+                writeln!(out, "MEASURE {} ro[{}]", qubit, bit).unwrap()
+            }
+            Operation::Reset(qubit) => writeln!(out, "RESET {}", qubit).unwrap(),
+            Operation::Barrier(_) => {}
+        }
+
+        if let Some(label) = label {
+            writeln!(out, "LABEL {}", label).unwrap();
+        }
+        Ok(())
+    }
+
+    /// The lines that apply a gate, defining it first if needed.
+    fn gate(
+        &mut self,
+        name: &str,
+        params: &[Expr],
+        qubits: &[String],
+        syntax: &Syntax,
+    ) -> Result<String> {
+        let params = params
+            .iter()
+            .map(|p| expression(p, syntax))
+            .collect::<Result<Vec<_>>>()?;
+        let qubits = qubits.join(" ");
+
+        if name == "U" {
+            // U(theta, phi, lambda) is RZ(phi) RY(theta) RZ(lambda), up to a global phase
+            let mut lines = String::new();
+            for &(gate, i) in &[("RZ", 2), ("RY", 0), ("RZ", 1)] {
+                if params[i] != "0" && params[i] != "0.0" {
+                    writeln!(lines, "{}({}) {}", gate, params[i], qubits).unwrap();
+                }
+            }
+            return Ok(lines);
+        }
+
+        let quil_name = match standard_gate(name) {
+            Some(quil_name) if name == "CX" || self.standard.contains(name) => {
+                quil_name.to_string()
+            }
+            _ => {
+                self.define(name)?;
+                name.to_string()
+            }
+        };
+
+        if params.is_empty() {
+            Ok(format!("{} {}\n", quil_name, qubits))
+        } else {
+            Ok(format!("{}({}) {}\n", quil_name, params.join(", "), qubits))
+        }
+    }
+
+    /// Adds the definition of a gate, and of the gates it uses.
+    fn define(&mut self, name: &str) -> Result<()> {
+        if self.defined.contains(name) {
+            return Ok(());
+        }
+        let gate = match self.circuit.gate(name) {
+            Some(gate) if !self.defining.contains(&name) => gate,
+            None if self.circuit.opaque(name).is_some() => {
+                return Err(Error::Unsupported(format!("opaque {}", name)))
+            }
+            _ => return Err(Error::UndefinedGate(name.to_string())),
+        };
+
+        let mut definition = String::new();
+        if gate.params.is_empty() && gate.qubits.len() <= MAX_DEFGATE_QUBITS {
+            let matrix = self.circuit.unitary(name, &[])?;
+            writeln!(definition, "DEFGATE {}:", name).unwrap();
+            write_matrix(&mut definition, &matrix);
+        } else {
+            let params: Vec<_> = gate.params.iter().map(|p| format!("%{}", p)).collect();
+            if params.is_empty() {
+                writeln!(definition, "DEFCIRCUIT {} {}:", name, gate.qubits.join(" ")).unwrap();
+            } else {
+                writeln!(
+                    definition,
+                    "DEFCIRCUIT {}({}) {}:",
+                    name,
+                    params.join(", "),
+                    gate.qubits.join(" ")
+                )
+                .unwrap();
+            }
+            self.defining.push(&gate.name);
+            for node in &gate.body {
+                let (id, exprs, args): (&str, &[String], Vec<&Argument>) = match *node {
+                    AstNode::U(ref arg, ref exprs) => ("U", exprs, vec![arg]),
+                    AstNode::CX(ref control, ref target) => ("CX", &[], vec![control, target]),
+                    AstNode::ApplyGate(ref id, ref args, ref exprs) => {
+                        (id, exprs, args.iter().collect())
+                    }
+                    _ => continue,
+                };
+                let exprs = exprs
+                    .iter()
+                    .map(|e| Expr::parse(e))
+                    .collect::<Result<Vec<_>>>()?;
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| match **arg {
+                        Argument::Register(ref id) => Ok(id.clone()),
+                        Argument::Qubit(ref id, _) => Err(Error::IndexedGateArgument(id.clone())),
+                    })
+                    .collect::<Result<_>>()?;
+                for line in self.gate(id, &exprs, &args, &QUIL_PARAMS)?.lines() {
+                    writeln!(definition, "    {}", line).unwrap();
+                }
+            }
+            self.defining.pop();
+        }
+        self.defined.insert(name.to_string());
+
+        // Definitions of the gates this one uses have been added by now
+        writeln!(definition).unwrap();
+        self.definitions.push_str(&definition);
+        Ok(())
+    }
+}
+
+/// Writes an expression, which can't use functions Quil doesn't have.
+fn expression(expr: &Expr, syntax: &Syntax) -> Result<String> {
+    match unsupported_function(expr) {
+        // Constant expressions can be evaluated instead
+        Some(_) if expr.ids().is_empty() => Ok(format!("{:?}", expr.eval()?)),
+        Some(function) => Err(Error::Unsupported(format!("{} in Quil", function))),
+        None => Ok(expr.to_string_with(syntax)),
+    }
+}
+
+fn unsupported_function(expr: &Expr) -> Option<Function> {
+    match *expr {
+        Expr::Call(function @ Function::Tan, _) | Expr::Call(function @ Function::Ln, _) => {
+            Some(function)
+        }
+        Expr::Call(_, ref e) | Expr::Neg(ref e) => unsupported_function(e),
+        Expr::Binary(_, ref l, ref r) => {
+            unsupported_function(l).or_else(|| unsupported_function(r))
+        }
+        _ => None,
+    }
+}
+
+fn write_matrix(out: &mut String, matrix: &Matrix) {
+    for row in 0..matrix.size {
+        let entries: Vec<_> = (0..matrix.size)
+            .map(|col| complex(matrix.get(row, col)))
+            .collect();
+        writeln!(out, "    {}", entries.join(", ")).unwrap();
+    }
+}
+
+/// Writes a complex number as Quil, rounding away floating point error.
+fn complex(c: Complex) -> String {
+    let round = |x: f64| {
+        let x = (x * 1e12).round() / 1e12;
+        if x == 0.0 {
+            0.0
+        } else {
+            x
+        }
+    };
+    let (re, im) = (round(c.re), round(c.im));
+    match (re, im) {
+        (re, 0.0) => format!("{}", re),
+        (0.0, im) => format!("{}i", im),
+        (re, im) => format!("{}{:+}i", re, im),
+    }
+}
+
+#[test]
+fn emit_test() {
+    let source = "OPENQASM 2.0;
+        gate flip a, b { CX b, a; }
+        gate rot(theta) a { U(theta, 0, -theta) a; }
+        qreg q[2];
+        creg c[2];
+        flip q[0], q[1];
+        rot(pi) q[1];
+        if(c==2) U(0, 0, tan(1)) q;";
+    let circuit = ::parse_tokens(::Lexer::new(source))
+        .and_then(::Program::from_ast)
+        .and_then(|p| p.flatten())
+        .unwrap();
+    let tan = format!("{:?}", 1f64.tan());
+
+    assert_eq!(
+        emit(&circuit).unwrap(),
+        format!(
+            "DECLARE ro BIT[2]
+DEFGATE flip:
+    1, 0, 0, 0
+    0, 0, 0, 1
+    0, 0, 1, 0
+    0, 1, 0, 0
+
+DEFCIRCUIT rot(%theta) a:
+    RZ(-%theta) a
+    RY(%theta) a
+
+flip 0 1
+rot(pi) 1
+JUMP-WHEN @skip0 ro[0]
+JUMP-UNLESS @skip0 ro[1]
+RZ({tan}) 0
+LABEL @skip0
+JUMP-WHEN @skip1 ro[0]
+JUMP-UNLESS @skip1 ro[1]
+RZ({tan}) 1
+LABEL @skip1
+",
+            tan = tan
+        )
+    );
+    // A gate that uses itself can't be defined
+    let mut circuit = circuit;
+    circuit.gates[1].body.push(AstNode::ApplyGate(
+        "rot".to_string(),
+        vec![Argument::Register("a".to_string())],
+        vec!["theta".to_string()],
+    ));
+    assert_eq!(emit(&circuit), Err(Error::UndefinedGate("rot".to_string())));
+}
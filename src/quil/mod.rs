@@ -0,0 +1,16 @@
+//! # Quil
+//!
+//! Converts programs to and from [Quil](https://github.com/quil-lang/quil), the
+//! instruction language used by Rigetti's quantum computers.
+//!
+//! Programs are flattened with [Program::flatten](../struct.Program.html#method.flatten)
+//! first, so that every qubit is given by its global index, then written as
+//! Quil with [emit](fn.emit.html).
+//!
+//! Quil programs are parsed into AST nodes with [parse](fn.parse.html).
+
+mod emit;
+mod parse;
+
+pub use self::emit::emit;
+pub use self::parse::parse;
@@ -0,0 +1,1050 @@
+use ast::{Argument, AstNode};
+use error::Error;
+use expr::Expr;
+use std::collections::HashMap;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// Instructions that have no equivalent in OpenQASM 2.0.
+const UNSUPPORTED: [&str; 38] = [
+    "DEFGATE",
+    "WAIT",
+    "INCLUDE",
+    "MOVE",
+    "EXCHANGE",
+    "CONVERT",
+    "LOAD",
+    "STORE",
+    "NEG",
+    "NOT",
+    "AND",
+    "IOR",
+    "XOR",
+    "OR",
+    "ADD",
+    "SUB",
+    "MUL",
+    "DIV",
+    "EQ",
+    "GT",
+    "GE",
+    "LT",
+    "LE",
+    "TRUE",
+    "FALSE",
+    "DEFFRAME",
+    "DEFWAVEFORM",
+    "DEFCAL",
+    "PULSE",
+    "CAPTURE",
+    "RAW-CAPTURE",
+    "DELAY",
+    "FENCE",
+    "SET-FREQUENCY",
+    "SHIFT-FREQUENCY",
+    "SET-PHASE",
+    "SHIFT-PHASE",
+    "SET-SCALE",
+];
+
+/// Definitions of Quil gates that aren't in `qelib1.inc`.
+const EXTRA_GATES: [(&str, &str); 3] = [
+    ("swap", "gate swap a,b { cx a,b; cx b,a; cx a,b; }"),
+    ("cswap", "gate cswap a,b,c { cx c,b; ccx a,b,c; cx c,b; }"),
+    (
+        "iswap",
+        "gate iswap a,b { s a; s b; h a; cx a,b; cx b,a; h b; }",
+    ),
+];
+
+/// A line of the program, before jumps are turned into `if` statements.
+enum Line {
+    Node(AstNode),
+    /// A jump to a label, when a bit is set (or unset), or always
+    Jump(String, Option<(Argument, bool)>),
+    Label(String),
+}
+
+struct Parser {
+    qreg: String,
+    /// The sizes of the `BIT` memory regions, which become classical registers
+    cregs: Vec<(String, i32)>,
+    gates: Vec<AstNode>,
+    /// The names of the gates defined with `DEFCIRCUIT`
+    circuits: Vec<String>,
+    /// The names of the extra gates that are used
+    extra: Vec<&'static str>,
+    num_qubits: i32,
+}
+
+/// Parses a Quil program into AST nodes.
+///
+/// The nodes start with the gate definitions from `qelib1.inc`, as if it was
+/// included, then the `DEFCIRCUIT`s as gate definitions, which replace any
+/// gate from `qelib1.inc` with the same name. Qubits are put in a
+/// single quantum register, `q`, with as many qubits as are used, and `BIT`
+/// memory declared with `DECLARE` becomes classical registers.
+///
+/// * Standard gates are given their `qelib1.inc` names, including with the
+///   `DAGGER` and `CONTROLLED` modifiers where `qelib1.inc` has the result,
+///   such as `DAGGER S` for `sdg`. `SWAP`, `CSWAP` and `ISWAP` are defined.
+/// * `MEASURE`, `RESET`, and `RESET` of every qubit are supported.
+/// * Conditional jumps past gates become `if` statements. This can be a jump
+///   for every bit of a register to a `LABEL` after the gates, or a
+///   `JUMP-WHEN @then b`, `JUMP @end`, `LABEL @then`, `LABEL @end` block
+///   on a single bit register.
+///
+/// `PRAGMA`s, `NOP`s, and a `HALT` at the end of the program are skipped. Other
+/// instructions, such as `DEFGATE`, classical arithmetic, or memory that isn't
+/// `BIT`, have no equivalent in OpenQASM 2.0, and return an `Unsupported` error.
+///
+/// ## Example
+/// ```rust
+/// extern crate qasm;
+/// use qasm::{Argument, AstNode};
+///
+/// let source = "
+/// DECLARE ro BIT[1]
+/// H 0
+/// CNOT 0 1
+/// MEASURE 1 ro[0]
+/// JUMP-UNLESS @skip ro[0]
+/// DAGGER S 0
+/// LABEL @skip
+/// ";
+///
+/// let nodes = qasm::quil::parse(source).unwrap();
+/// let statements = &nodes[nodes.len() - 4..];
+/// assert_eq!(statements[0], AstNode::ApplyGate(
+///     "h".to_string(),
+///     vec![Argument::Qubit("q".to_string(), 0)],
+///     vec![]
+/// ));
+/// assert_eq!(statements[3], AstNode::If(
+///     "ro".to_string(),
+///     1,
+///     Box::new(AstNode::ApplyGate(
+///         "sdg".to_string(),
+///         vec![Argument::Qubit("q".to_string(), 0)],
+///         vec![]
+///     ))
+/// ));
+/// ```
+pub fn parse(source: &str) -> Result<Vec<AstNode>> {
+    // Comments run to the end of the line, and indented lines are in a definition
+    let lines: Vec<(bool, &str)> = source
+        .lines()
+        .map(|line| line.split('#').next().unwrap())
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| (line.starts_with(char::is_whitespace), line.trim()))
+        .collect();
+
+    // The names that are declared, which can be used before their declaration
+    let declared = |keyword: &str| -> Vec<String> {
+        lines
+            .iter()
+            .filter_map(|&(_, line)| {
+                let mut words = line.split(|c: char| c.is_whitespace() || c == '(' || c == ':');
+                if words.next() == Some(keyword) {
+                    words.next().map(|w| w.replace('-', "_"))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    };
+    let circuits = declared("DEFCIRCUIT");
+    let names: Vec<_> = declared("DECLARE")
+        .into_iter()
+        .chain(circuits.iter().cloned())
+        .collect();
+
+    // The qubits are in a register named `q`, unless that name is taken
+    let mut qreg = "q".to_string();
+    while names.contains(&qreg) {
+        qreg.push('_');
+    }
+
+    let mut parser = Parser {
+        qreg,
+        cregs: Vec::new(),
+        gates: Vec::new(),
+        circuits,
+        extra: Vec::new(),
+        num_qubits: 0,
+    };
+
+    let mut program = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let (indented, line) = lines[i];
+        i += 1;
+        if indented {
+            return Err(Error::SourceError);
+        }
+
+        let instruction = line.split_whitespace().next().unwrap();
+        match instruction {
+            "DECLARE" => parser.declare(line)?,
+            "DEFCIRCUIT" => {
+                let start = i;
+                while i < lines.len() && lines[i].0 {
+                    i += 1;
+                }
+                let body: Vec<_> = lines[start..i].iter().map(|&(_, l)| l).collect();
+                let gate = parser.def_circuit(line, &body)?;
+                parser.gates.push(gate);
+            }
+            "PRAGMA" | "NOP" => {}
+            "HALT" if i == lines.len() => {}
+            "HALT" => return Err(Error::Unsupported("HALT".to_string())),
+            "LABEL" => program.push(Line::Label(label(line)?)),
+            "JUMP" | "JUMP-WHEN" | "JUMP-UNLESS" => program.push(parser.jump(line)?),
+            _ => program.push(Line::Node(parser.statement(line)?)),
+        }
+    }
+
+    let statements: Vec<_> = parser
+        .control_flow(program)?
+        .into_iter()
+        .flat_map(|node| parser.reset_all(node))
+        .collect();
+
+    let mut standard = ::qelib1_gates();
+    for &(name, definition) in EXTRA_GATES.iter() {
+        if parser.extra.contains(&name) {
+            standard.extend(::parse_tokens(::Lexer::new(&format!(
+                "OPENQASM 2.0; {}",
+                definition
+            )))?);
+        }
+    }
+
+    // Gates defined with DEFCIRCUIT replace those of the same name
+    let mut nodes: Vec<_> = standard
+        .iter()
+        .filter(|node| match **node {
+            AstNode::Gate(ref name, ..) => !parser.circuits.contains(name),
+            _ => true,
+        })
+        .cloned()
+        .collect();
+    nodes.extend(expand_standard(parser.gates, &standard, &parser.circuits)?);
+    let mut nodes = declaration_order(nodes);
+    if parser.num_qubits > 0 {
+        nodes.push(AstNode::QReg(parser.qreg, parser.num_qubits));
+    }
+    nodes.extend(
+        parser
+            .cregs
+            .into_iter()
+            .map(|(name, size)| AstNode::CReg(name, size)),
+    );
+    nodes.extend(statements);
+
+    Ok(nodes)
+}
+
+/// The qubit arguments, parameters and bodies of gates, by name.
+type Definitions<'a> = HashMap<&'a str, (&'a [String], &'a [String], &'a [AstNode])>;
+
+/// A gate defined with `DEFCIRCUIT` can replace a standard gate that other
+/// standard gates use, as `ry` uses `u3`. Those standard gates are expanded in
+/// the bodies of the circuits with the standard definitions, so that they keep
+/// their Quil meaning and a circuit doesn't end up using itself.
+fn expand_standard(
+    gates: Vec<AstNode>,
+    standard: &[AstNode],
+    circuits: &[String],
+) -> Result<Vec<AstNode>> {
+    fn uses_circuit(body: &[AstNode], definitions: &Definitions, circuits: &[String]) -> bool {
+        body.iter().any(|node| match *node {
+            AstNode::ApplyGate(ref id, ..) if circuits.contains(id) => true,
+            AstNode::ApplyGate(ref id, ..) => match definitions.get(id.as_str()) {
+                Some(&(_, _, body)) => uses_circuit(body, definitions, circuits),
+                _ => false,
+            },
+            _ => false,
+        })
+    }
+
+    fn expand(
+        body: &[AstNode],
+        definitions: &Definitions,
+        circuits: &[String],
+        qubits: &HashMap<String, Argument>,
+        params: &HashMap<String, Expr>,
+        nested: bool,
+        nodes: &mut Vec<AstNode>,
+    ) -> Result<()> {
+        let arg = |arg: &Argument| match *arg {
+            Argument::Register(ref id) => qubits.get(id).cloned().unwrap_or_else(|| arg.clone()),
+            Argument::Qubit(..) => arg.clone(),
+        };
+        let substitute = |e: &String| -> Result<Expr> { Ok(Expr::parse(e)?.substitute(params)) };
+        let exprs = |values: &mut [String]| -> Result<()> {
+            for e in values {
+                *e = substitute(e)?.to_string();
+            }
+            Ok(())
+        };
+
+        for node in body {
+            match *node {
+                AstNode::ApplyGate(ref id, ref args, ref values) => {
+                    match definitions.get(id.as_str()) {
+                        // Within a standard definition, the replaced gates are standard too
+                        Some(&(names, param_names, body))
+                            if (nested && circuits.contains(id))
+                                || uses_circuit(body, definitions, circuits) =>
+                        {
+                            let qubits = names.iter().cloned().zip(args.iter().map(&arg)).collect();
+                            let params = param_names
+                                .iter()
+                                .zip(values)
+                                .map(|(name, e)| Ok((name.clone(), substitute(e)?)))
+                                .collect::<Result<_>>()?;
+                            expand(body, definitions, circuits, &qubits, &params, true, nodes)?;
+                        }
+                        _ => {
+                            let mut values = values.clone();
+                            exprs(&mut values)?;
+                            nodes.push(AstNode::ApplyGate(
+                                id.clone(),
+                                args.iter().map(&arg).collect(),
+                                values,
+                            ));
+                        }
+                    }
+                }
+                AstNode::U(ref a, ref values) => {
+                    let mut values = values.clone();
+                    exprs(&mut values)?;
+                    nodes.push(AstNode::U(arg(a), values));
+                }
+                AstNode::CX(ref c, ref t) => nodes.push(AstNode::CX(arg(c), arg(t))),
+                _ => nodes.push(node.clone()),
+            }
+        }
+
+        Ok(())
+    }
+
+    let definitions: HashMap<_, _> = standard
+        .iter()
+        .filter_map(|node| match *node {
+            AstNode::Gate(ref name, ref qubits, ref params, ref body) => {
+                Some((name.as_str(), (&qubits[..], &params[..], &body[..])))
+            }
+            _ => None,
+        })
+        .collect();
+    if !circuits
+        .iter()
+        .any(|name| definitions.contains_key(name.as_str()))
+    {
+        return Ok(gates);
+    }
+
+    gates
+        .into_iter()
+        .map(|node| match node {
+            AstNode::Gate(name, qubits, params, body) => {
+                let mut nodes = vec![];
+                expand(
+                    &body,
+                    &definitions,
+                    circuits,
+                    &HashMap::new(),
+                    &HashMap::new(),
+                    false,
+                    &mut nodes,
+                )?;
+                Ok(AstNode::Gate(name, qubits, params, nodes))
+            }
+            node => Ok(node),
+        })
+        .collect()
+}
+
+/// Orders gate definitions so that each comes after the gates it uses,
+/// as a `DEFCIRCUIT` can replace a standard gate with one that uses gates
+/// defined later in `qelib1.inc`. Otherwise the order is kept.
+fn declaration_order(gates: Vec<AstNode>) -> Vec<AstNode> {
+    fn visit(
+        i: usize,
+        gates: &[AstNode],
+        index: &HashMap<&str, usize>,
+        visited: &mut Vec<bool>,
+        order: &mut Vec<usize>,
+    ) {
+        if visited[i] {
+            return;
+        }
+        visited[i] = true;
+        if let AstNode::Gate(_, _, _, ref body) = gates[i] {
+            for node in body {
+                if let AstNode::ApplyGate(ref id, ..) = *node {
+                    if let Some(&j) = index.get(id.as_str()) {
+                        visit(j, gates, index, visited, order);
+                    }
+                }
+            }
+        }
+        order.push(i);
+    }
+
+    let index: HashMap<_, _> = gates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, node)| match *node {
+            AstNode::Gate(ref name, ..) => Some((name.as_str(), i)),
+            _ => None,
+        })
+        .collect();
+    let mut visited = vec![false; gates.len()];
+    let mut order = Vec::with_capacity(gates.len());
+    for i in 0..gates.len() {
+        visit(i, &gates, &index, &mut visited, &mut order);
+    }
+
+    let mut gates: Vec<_> = gates.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| gates[i].take()).collect()
+}
+
+impl Parser {
+    fn declare(&mut self, line: &str) -> Result<()> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let unsupported = || Error::Unsupported(line.to_string());
+        if words.len() != 3 {
+            return Err(unsupported());
+        }
+
+        let size = match words[2] {
+            "BIT" => 1,
+            ty if ty.starts_with("BIT[") && ty.ends_with(']') => {
+                ty[4..ty.len() - 1].parse().map_err(|_| Error::MissingInt)?
+            }
+            _ => return Err(unsupported()),
+        };
+        self.cregs.push((words[1].to_string(), size));
+        Ok(())
+    }
+
+    fn def_circuit(&mut self, header: &str, body: &[&str]) -> Result<AstNode> {
+        let header = header["DEFCIRCUIT".len()..].trim();
+        if !header.ends_with(':') {
+            return Err(Error::SourceError);
+        }
+        let (name, params, qubits) = split_application(&header[..header.len() - 1])?;
+        let params = params
+            .iter()
+            .map(|p| match p.strip_prefix('%') {
+                Some(param) => Ok(param.to_string()),
+                None => Err(Error::SourceError),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut nodes = Vec::new();
+        for line in body {
+            let instruction = line.split_whitespace().next().unwrap();
+            if instruction == "MEASURE" || instruction == "RESET" || instruction.starts_with("JUMP")
+            {
+                return Err(Error::Unsupported(format!("{} in DEFCIRCUIT", instruction)));
+            }
+            nodes.push(self.gate(line, &params, &|q| {
+                if qubits.contains(&q) {
+                    Ok(Argument::Register(q.to_string()))
+                } else {
+                    Err(Error::UndefinedGateArgument(q.to_string()))
+                }
+            })?);
+        }
+
+        Ok(AstNode::Gate(
+            name,
+            qubits.iter().map(|q| q.to_string()).collect(),
+            params,
+            nodes,
+        ))
+    }
+
+    fn statement(&mut self, line: &str) -> Result<AstNode> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words[0] {
+            "MEASURE" if words.len() == 3 => Ok(AstNode::Measure(
+                self.qubit(words[1])?,
+                self.memory(words[2])?,
+            )),
+            "MEASURE" => Err(Error::Unsupported("MEASURE without memory".to_string())),
+            "RESET" if words.len() == 1 => {
+                Ok(AstNode::Reset(Argument::Register(self.qreg.clone())))
+            }
+            "RESET" if words.len() == 2 => Ok(AstNode::Reset(self.qubit(words[1])?)),
+            "RESET" => Err(Error::SourceError),
+            instruction if UNSUPPORTED.contains(&instruction) => {
+                Err(Error::Unsupported(instruction.to_string()))
+            }
+            _ => {
+                let qreg = self.qreg.clone();
+                let gate =
+                    self.gate(line, &[], &|q| Ok(Argument::Qubit(qreg.clone(), index(q)?)))?;
+                if let AstNode::ApplyGate(_, ref args, _) = gate {
+                    for arg in args {
+                        if let Argument::Qubit(_, index) = *arg {
+                            self.num_qubits = self.num_qubits.max(index + 1);
+                        }
+                    }
+                }
+                Ok(gate)
+            }
+        }
+    }
+
+    fn jump(&self, line: &str) -> Result<Line> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match (words[0], words.len()) {
+            ("JUMP", 2) => Ok(Line::Jump(label(line)?, None)),
+            ("JUMP-WHEN", 3) => Ok(Line::Jump(
+                label(line)?,
+                Some((self.memory(words[2])?, true)),
+            )),
+            ("JUMP-UNLESS", 3) => Ok(Line::Jump(
+                label(line)?,
+                Some((self.memory(words[2])?, false)),
+            )),
+            _ => Err(Error::SourceError),
+        }
+    }
+
+    fn qubit(&mut self, text: &str) -> Result<Argument> {
+        let index = index(text)?;
+        self.num_qubits = self.num_qubits.max(index + 1);
+        Ok(Argument::Qubit(self.qreg.clone(), index))
+    }
+
+    /// A reference to a bit of declared memory, such as `ro[1]`, or `ro` for its first bit.
+    fn memory(&self, text: &str) -> Result<Argument> {
+        let (name, index) = match text.find('[') {
+            Some(open) if text.ends_with(']') => (
+                &text[..open],
+                text[open + 1..text.len() - 1]
+                    .parse()
+                    .map_err(|_| Error::MissingInt)?,
+            ),
+            Some(_) => return Err(Error::SourceError),
+            None => (text, 0),
+        };
+        match self.cregs.iter().find(|&(creg, _)| creg == name) {
+            Some(&(_, size)) if index >= 0 && index < size => {
+                Ok(Argument::Qubit(name.to_string(), index))
+            }
+            Some(_) => Err(Error::IndexOutOfRange(name.to_string(), index)),
+            None => Err(Error::UndefinedCReg(name.to_string())),
+        }
+    }
+
+    fn register_size(&self, name: &str) -> i32 {
+        self.cregs
+            .iter()
+            .find(|&(creg, _)| creg == name)
+            .map_or(0, |&(_, size)| size)
+    }
+
+    /// Converts a gate application, with the Quil gate names mapped to `qelib1.inc`.
+    fn gate(
+        &mut self,
+        line: &str,
+        params: &[String],
+        qubit: &dyn Fn(&str) -> Result<Argument>,
+    ) -> Result<AstNode> {
+        let mut rest = line;
+        let mut modifiers: HashMap<&str, usize> = HashMap::new();
+        loop {
+            let word = rest.split_whitespace().next().unwrap_or("");
+            match word {
+                "DAGGER" | "CONTROLLED" | "FORKED" => {
+                    *modifiers.entry(word).or_insert(0) += 1;
+                    rest = rest.trim_start()[word.len()..].trim_start();
+                }
+                _ => break,
+            }
+        }
+        if modifiers.contains_key("FORKED") {
+            return Err(Error::Unsupported("FORKED".to_string()));
+        }
+
+        let (name, exprs, qubits) = split_application(rest)?;
+        let mut exprs = exprs
+            .iter()
+            .map(|e| expression(e, params))
+            .collect::<Result<Vec<_>>>()?;
+        let args = qubits
+            .iter()
+            .map(|q| qubit(q))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Gates from DEFCIRCUIT keep their name
+        if self.circuits.contains(&name) {
+            return if modifiers.is_empty() {
+                Ok(AstNode::ApplyGate(name, args, exprs))
+            } else {
+                Err(Error::Unsupported(line.to_string()))
+            };
+        }
+
+        let dagger = modifiers.get("DAGGER").map_or(0, |n| n % 2) == 1;
+        let controls = modifiers.get("CONTROLLED").cloned().unwrap_or(0);
+        let unsupported = || Error::Unsupported(line.to_string());
+
+        // The inverse, as a Quil gate
+        let mut quil_name = name.as_str();
+        if dagger {
+            match quil_name {
+                "S" => quil_name = "DAGGER S",
+                "T" => quil_name = "DAGGER T",
+                "I" | "X" | "Y" | "Z" | "H" | "CNOT" | "CZ" | "CCNOT" | "SWAP" | "CSWAP" => {}
+                "RX" | "RY" | "RZ" | "PHASE" | "CPHASE" if exprs.len() == 1 => {
+                    exprs[0] = negate(&exprs[0])?
+                }
+                _ => return Err(unsupported()),
+            }
+        }
+
+        let qasm_name = match (controls, quil_name) {
+            (0, "I") => "id",
+            (0, "X") => "x",
+            (0, "Y") => "y",
+            (0, "Z") => "z",
+            (0, "H") => "h",
+            (0, "S") => "s",
+            (0, "T") => "t",
+            (0, "DAGGER S") => "sdg",
+            (0, "DAGGER T") => "tdg",
+            (0, "RX") => "rx",
+            (0, "RY") => "ry",
+            (0, "RZ") => "rz",
+            (0, "PHASE") => "u1",
+            (0, "CNOT") | (1, "X") => "cx",
+            (0, "CZ") | (1, "Z") => "cz",
+            (1, "Y") => "cy",
+            (1, "H") => "ch",
+            (1, "RZ") => "crz",
+            (0, "CPHASE") | (1, "PHASE") => "cu1",
+            (0, "CCNOT") | (1, "CNOT") | (2, "X") => "ccx",
+            (0, "SWAP") => "swap",
+            (0, "CSWAP") | (1, "SWAP") => "cswap",
+            (0, "ISWAP") => "iswap",
+            _ => return Err(unsupported()),
+        };
+
+        for &(extra_name, _) in EXTRA_GATES.iter() {
+            if extra_name == qasm_name && !self.extra.contains(&extra_name) {
+                self.extra.push(extra_name);
+            }
+        }
+        Ok(AstNode::ApplyGate(qasm_name.to_string(), args, exprs))
+    }
+
+    /// Expands a `RESET` of every qubit into a reset of each qubit of the register,
+    /// which are only known once the whole program is parsed.
+    fn reset_all(&self, node: AstNode) -> Vec<AstNode> {
+        match node {
+            AstNode::Reset(Argument::Register(ref name)) if *name == self.qreg => (0..self
+                .num_qubits)
+                .map(|i| AstNode::Reset(Argument::Qubit(name.clone(), i)))
+                .collect(),
+            AstNode::If(register, value, node) => self
+                .reset_all(*node)
+                .into_iter()
+                .map(|node| AstNode::If(register.clone(), value, Box::new(node)))
+                .collect(),
+            node => vec![node],
+        }
+    }
+
+    /// Turns conditional jumps over gates into `if` statements.
+    fn control_flow(&self, lines: Vec<Line>) -> Result<Vec<AstNode>> {
+        let mut nodes = Vec::new();
+        let mut lines = lines.into_iter().peekable();
+
+        while let Some(line) = lines.next() {
+            let (target, bit, when) = match line {
+                Line::Node(node) => {
+                    nodes.push(node);
+                    continue;
+                }
+                Line::Label(label) => return Err(Error::Unsupported(format!("LABEL {}", label))),
+                Line::Jump(label, None) => {
+                    return Err(Error::Unsupported(format!("JUMP {}", label)))
+                }
+                Line::Jump(label, Some((bit, when))) => (label, bit, when),
+            };
+
+            // The jumps to the same label, one for each bit of the register
+            let mut bits = vec![(bit, when)];
+            while let Some(&Line::Jump(ref label, Some(_))) = lines.peek() {
+                if *label != target {
+                    break;
+                }
+                if let Some(Line::Jump(_, Some(bit))) = lines.next() {
+                    bits.push(bit);
+                }
+            }
+
+            let skipped = conditional_block(&mut lines)?;
+            match lines.next() {
+                // Jumps past a block when the register doesn't have a value
+                Some(Line::Label(ref label)) if *label == target => {
+                    let (register, value) = self.condition(&bits)?;
+                    nodes.extend(
+                        skipped
+                            .into_iter()
+                            .map(|node| AstNode::If(register.clone(), value, Box::new(node))),
+                    );
+                }
+                // Jumps to a `then` block, which is followed by an `end` label
+                Some(Line::Jump(end, None)) if bits.len() == 1 => {
+                    let (register, value) = self.condition(&bits)?;
+                    let then_label = lines.next();
+                    let then = conditional_block(&mut lines)?;
+                    match (then_label, lines.next()) {
+                        (Some(Line::Label(ref t)), Some(Line::Label(ref e)))
+                            if *t == target && *e == end => {}
+                        _ => return Err(Error::Unsupported(format!("JUMP-WHEN {}", target))),
+                    }
+                    // The `else` block runs when the jump isn't taken, the `then` block when it is
+                    let if_node = |value: i32| {
+                        let register = register.clone();
+                        move |node| AstNode::If(register.clone(), value, Box::new(node))
+                    };
+                    nodes.extend(skipped.into_iter().map(if_node(value)));
+                    nodes.extend(then.into_iter().map(if_node(1 - value)));
+                }
+                _ => return Err(Error::Unsupported(format!("JUMP-WHEN {}", target))),
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// The register and value that a group of jumps skip a block for, which must
+    /// test every bit of one register. A block is run when its jumps aren't taken.
+    fn condition(&self, bits: &[(Argument, bool)]) -> Result<(String, i32)> {
+        let register = match bits[0].0 {
+            Argument::Qubit(ref name, _) | Argument::Register(ref name) => name.clone(),
+        };
+        let size = self.register_size(&register);
+        let mut value = 0;
+        let mut seen = vec![false; size as usize];
+        for &(ref bit, when) in bits {
+            match *bit {
+                Argument::Qubit(ref name, index) if *name == register && !seen[index as usize] => {
+                    seen[index as usize] = true;
+                    // The value of a condition is an `i32`, so it can't use the higher bits
+                    if !when && index >= 31 {
+                        return Err(Error::Unsupported(
+                            "condition on bit 31 or above".to_string(),
+                        ));
+                    }
+                    if !when {
+                        value |= 1 << index;
+                    }
+                }
+                _ => {
+                    return Err(Error::Unsupported(
+                        "JUMP-WHEN on several registers".to_string(),
+                    ))
+                }
+            }
+        }
+        if seen.contains(&false) {
+            return Err(Error::Unsupported(format!(
+                "JUMP-WHEN on part of {}",
+                register
+            )));
+        }
+        Ok((register, value))
+    }
+}
+
+/// The index of a qubit, which can't be negative.
+fn index(text: &str) -> Result<i32> {
+    match text.parse() {
+        Ok(index) if index >= 0 => Ok(index),
+        _ => Err(Error::SourceError),
+    }
+}
+
+/// The gates and resets up to the next jump or label.
+fn conditional_block<I: Iterator<Item = Line>>(
+    lines: &mut ::std::iter::Peekable<I>,
+) -> Result<Vec<AstNode>> {
+    let mut nodes = Vec::new();
+    while let Some(Line::Node(node)) = lines.peek() {
+        if let AstNode::Measure(..) = *node {
+            return Err(Error::Unsupported(
+                "MEASURE in a conditional block".to_string(),
+            ));
+        }
+        if let Some(Line::Node(node)) = lines.next() {
+            nodes.push(node);
+        }
+    }
+    Ok(nodes)
+}
+
+fn label(line: &str) -> Result<String> {
+    match line.split_whitespace().nth(1) {
+        Some(label) if label.starts_with('@') => Ok(label.to_string()),
+        _ => Err(Error::SourceError),
+    }
+}
+
+/// Splits a gate application or definition header into its name, parameters and qubits.
+fn split_application(text: &str) -> Result<(String, Vec<String>, Vec<&str>)> {
+    let end = text
+        .find(|c: char| c == '(' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let name = &text[..end];
+    let mut rest = &text[end..];
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        return Err(Error::SourceError);
+    }
+
+    let mut params = Vec::new();
+    if rest.starts_with('(') {
+        let mut depth = 0;
+        let mut start = 1;
+        let mut close = None;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 1 => {
+                    close = Some(i);
+                    break;
+                }
+                ')' => depth -= 1,
+                ',' if depth == 1 => {
+                    params.push(rest[start..i].trim().to_string());
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        let close = close.ok_or(Error::SourceError)?;
+        params.push(rest[start..close].trim().to_string());
+        rest = &rest[close + 1..];
+    }
+
+    // Quil names can have dashes, which OpenQASM identifiers can't
+    Ok((
+        name.replace('-', "_"),
+        params,
+        rest.split_whitespace().collect(),
+    ))
+}
+
+/// Converts an expression, which can only use the parameters given.
+fn expression(text: &str, params: &[String]) -> Result<String> {
+    let expr = Expr::parse(&text.replace('%', ""))?;
+    if let Some(id) = expr
+        .ids()
+        .into_iter()
+        .find(|id| !params.iter().any(|p| p == id))
+    {
+        return Err(Error::Unsupported(format!(
+            "memory reference `{}` in a parameter",
+            id
+        )));
+    }
+    Ok(expr.to_string())
+}
+
+fn negate(expr: &str) -> Result<String> {
+    Ok(Expr::parse(&format!("-({})", expr))?.to_string())
+}
+
+#[test]
+fn parse_test() {
+    let source = "
+DECLARE c BIT[2]
+DEFCIRCUIT rot(%theta) a:
+    RZ(%theta / 2) a
+    DAGGER RY(%theta) a
+
+SWAP 0 2
+rot(pi) 1
+MEASURE 0 c[1]
+JUMP-WHEN @then c[1]
+JUMP @end
+LABEL @then
+X 1
+LABEL @end
+";
+    assert_eq!(
+        parse(source),
+        Err(Error::Unsupported("JUMP-WHEN on part of c".to_string()))
+    );
+
+    let nodes = parse(&source.replace("BIT[2]", "BIT").replace("c[1]", "c")).unwrap();
+    let q = |i| vec![Argument::Qubit("q".to_string(), i)];
+    let statements = &nodes[nodes.len() - 7..];
+
+    assert_eq!(
+        statements[0],
+        AstNode::Gate(
+            "rot".to_string(),
+            vec!["a".to_string()],
+            vec!["theta".to_string()],
+            vec![
+                AstNode::ApplyGate(
+                    "rz".to_string(),
+                    vec![Argument::Register("a".to_string())],
+                    vec!["theta/2".to_string()]
+                ),
+                AstNode::ApplyGate(
+                    "ry".to_string(),
+                    vec![Argument::Register("a".to_string())],
+                    vec!["-theta".to_string()]
+                ),
+            ]
+        )
+    );
+    assert_eq!(statements[1], AstNode::QReg("q".to_string(), 3));
+    assert_eq!(statements[2], AstNode::CReg("c".to_string(), 1));
+    assert_eq!(
+        statements[4],
+        AstNode::ApplyGate("rot".to_string(), q(1), vec!["pi".to_string()])
+    );
+    assert_eq!(
+        statements[6],
+        AstNode::If(
+            "c".to_string(),
+            1,
+            Box::new(AstNode::ApplyGate("x".to_string(), q(1), vec![]))
+        )
+    );
+    assert!(nodes
+        .iter()
+        .any(|n| matches!(*n, AstNode::Gate(ref name, ..) if name == "swap")));
+
+    // A replaced standard gate comes after the gates its new definition uses,
+    // and standard gates that use it are expanded in its body
+    let nodes =
+        parse("DEFCIRCUIT u3(%t, %p, %l) a:\n    RZ(%l) a\n    RY(%t) a\n\nu3(pi, 0, 0) 0\n")
+            .unwrap();
+    let position = |name: &str| {
+        nodes
+            .iter()
+            .position(|n| matches!(*n, AstNode::Gate(ref id, ..) if id == name))
+            .unwrap()
+    };
+    assert!(position("rz") < position("u3"));
+    assert_eq!(
+        nodes[position("u3")],
+        AstNode::Gate(
+            "u3".to_string(),
+            vec!["a".to_string()],
+            vec!["t".to_string(), "p".to_string(), "l".to_string()],
+            vec![
+                AstNode::ApplyGate(
+                    "rz".to_string(),
+                    vec![Argument::Register("a".to_string())],
+                    vec!["l".to_string()]
+                ),
+                AstNode::U(
+                    Argument::Register("a".to_string()),
+                    ["t".to_string(), "0".to_string(), "0".to_string()]
+                ),
+            ]
+        )
+    );
+
+    // A reset of every qubit resets each qubit that the program uses
+    let nodes = parse("RESET\nX 1\n").unwrap();
+    assert_eq!(
+        nodes[nodes.len() - 3..nodes.len() - 1],
+        [
+            AstNode::Reset(Argument::Qubit("q".to_string(), 0)),
+            AstNode::Reset(Argument::Qubit("q".to_string(), 1)),
+        ]
+    );
+    let nodes = parse("RESET").unwrap();
+    assert!(!nodes
+        .iter()
+        .any(|n| matches!(*n, AstNode::QReg(..) | AstNode::Reset(..))));
+
+    assert_eq!(parse("X -1"), Err(Error::SourceError));
+    assert_eq!(parse("RESET -1"), Err(Error::SourceError));
+    assert_eq!(
+        parse("DECLARE ro BIT\nMEASURE 0 ro[-1]"),
+        Err(Error::IndexOutOfRange("ro".to_string(), -1))
+    );
+
+    // Conditions on registers of 32 bits or more, where the value fits in an `i32`
+    let jumps = |unless: usize| {
+        let mut source = "DECLARE ro BIT[40]\n".to_string();
+        for i in 0..40 {
+            let jump = if i == unless {
+                "JUMP-UNLESS"
+            } else {
+                "JUMP-WHEN"
+            };
+            source.push_str(&format!("{} @skip ro[{}]\n", jump, i));
+        }
+        source + "X 0\nLABEL @skip\n"
+    };
+    let nodes = parse(&jumps(30)).unwrap();
+    assert_eq!(
+        nodes[nodes.len() - 1],
+        AstNode::If(
+            "ro".to_string(),
+            1 << 30,
+            Box::new(AstNode::ApplyGate("x".to_string(), q(0), vec![]))
+        )
+    );
+    assert_eq!(
+        parse(&jumps(35)),
+        Err(Error::Unsupported(
+            "condition on bit 31 or above".to_string()
+        ))
+    );
+    assert_eq!(
+        parse("DECLARE ro BIT[64]\nJUMP-UNLESS @skip ro[40]\nX 0\nLABEL @skip\n"),
+        Err(Error::Unsupported(
+            "condition on bit 31 or above".to_string()
+        ))
+    );
+    assert_eq!(
+        parse("DECLARE ro BIT[64]\nJUMP-WHEN @skip ro[40]\nX 0\nLABEL @skip\n"),
+        Err(Error::Unsupported("JUMP-WHEN on part of ro".to_string()))
+    );
+
+    assert_eq!(
+        parse("DECLARE theta REAL"),
+        Err(Error::Unsupported("DECLARE theta REAL".to_string()))
+    );
+    assert_eq!(
+        parse("ADD ro 1"),
+        Err(Error::Unsupported("ADD".to_string()))
+    );
+}
+
+#[test]
+fn extra_gates_test() {
+    use matrix::Complex;
+    use program::Program;
+
+    let circuit = Program::from_ast(parse("ISWAP 0 1").unwrap())
+        .and_then(|p| p.flatten())
+        .unwrap();
+    let iswap = circuit.unitary("iswap", &[]).unwrap();
+    let (o, l, i) = (Complex::ZERO, Complex::ONE, Complex::new(0.0, 1.0));
+    let expected = [l, o, o, o, o, o, i, o, o, i, o, o, o, o, o, l];
+
+    for (a, b) in iswap.data.iter().zip(expected.iter()) {
+        assert!((a.re - b.re).abs() < 1e-12 && (a.im - b.im).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,212 @@
+//! Semantic checks of a program.
+//! These find errors that the parser can't, as they
+//! depend on what has been declared.
+
+use ast::{Argument, AstNode};
+use error::Error;
+use program::{Program, Register};
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+pub fn check(program: &Program) -> Result<()> {
+    for (i, gate) in program.gates().iter().enumerate() {
+        for node in &gate.body {
+            match *node {
+                AstNode::CX(ref control, ref target) => {
+                    check_distinct(&[control.clone(), target.clone()])?;
+                }
+                AstNode::ApplyGate(ref id, ref args, ref params) => {
+                    // A gate may only use gates declared before it, which also rules out recursion
+                    let declared = program.gates()[..i].iter().any(|g| g.name == *id);
+                    if !declared && program.opaque(id).is_none() {
+                        return Err(Error::UndefinedGate(id.clone()));
+                    }
+                    check_signature(program, id, args.len(), params.len())?;
+                    check_distinct(args)?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for node in program.statements() {
+        check_statement(program, node)?;
+    }
+
+    Ok(())
+}
+
+/// The number of qubits and the number of parameters of a gate.
+fn signature(program: &Program, id: &str) -> Option<(usize, usize)> {
+    program
+        .gate(id)
+        .map(|g| (g.qubits.len(), g.params.len()))
+        .or_else(|| program.opaque(id).map(|g| (g.qubits.len(), g.params.len())))
+}
+
+fn check_signature(program: &Program, id: &str, num_args: usize, num_params: usize) -> Result<()> {
+    let (qubits, params) =
+        signature(program, id).ok_or_else(|| Error::UndefinedGate(id.to_string()))?;
+
+    if qubits != num_args {
+        return Err(Error::WrongArgumentCount(id.to_string()));
+    }
+    if params != num_params {
+        return Err(Error::WrongParameterCount(id.to_string()));
+    }
+
+    Ok(())
+}
+
+fn check_statement(program: &Program, node: &AstNode) -> Result<()> {
+    match *node {
+        AstNode::Barrier(ref args) => {
+            for arg in args {
+                qubit_size(program, arg)?;
+            }
+        }
+        AstNode::Reset(ref arg) => {
+            qubit_size(program, arg)?;
+        }
+        AstNode::Measure(ref qubit, ref bit) => {
+            let sizes = [qubit_size(program, qubit)?, bit_size(program, bit)?];
+            check_sizes("measure", &sizes)?;
+        }
+        AstNode::U(ref arg, _) => {
+            qubit_size(program, arg)?;
+        }
+        AstNode::CX(ref control, ref target) => {
+            let sizes = [qubit_size(program, control)?, qubit_size(program, target)?];
+            check_sizes("CX", &sizes)?;
+            check_distinct(&[control.clone(), target.clone()])?;
+        }
+        AstNode::ApplyGate(ref id, ref args, ref params) => {
+            check_signature(program, id, args.len(), params.len())?;
+            let sizes = args
+                .iter()
+                .map(|arg| qubit_size(program, arg))
+                .collect::<Result<Vec<_>>>()?;
+            check_sizes(id, &sizes)?;
+            check_distinct(args)?;
+        }
+        AstNode::If(ref id, _, ref node) => {
+            if program.creg(id).is_none() {
+                return Err(Error::UndefinedCReg(id.clone()));
+            }
+            check_statement(program, node)?;
+        }
+        AstNode::QReg(ref id, _)
+        | AstNode::CReg(ref id, _)
+        | AstNode::Gate(ref id, ..)
+        | AstNode::Opaque(ref id, ..) => return Err(Error::Redefinition(id.clone())),
+    }
+
+    Ok(())
+}
+
+/// Registers used together must all be the same size, as the
+/// statement is applied to each of their qubits in turn.
+fn check_sizes(id: &str, sizes: &[Option<usize>]) -> Result<()> {
+    let mut sizes = sizes.iter().filter_map(|&size| size);
+    if let Some(first) = sizes.next() {
+        if sizes.any(|size| size != first) {
+            return Err(Error::RegisterSizeMismatch(id.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// The qubit arguments of a gate must be distinct. A whole register
+/// overlaps with any of its qubits, as it is applied to each in turn.
+fn check_distinct(args: &[Argument]) -> Result<()> {
+    for (i, arg) in args.iter().enumerate() {
+        let (id, index) = split(arg);
+        let overlaps = args[..i].iter().any(|other| match split(other) {
+            (other_id, _) if other_id != id => false,
+            (_, Some(other_index)) => index.is_none_or(|index| index == other_index),
+            (_, None) => true,
+        });
+        if overlaps {
+            let name = index.map_or(id.to_string(), |index| format!("{}[{}]", id, index));
+            return Err(Error::DuplicateArgument(name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a quantum argument, returning the size of the register when
+/// the whole register is used, or `None` for a single qubit.
+fn qubit_size(program: &Program, arg: &Argument) -> Result<Option<usize>> {
+    let (id, _) = split(arg);
+    let register = program.qreg(id).ok_or_else(|| Error::UndefinedQReg(id.to_string()))?;
+    argument_size(register, arg)
+}
+
+/// Checks a classical argument, returning the size of the register when
+/// the whole register is used, or `None` for a single bit.
+fn bit_size(program: &Program, arg: &Argument) -> Result<Option<usize>> {
+    let (id, _) = split(arg);
+    let register = program.creg(id).ok_or_else(|| Error::UndefinedCReg(id.to_string()))?;
+    argument_size(register, arg)
+}
+
+fn argument_size(register: &Register, arg: &Argument) -> Result<Option<usize>> {
+    match split(arg) {
+        (_, None) => Ok(Some(register.size)),
+        (_, Some(index)) if (index as usize) < register.size => Ok(None),
+        (id, Some(index)) => Err(Error::IndexOutOfRange(id.to_string(), index)),
+    }
+}
+
+fn split(arg: &Argument) -> (&str, Option<i32>) {
+    match *arg {
+        Argument::Register(ref id) => (id, None),
+        Argument::Qubit(ref id, index) => (id, Some(index)),
+    }
+}
+
+#[test]
+fn check_test() {
+    let program = Program::from_ast(vec![
+        AstNode::QReg("q".to_string(), 2),
+        AstNode::CReg("c".to_string(), 2),
+        AstNode::CX(Argument::Qubit("q".to_string(), 0), Argument::Qubit("q".to_string(), 1)),
+        AstNode::Measure(Argument::Register("q".to_string()), Argument::Qubit("c".to_string(), 2)),
+    ]);
+
+    assert_eq!(check(&program.unwrap()), Err(Error::IndexOutOfRange("c".to_string(), 2)));
+}
+
+#[test]
+fn check_gates_test() {
+    let check_str = |source| {
+        let program = ::parse_str(source, ::std::path::Path::new(""))?;
+        check(&program)
+    };
+    let source = "OPENQASM 2.0; qreg q[2]; gate g a, b { CX a, b; } g q[0], q[1]; g q[1], q[0];";
+
+    assert!(check_str(source).is_ok());
+    assert_eq!(
+        check_str("OPENQASM 2.0; qreg q[1]; gate g a { g a; } g q[0];"),
+        Err(Error::UndefinedGate("g".to_string()))
+    );
+    assert_eq!(
+        check_str("OPENQASM 2.0; gate f a { g a; } gate g a { U(0, 0, 0) a; }"),
+        Err(Error::UndefinedGate("g".to_string()))
+    );
+    assert_eq!(
+        check_str("OPENQASM 2.0; qreg q[2]; CX q[0], q[0];"),
+        Err(Error::DuplicateArgument("q[0]".to_string()))
+    );
+    assert_eq!(
+        check_str("OPENQASM 2.0; qreg q[2]; gate g a, b { CX a, b; } g q, q[1];"),
+        Err(Error::DuplicateArgument("q[1]".to_string()))
+    );
+    assert_eq!(
+        check_str("OPENQASM 2.0; gate g a, b { CX a, a; }"),
+        Err(Error::DuplicateArgument("a".to_string()))
+    );
+}
@@ -0,0 +1,297 @@
+//! The serialized forms of the AST and errors, used with the `serde` feature.
+//!
+//! The public types are converted to these mirrors, which give every field a
+//! name, so the serialized shape doesn't depend on tuple positions.
+
+use ast::{Argument, AstNode};
+use error::Error;
+use std::io::ErrorKind;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ArgumentRepr {
+    Qubit { register: String, index: i32 },
+    Register { register: String },
+}
+
+impl From<Argument> for ArgumentRepr {
+    fn from(arg: Argument) -> ArgumentRepr {
+        match arg {
+            Argument::Qubit(register, index) => ArgumentRepr::Qubit { register, index },
+            Argument::Register(register) => ArgumentRepr::Register { register },
+        }
+    }
+}
+
+impl From<ArgumentRepr> for Argument {
+    fn from(repr: ArgumentRepr) -> Argument {
+        match repr {
+            ArgumentRepr::Qubit { register, index } => Argument::Qubit(register, index),
+            ArgumentRepr::Register { register } => Argument::Register(register),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AstNodeRepr {
+    QReg {
+        name: String,
+        size: i32,
+    },
+    CReg {
+        name: String,
+        size: i32,
+    },
+    Barrier {
+        qubits: Vec<Argument>,
+    },
+    Reset {
+        qubit: Argument,
+    },
+    Measure {
+        qubit: Argument,
+        bit: Argument,
+    },
+    U {
+        qubit: Argument,
+        params: [String; 3],
+    },
+    CX {
+        control: Argument,
+        target: Argument,
+    },
+    ApplyGate {
+        name: String,
+        qubits: Vec<Argument>,
+        params: Vec<String>,
+    },
+    Opaque {
+        name: String,
+        qubits: Vec<Argument>,
+        params: Vec<String>,
+    },
+    Gate {
+        name: String,
+        qubits: Vec<String>,
+        params: Vec<String>,
+        body: Vec<AstNode>,
+    },
+    If {
+        register: String,
+        value: i32,
+        statement: Box<AstNode>,
+    },
+}
+
+impl From<AstNode> for AstNodeRepr {
+    fn from(node: AstNode) -> AstNodeRepr {
+        match node {
+            AstNode::QReg(name, size) => AstNodeRepr::QReg { name, size },
+            AstNode::CReg(name, size) => AstNodeRepr::CReg { name, size },
+            AstNode::Barrier(qubits) => AstNodeRepr::Barrier { qubits },
+            AstNode::Reset(qubit) => AstNodeRepr::Reset { qubit },
+            AstNode::Measure(qubit, bit) => AstNodeRepr::Measure { qubit, bit },
+            AstNode::U(qubit, params) => AstNodeRepr::U { qubit, params },
+            AstNode::CX(control, target) => AstNodeRepr::CX { control, target },
+            AstNode::ApplyGate(name, qubits, params) => AstNodeRepr::ApplyGate {
+                name,
+                qubits,
+                params,
+            },
+            AstNode::Opaque(name, qubits, params) => AstNodeRepr::Opaque {
+                name,
+                qubits,
+                params,
+            },
+            AstNode::Gate(name, qubits, params, body) => AstNodeRepr::Gate {
+                name,
+                qubits,
+                params,
+                body,
+            },
+            AstNode::If(register, value, statement) => AstNodeRepr::If {
+                register,
+                value,
+                statement,
+            },
+        }
+    }
+}
+
+impl From<AstNodeRepr> for AstNode {
+    fn from(repr: AstNodeRepr) -> AstNode {
+        match repr {
+            AstNodeRepr::QReg { name, size } => AstNode::QReg(name, size),
+            AstNodeRepr::CReg { name, size } => AstNode::CReg(name, size),
+            AstNodeRepr::Barrier { qubits } => AstNode::Barrier(qubits),
+            AstNodeRepr::Reset { qubit } => AstNode::Reset(qubit),
+            AstNodeRepr::Measure { qubit, bit } => AstNode::Measure(qubit, bit),
+            AstNodeRepr::U { qubit, params } => AstNode::U(qubit, params),
+            AstNodeRepr::CX { control, target } => AstNode::CX(control, target),
+            AstNodeRepr::ApplyGate {
+                name,
+                qubits,
+                params,
+            } => AstNode::ApplyGate(name, qubits, params),
+            AstNodeRepr::Opaque {
+                name,
+                qubits,
+                params,
+            } => AstNode::Opaque(name, qubits, params),
+            AstNodeRepr::Gate {
+                name,
+                qubits,
+                params,
+                body,
+            } => AstNode::Gate(name, qubits, params, body),
+            AstNodeRepr::If {
+                register,
+                value,
+                statement,
+            } => AstNode::If(register, value, statement),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ErrorRepr {
+    MissingSemicolon,
+    UnsupportedVersion,
+    SourceError,
+    MissingReal,
+    MissingInt,
+    MissingIdentifier,
+    MissingVersion,
+    IoError { kind: String },
+    IncludeError { file: String },
+    UndefinedQReg { name: String },
+    UndefinedCReg { name: String },
+    UndefinedGate { name: String },
+    Redefinition { name: String },
+    IndexOutOfRange { name: String, index: i32 },
+    RegisterSizeMismatch { name: String },
+    WrongArgumentCount { name: String },
+    WrongParameterCount { name: String },
+    InvalidExpression { expression: String },
+    UndefinedParameter { name: String },
+    InvalidIdentifier { name: String },
+    UndefinedGateArgument { name: String },
+    IndexedGateArgument { name: String },
+    DuplicateArgument { name: String },
+    Unsupported { construct: String },
+}
+
+/// The name of an IO error kind in JSON. Kinds that aren't listed are written,
+/// and so read back, as `"Other"`.
+fn kind_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::NotFound => "NotFound",
+        ErrorKind::PermissionDenied => "PermissionDenied",
+        ErrorKind::ConnectionRefused => "ConnectionRefused",
+        ErrorKind::ConnectionReset => "ConnectionReset",
+        ErrorKind::ConnectionAborted => "ConnectionAborted",
+        ErrorKind::NotConnected => "NotConnected",
+        ErrorKind::AddrInUse => "AddrInUse",
+        ErrorKind::AddrNotAvailable => "AddrNotAvailable",
+        ErrorKind::BrokenPipe => "BrokenPipe",
+        ErrorKind::AlreadyExists => "AlreadyExists",
+        ErrorKind::WouldBlock => "WouldBlock",
+        ErrorKind::InvalidInput => "InvalidInput",
+        ErrorKind::InvalidData => "InvalidData",
+        ErrorKind::TimedOut => "TimedOut",
+        ErrorKind::WriteZero => "WriteZero",
+        ErrorKind::Interrupted => "Interrupted",
+        ErrorKind::UnexpectedEof => "UnexpectedEof",
+        _ => "Other",
+    }
+}
+
+/// The IO error kind with a name from `kind_name`, or `Other` for any other name.
+fn kind_from_name(name: &str) -> ErrorKind {
+    match name {
+        "NotFound" => ErrorKind::NotFound,
+        "PermissionDenied" => ErrorKind::PermissionDenied,
+        "ConnectionRefused" => ErrorKind::ConnectionRefused,
+        "ConnectionReset" => ErrorKind::ConnectionReset,
+        "ConnectionAborted" => ErrorKind::ConnectionAborted,
+        "NotConnected" => ErrorKind::NotConnected,
+        "AddrInUse" => ErrorKind::AddrInUse,
+        "AddrNotAvailable" => ErrorKind::AddrNotAvailable,
+        "BrokenPipe" => ErrorKind::BrokenPipe,
+        "AlreadyExists" => ErrorKind::AlreadyExists,
+        "WouldBlock" => ErrorKind::WouldBlock,
+        "InvalidInput" => ErrorKind::InvalidInput,
+        "InvalidData" => ErrorKind::InvalidData,
+        "TimedOut" => ErrorKind::TimedOut,
+        "WriteZero" => ErrorKind::WriteZero,
+        "Interrupted" => ErrorKind::Interrupted,
+        "UnexpectedEof" => ErrorKind::UnexpectedEof,
+        _ => ErrorKind::Other,
+    }
+}
+
+impl From<Error> for ErrorRepr {
+    fn from(error: Error) -> ErrorRepr {
+        match error {
+            Error::MissingSemicolon => ErrorRepr::MissingSemicolon,
+            Error::UnsupportedVersion => ErrorRepr::UnsupportedVersion,
+            Error::SourceError => ErrorRepr::SourceError,
+            Error::MissingReal => ErrorRepr::MissingReal,
+            Error::MissingInt => ErrorRepr::MissingInt,
+            Error::MissingIdentifier => ErrorRepr::MissingIdentifier,
+            Error::MissingVersion => ErrorRepr::MissingVersion,
+            Error::IoError(kind) => ErrorRepr::IoError {
+                kind: kind_name(kind).to_string(),
+            },
+            Error::IncludeError(file) => ErrorRepr::IncludeError { file },
+            Error::UndefinedQReg(name) => ErrorRepr::UndefinedQReg { name },
+            Error::UndefinedCReg(name) => ErrorRepr::UndefinedCReg { name },
+            Error::UndefinedGate(name) => ErrorRepr::UndefinedGate { name },
+            Error::Redefinition(name) => ErrorRepr::Redefinition { name },
+            Error::IndexOutOfRange(name, index) => ErrorRepr::IndexOutOfRange { name, index },
+            Error::RegisterSizeMismatch(name) => ErrorRepr::RegisterSizeMismatch { name },
+            Error::WrongArgumentCount(name) => ErrorRepr::WrongArgumentCount { name },
+            Error::WrongParameterCount(name) => ErrorRepr::WrongParameterCount { name },
+            Error::InvalidExpression(expression) => ErrorRepr::InvalidExpression { expression },
+            Error::UndefinedParameter(name) => ErrorRepr::UndefinedParameter { name },
+            Error::InvalidIdentifier(name) => ErrorRepr::InvalidIdentifier { name },
+            Error::UndefinedGateArgument(name) => ErrorRepr::UndefinedGateArgument { name },
+            Error::IndexedGateArgument(name) => ErrorRepr::IndexedGateArgument { name },
+            Error::DuplicateArgument(name) => ErrorRepr::DuplicateArgument { name },
+            Error::Unsupported(construct) => ErrorRepr::Unsupported { construct },
+        }
+    }
+}
+
+impl From<ErrorRepr> for Error {
+    fn from(repr: ErrorRepr) -> Error {
+        match repr {
+            ErrorRepr::MissingSemicolon => Error::MissingSemicolon,
+            ErrorRepr::UnsupportedVersion => Error::UnsupportedVersion,
+            ErrorRepr::SourceError => Error::SourceError,
+            ErrorRepr::MissingReal => Error::MissingReal,
+            ErrorRepr::MissingInt => Error::MissingInt,
+            ErrorRepr::MissingIdentifier => Error::MissingIdentifier,
+            ErrorRepr::MissingVersion => Error::MissingVersion,
+            ErrorRepr::IoError { kind } => Error::IoError(kind_from_name(&kind)),
+            ErrorRepr::IncludeError { file } => Error::IncludeError(file),
+            ErrorRepr::UndefinedQReg { name } => Error::UndefinedQReg(name),
+            ErrorRepr::UndefinedCReg { name } => Error::UndefinedCReg(name),
+            ErrorRepr::UndefinedGate { name } => Error::UndefinedGate(name),
+            ErrorRepr::Redefinition { name } => Error::Redefinition(name),
+            ErrorRepr::IndexOutOfRange { name, index } => Error::IndexOutOfRange(name, index),
+            ErrorRepr::RegisterSizeMismatch { name } => Error::RegisterSizeMismatch(name),
+            ErrorRepr::WrongArgumentCount { name } => Error::WrongArgumentCount(name),
+            ErrorRepr::WrongParameterCount { name } => Error::WrongParameterCount(name),
+            ErrorRepr::InvalidExpression { expression } => Error::InvalidExpression(expression),
+            ErrorRepr::UndefinedParameter { name } => Error::UndefinedParameter(name),
+            ErrorRepr::InvalidIdentifier { name } => Error::InvalidIdentifier(name),
+            ErrorRepr::UndefinedGateArgument { name } => Error::UndefinedGateArgument(name),
+            ErrorRepr::IndexedGateArgument { name } => Error::IndexedGateArgument(name),
+            ErrorRepr::DuplicateArgument { name } => Error::DuplicateArgument(name),
+            ErrorRepr::Unsupported { construct } => Error::Unsupported(construct),
+        }
+    }
+}
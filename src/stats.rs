@@ -0,0 +1,287 @@
+//! Resource usage of a program: operation counts, depth and width.
+
+use circuit::{Circuit, Instruction, Operation};
+use dag::{Dag, NodeIndex};
+use error::Error;
+use program::Program;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::result;
+
+type Result<T> = result::Result<T, Error>;
+
+/// Statistics about the resources a program uses.
+///
+/// Operations are counted by name: the name of a gate, or `measure`, `reset`
+/// or `barrier`. Barriers are left out of the totals and the depth, as they
+/// don't run anything. The counts are given for the program as written, and
+/// with every gate inlined into `U`, `CX` and opaque gates.
+///
+/// The depth is the number of operations on the longest path through the
+/// program, where an operation follows the ones before it on its qubits and
+/// bits. Conditions use every bit of their register.
+///
+/// The struct implements `Display`, giving a summary:
+///
+/// ```rust
+/// extern crate qasm;
+/// use qasm::Stats;
+/// use std::env;
+///
+/// let source = r#"
+/// OPENQASM 2.0;
+/// include "tests/source/qelib1.inc";
+/// qreg q[3];
+/// creg c[2];
+/// h q[0];
+/// cx q[0], q[1];
+/// x q[2];
+/// measure q[1] -> c[1];
+/// "#;
+///
+/// let program = qasm::parse_str(source, &env::current_dir().unwrap()).unwrap();
+/// let stats = Stats::from_program(&program).unwrap();
+///
+/// assert_eq!(stats.operations, 4);
+/// assert_eq!(stats.counts["cx"], 1);
+/// assert_eq!(stats.inlined_counts["U"], 2);
+/// assert_eq!(stats.depth, 3);
+/// assert_eq!(stats.two_qubit_depth, 1);
+/// assert_eq!(stats.used_clbits, 1);
+/// assert_eq!(
+///     stats.to_string(),
+///     "qubits: 3 (3 used)
+/// clbits: 2 (1 used)
+/// operations: 4 (4 inlined)
+/// depth: 3 (1 counting only two-qubit gates)
+/// critical path: h, cx, measure
+///
+/// name     count  inlined
+/// CX           0        1
+/// U            0        2
+/// cx           1        0
+/// h            1        0
+/// measure      1        1
+/// x            1        0
+/// "
+/// );
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stats {
+    /// The number of qubits in the quantum registers.
+    pub qubits: usize,
+    /// The number of qubits that an operation, other than a barrier, acts on.
+    pub used_qubits: usize,
+    /// The number of bits in the classical registers.
+    pub clbits: usize,
+    /// The number of bits that are measured into, or read by a condition.
+    pub used_clbits: usize,
+    /// The total number of operations.
+    pub operations: usize,
+    /// The number of operations with each name.
+    pub counts: BTreeMap<String, usize>,
+    /// The total number of operations, with gates inlined.
+    pub inlined_operations: usize,
+    /// The number of operations with each name, with gates inlined.
+    pub inlined_counts: BTreeMap<String, usize>,
+    /// The depth of the program.
+    pub depth: usize,
+    /// The depth, counting only gates on two or more qubits.
+    pub two_qubit_depth: usize,
+    /// The operations on a longest path through the program, in order.
+    pub critical_path: Vec<Instruction>,
+}
+
+impl Stats {
+    /// Flattens a program, then finds its statistics.
+    pub fn from_program(program: &Program) -> Result<Stats> {
+        Stats::from_circuit(&program.flatten()?)
+    }
+
+    /// Finds the statistics of a flattened program.
+    ///
+    /// This returns an `InvalidExpression` error if a parameter of an inlined
+    /// gate isn't a finite number.
+    pub fn from_circuit(circuit: &Circuit) -> Result<Stats> {
+        let inlined = circuit.inline(&|_| false)?;
+        let (operations, counts) = count(circuit);
+        let (inlined_operations, inlined_counts) = count(&inlined);
+
+        let mut qubits = BTreeSet::new();
+        let mut clbits = BTreeSet::new();
+        for instruction in &circuit.instructions {
+            match instruction.operation {
+                Operation::Gate(_, _, ref targets) => qubits.extend(targets.iter().cloned()),
+                Operation::Measure(q, b) => {
+                    qubits.insert(q);
+                    clbits.insert(b);
+                }
+                Operation::Reset(q) => {
+                    qubits.insert(q);
+                }
+                Operation::Barrier(_) => {}
+            }
+            if let Some(ref condition) = instruction.condition {
+                let register = &condition.register;
+                clbits.extend(register.offset..register.offset + register.size);
+            }
+        }
+
+        let dag = Dag::from_circuit(circuit);
+        let (depth, path) = longest_path(&dag, |instruction| match instruction.operation {
+            Operation::Barrier(_) => 0,
+            _ => 1,
+        });
+        let (two_qubit_depth, _) = longest_path(&dag, |instruction| match instruction.operation {
+            Operation::Gate(_, _, ref targets) if targets.len() >= 2 => 1,
+            _ => 0,
+        });
+        let critical_path = path
+            .into_iter()
+            .map(|node| dag.instruction(node))
+            .filter(|instruction| !matches!(instruction.operation, Operation::Barrier(_)))
+            .cloned()
+            .collect();
+
+        Ok(Stats {
+            qubits: circuit.num_qubits(),
+            used_qubits: qubits.len(),
+            clbits: circuit.num_clbits(),
+            used_clbits: clbits.len(),
+            operations,
+            counts,
+            inlined_operations,
+            inlined_counts,
+            depth,
+            two_qubit_depth,
+            critical_path,
+        })
+    }
+}
+
+/// The name an operation is counted under.
+fn name(operation: &Operation) -> &str {
+    match *operation {
+        Operation::Gate(ref name, ..) => name,
+        Operation::Measure(..) => "measure",
+        Operation::Reset(_) => "reset",
+        Operation::Barrier(_) => "barrier",
+    }
+}
+
+/// The total number of operations other than barriers, and the number with
+/// each name.
+fn count(circuit: &Circuit) -> (usize, BTreeMap<String, usize>) {
+    let mut counts = BTreeMap::new();
+    for instruction in &circuit.instructions {
+        *counts
+            .entry(name(&instruction.operation).to_string())
+            .or_insert(0) += 1;
+    }
+    let barriers = counts.get("barrier").cloned().unwrap_or(0);
+    (circuit.instructions.len() - barriers, counts)
+}
+
+/// The weight of the heaviest path through the graph, where each node has
+/// a weight, and the nodes on it.
+pub(crate) fn longest_path<F>(dag: &Dag, weight: F) -> (usize, Vec<NodeIndex>)
+where
+    F: Fn(&Instruction) -> usize,
+{
+    // The heaviest path ending at each node, and the node before it on the path
+    let mut paths: HashMap<NodeIndex, (usize, Option<NodeIndex>)> = HashMap::new();
+    let mut end: Option<(usize, NodeIndex)> = None;
+    for node in dag.topological() {
+        let before = dag
+            .predecessors(node)
+            .into_iter()
+            .map(|p| (paths[&p].0, p))
+            .max_by_key(|&(total, p)| (total, ::std::cmp::Reverse(p)));
+        let total = before.map_or(0, |b| b.0) + weight(dag.instruction(node));
+        paths.insert(node, (total, before.map(|b| b.1)));
+        if end.is_none_or(|(longest, _)| total > longest) {
+            end = Some((total, node));
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut node = end.map(|e| e.1);
+    while let Some(n) = node {
+        path.push(n);
+        node = paths[&n].1;
+    }
+    path.reverse();
+    (end.map_or(0, |e| e.0), path)
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "qubits: {} ({} used)", self.qubits, self.used_qubits)?;
+        writeln!(f, "clbits: {} ({} used)", self.clbits, self.used_clbits)?;
+        writeln!(
+            f,
+            "operations: {} ({} inlined)",
+            self.operations, self.inlined_operations
+        )?;
+        writeln!(
+            f,
+            "depth: {} ({} counting only two-qubit gates)",
+            self.depth, self.two_qubit_depth
+        )?;
+        let path: Vec<_> = self
+            .critical_path
+            .iter()
+            .map(|i| name(&i.operation))
+            .collect();
+        writeln!(f, "critical path: {}", path.join(", "))?;
+
+        let names: BTreeSet<_> = self
+            .counts
+            .keys()
+            .chain(self.inlined_counts.keys())
+            .collect();
+        let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(4);
+        writeln!(f)?;
+        writeln!(f, "{:3$}  {:>5}  {:>7}", "name", "count", "inlined", width)?;
+        for name in names {
+            let get = |counts: &BTreeMap<String, usize>| counts.get(name).cloned().unwrap_or(0);
+            writeln!(
+                f,
+                "{:3$}  {:>5}  {:>7}",
+                name,
+                get(&self.counts),
+                get(&self.inlined_counts),
+                width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn stats_test() {
+    let source = "OPENQASM 2.0;
+        qreg q[3];
+        creg c[2];
+        gate g a, b { U(0, 0, 0) a; CX a, b; CX b, a; }
+        g q[0], q[1];
+        barrier q;
+        U(0, 0, 0) q[2];
+        measure q[1] -> c[0];
+        if(c==1) CX q[2], q[0];";
+    let program = ::parse_str(source, ::std::path::Path::new("")).unwrap();
+    let stats = Stats::from_program(&program).unwrap();
+
+    assert_eq!((stats.used_qubits, stats.used_clbits), (3, 2));
+    assert_eq!(stats.operations, 4);
+    assert_eq!(stats.counts["barrier"], 1);
+    assert_eq!(stats.inlined_operations, 6);
+    assert_eq!(stats.inlined_counts["CX"], 3);
+    assert_eq!((stats.depth, stats.two_qubit_depth), (3, 2));
+    let path: Vec<_> = stats
+        .critical_path
+        .iter()
+        .map(|i| name(&i.operation))
+        .collect();
+    assert_eq!(path, vec!["g", "U", "CX"]);
+}
@@ -0,0 +1,190 @@
+//! Strict checks, which follow the OpenQASM 2.0 specification exactly.
+//! The parser accepts some programs that the specification doesn't,
+//! these checks reject them.
+
+use ast::{Argument, AstNode};
+use error::Error;
+use expr::Expr;
+use std::collections::HashMap;
+use std::result;
+use std::slice;
+
+type Result<T> = result::Result<T, Error>;
+
+/// The number of qubits and the number of parameters of each gate declared so far.
+type Gates<'a> = HashMap<&'a str, (usize, usize)>;
+
+/// Checks a list of nodes in order, as gates must be declared before they are used.
+pub fn check(nodes: &[AstNode]) -> Result<()> {
+    let mut gates = Gates::new();
+
+    for node in nodes {
+        match *node {
+            AstNode::QReg(ref id, _) | AstNode::CReg(ref id, _) => check_identifier(id)?,
+            AstNode::Gate(ref id, ref qubits, ref params, ref body) => {
+                check_declaration(id, qubits, params)?;
+                for node in body {
+                    check_body_node(&gates, qubits, params, node)?;
+                }
+                gates.insert(id, (qubits.len(), params.len()));
+            }
+            AstNode::Opaque(ref id, ref args, ref params) => {
+                let qubits = args
+                    .iter()
+                    .map(|arg| match *arg {
+                        Argument::Register(ref id) => Ok(id.clone()),
+                        Argument::Qubit(ref id, _) => Err(Error::IndexedGateArgument(id.clone())),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                check_declaration(id, &qubits, params)?;
+                gates.insert(id, (qubits.len(), params.len()));
+            }
+            _ => check_statement(&gates, node)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Identifiers must start with a lower case letter, followed by letters, digits and underscores.
+fn check_identifier(id: &str) -> Result<()> {
+    let mut chars = id.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidIdentifier(id.to_string()))
+    }
+}
+
+fn check_distinct<T: PartialEq>(items: &[T], name: fn(&T) -> String) -> Result<()> {
+    for (i, item) in items.iter().enumerate() {
+        if items[..i].contains(item) {
+            return Err(Error::DuplicateArgument(name(item)));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_declaration(id: &str, qubits: &[String], params: &[String]) -> Result<()> {
+    check_identifier(id)?;
+    for name in qubits.iter().chain(params) {
+        check_identifier(name)?;
+    }
+    check_distinct(qubits, String::clone)?;
+    check_distinct(params, String::clone)
+}
+
+fn check_signature(gates: &Gates, id: &str, num_args: usize, num_params: usize) -> Result<()> {
+    let &(qubits, params) = gates
+        .get(id)
+        .ok_or_else(|| Error::UndefinedGate(id.to_string()))?;
+
+    if qubits != num_args {
+        return Err(Error::WrongArgumentCount(id.to_string()));
+    }
+    if params != num_params {
+        return Err(Error::WrongParameterCount(id.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Expressions must be well formed, and only use the given parameters.
+fn check_expressions(exprs: &[String], params: &[String]) -> Result<()> {
+    for expr in exprs {
+        let expr = Expr::parse(expr)?;
+        if let Some(id) = expr.ids().into_iter().find(|id| !params.iter().any(|p| p == id)) {
+            return Err(Error::UndefinedParameter(id.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+fn argument_name(arg: &Argument) -> String {
+    match *arg {
+        Argument::Register(ref id) => id.clone(),
+        Argument::Qubit(ref id, index) => format!("{}[{}]", id, index),
+    }
+}
+
+/// A statement outside of a gate definition.
+fn check_statement(gates: &Gates, node: &AstNode) -> Result<()> {
+    match *node {
+        AstNode::U(_, ref params) => check_expressions(params, &[]),
+        AstNode::CX(ref control, ref target) => {
+            check_distinct(&[control.clone(), target.clone()], argument_name)
+        }
+        AstNode::ApplyGate(ref id, ref args, ref params) => {
+            check_signature(gates, id, args.len(), params.len())?;
+            check_expressions(params, &[])?;
+            check_distinct(args, argument_name)
+        }
+        AstNode::If(_, _, ref node) => check_statement(gates, node),
+        _ => Ok(()),
+    }
+}
+
+/// A statement inside a gate definition, where the arguments
+/// must be the unindexed qubit arguments of the gate.
+fn check_body_node(gates: &Gates, qubits: &[String], params: &[String], node: &AstNode) -> Result<()> {
+    if let AstNode::ApplyGate(ref id, ref args, ref exprs) = *node {
+        check_signature(gates, id, args.len(), exprs.len())?;
+    }
+    check_body_arguments(qubits, params, node)
+}
+
+/// Checks the body of a gate definition on its own, apart from the gates that
+/// it uses, as for rules that can use gates that are declared later.
+pub(crate) fn check_body(qubits: &[String], params: &[String], body: &[AstNode]) -> Result<()> {
+    check_distinct(qubits, String::clone)?;
+    check_distinct(params, String::clone)?;
+    for node in body {
+        check_body_arguments(qubits, params, node)?;
+    }
+
+    Ok(())
+}
+
+fn check_body_arguments(qubits: &[String], params: &[String], node: &AstNode) -> Result<()> {
+    let check_args = |args: &[Argument]| -> Result<()> {
+        for arg in args {
+            match *arg {
+                Argument::Register(ref id) if qubits.contains(id) => {}
+                Argument::Register(ref id) => return Err(Error::UndefinedGateArgument(id.clone())),
+                Argument::Qubit(ref id, _) => return Err(Error::IndexedGateArgument(id.clone())),
+            }
+        }
+        Ok(())
+    };
+
+    match *node {
+        AstNode::U(ref arg, ref exprs) => {
+            check_args(slice::from_ref(arg))?;
+            check_expressions(exprs, params)
+        }
+        AstNode::CX(ref control, ref target) => {
+            let args = [control.clone(), target.clone()];
+            check_args(&args)?;
+            check_distinct(&args, argument_name)
+        }
+        AstNode::ApplyGate(_, ref args, ref exprs) => {
+            check_args(args)?;
+            check_distinct(args, argument_name)?;
+            check_expressions(exprs, params)
+        }
+        AstNode::Barrier(ref args) => check_args(args),
+        _ => Err(Error::SourceError),
+    }
+}
+
+#[test]
+fn check_identifier_test() {
+    assert!(check_identifier("q_1").is_ok());
+    assert_eq!(check_identifier("Q"), Err(Error::InvalidIdentifier("Q".to_string())));
+    assert_eq!(check_identifier("_q"), Err(Error::InvalidIdentifier("_q".to_string())));
+}
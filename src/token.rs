@@ -1,6 +1,15 @@
 /// Tokens returned from lexing. Represents a small amount of the source code.
+///
+/// The type parameter is the storage used for identifiers. It defaults to an owned
+/// `String`, which is what [lex](fn.lex.html) returns. The [Lexer](struct.Lexer.html)
+/// itself produces `Token<&str>`, where identifiers borrow from the source string.
 #[derive(Debug, PartialEq, Clone)]
-pub enum Token {
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", content = "value")
+)]
+pub enum Token<S = String> {
     /// This token represents an illegal token. This is usually an error in the source code.
     Illegal,
     /// This token represents the end of a file.
@@ -8,11 +17,11 @@ pub enum Token {
 
     // Literals
     /// Represents a Real Number
-    Real(f32),
+    Real(f64),
     /// Represents an integer
     NNInteger(i32),
     /// Represents an identifier
-    Id(String),
+    Id(S),
 
     // Other Tokens
     /// The OPENQASM statement
@@ -37,6 +46,30 @@ pub enum Token {
     Arrow,
     /// An Equals `==`
     Equals,
+    /// An Assignment `=`, used by OpenQASM 3
+    Assign,
+    /// A Not Equals `!=`
+    NotEquals,
+    /// A Less Than `<`
+    Less,
+    /// A Less Than Or Equals `<=`
+    LessEquals,
+    /// A Greater Than `>`
+    Greater,
+    /// A Greater Than Or Equals `>=`
+    GreaterEquals,
+    /// A Logical Not `!`
+    Not,
+    /// A Logical And `&&`
+    And,
+    /// A Logical Or `||`
+    Or,
+    /// An At `@`, which separates gate modifiers
+    At,
+    /// A Colon `:`, used in ranges
+    Colon,
+    /// A string literal, without the quotes
+    Str(S),
 
     // Mathematical Expressions
     /// Plus Sign `+`
@@ -85,16 +118,150 @@ pub enum Token {
     Opaque,
     /// Reserved word, `if`
     If,
+
+    // OpenQASM 3 Reserved Words, only returned by `Lexer::new_qasm3`
+    /// Reserved word, `qubit`
+    Qubit,
+    /// Reserved word, `bit`
+    Bit,
+    /// Reserved word, `bool`
+    Bool,
+    /// Reserved word, `int`
+    Int,
+    /// Reserved word, `uint`
+    Uint,
+    /// Reserved word, `float`
+    Float,
+    /// Reserved word, `angle`
+    Angle,
+    /// Reserved word, `const`
+    Const,
+    /// Reserved word, `input`
+    Input,
+    /// Reserved word, `ctrl`
+    Ctrl,
+    /// Reserved word, `negctrl`
+    NegCtrl,
+    /// Reserved word, `inv`
+    Inv,
+    /// Reserved word, `pow`
+    Pow,
+    /// Reserved word, `for`
+    For,
+    /// Reserved word, `in`
+    In,
+    /// Reserved word, `while`
+    While,
+    /// Reserved word, `else`
+    Else,
+    /// Reserved word, `true`
+    True,
+    /// Reserved word, `false`
+    False,
 }
 
-impl Default for Token {
+impl<S> Default for Token<S> {
     /// Choose the Illegal token as default
-    fn default() -> Token {
+    fn default() -> Token<S> {
         Token::Illegal
     }
 }
 
-pub fn lookup_ident(ident: &str) -> Token {
+impl<S> Token<S> {
+    /// Convert the identifier and string storage of this token, leaving all other tokens unchanged.
+    fn map_id<T, F: FnOnce(S) -> T>(self, f: F) -> Token<T> {
+        match self {
+            Token::Illegal => Token::Illegal,
+            Token::EndOfFile => Token::EndOfFile,
+            Token::Real(n) => Token::Real(n),
+            Token::NNInteger(n) => Token::NNInteger(n),
+            Token::Id(s) => Token::Id(f(s)),
+            Token::OpenQASM => Token::OpenQASM,
+            Token::Semicolon => Token::Semicolon,
+            Token::Comma => Token::Comma,
+            Token::LParen => Token::LParen,
+            Token::LSParen => Token::LSParen,
+            Token::LCParen => Token::LCParen,
+            Token::RParen => Token::RParen,
+            Token::RSParen => Token::RSParen,
+            Token::RCParen => Token::RCParen,
+            Token::Arrow => Token::Arrow,
+            Token::Equals => Token::Equals,
+            Token::Assign => Token::Assign,
+            Token::NotEquals => Token::NotEquals,
+            Token::Less => Token::Less,
+            Token::LessEquals => Token::LessEquals,
+            Token::Greater => Token::Greater,
+            Token::GreaterEquals => Token::GreaterEquals,
+            Token::Not => Token::Not,
+            Token::And => Token::And,
+            Token::Or => Token::Or,
+            Token::At => Token::At,
+            Token::Colon => Token::Colon,
+            Token::Str(s) => Token::Str(f(s)),
+            Token::Plus => Token::Plus,
+            Token::Minus => Token::Minus,
+            Token::Times => Token::Times,
+            Token::Divide => Token::Divide,
+            Token::Power => Token::Power,
+            Token::Sin => Token::Sin,
+            Token::Cos => Token::Cos,
+            Token::Tan => Token::Tan,
+            Token::Exp => Token::Exp,
+            Token::Ln => Token::Ln,
+            Token::Sqrt => Token::Sqrt,
+            Token::Pi => Token::Pi,
+            Token::QReg => Token::QReg,
+            Token::CReg => Token::CReg,
+            Token::Barrier => Token::Barrier,
+            Token::Gate => Token::Gate,
+            Token::Measure => Token::Measure,
+            Token::Reset => Token::Reset,
+            Token::Include => Token::Include,
+            Token::Opaque => Token::Opaque,
+            Token::If => Token::If,
+            Token::Qubit => Token::Qubit,
+            Token::Bit => Token::Bit,
+            Token::Bool => Token::Bool,
+            Token::Int => Token::Int,
+            Token::Uint => Token::Uint,
+            Token::Float => Token::Float,
+            Token::Angle => Token::Angle,
+            Token::Const => Token::Const,
+            Token::Input => Token::Input,
+            Token::Ctrl => Token::Ctrl,
+            Token::NegCtrl => Token::NegCtrl,
+            Token::Inv => Token::Inv,
+            Token::Pow => Token::Pow,
+            Token::For => Token::For,
+            Token::In => Token::In,
+            Token::While => Token::While,
+            Token::Else => Token::Else,
+            Token::True => Token::True,
+            Token::False => Token::False,
+        }
+    }
+}
+
+impl Token<&str> {
+    /// Copy any borrowed identifier into an owned `Token`.
+    pub fn into_owned(self) -> Token {
+        self.map_id(String::from)
+    }
+}
+
+impl Token {
+    /// Borrow this token, so that it can be parsed without cloning identifiers.
+    pub fn as_borrowed(&self) -> Token<&str> {
+        match *self {
+            Token::Id(ref s) => Token::Id(s.as_str()),
+            Token::Str(ref s) => Token::Str(s.as_str()),
+            ref other => other.clone().map_id(|_| unreachable!()),
+        }
+    }
+}
+
+pub fn lookup_ident(ident: &str) -> Token<&str> {
     match ident {
         "qreg" => Token::QReg,
         "creg" => Token::CReg,
@@ -113,11 +280,50 @@ pub fn lookup_ident(ident: &str) -> Token {
         "sqrt" => Token::Sqrt,
         "pi" => Token::Pi,
         "OPENQASM" => Token::OpenQASM,
-        _ => Token::Id(ident.into()),
+        _ => Token::Id(ident),
+    }
+}
+
+/// Looks up reserved words in OpenQASM 3, which has all the words of OpenQASM 2.0 and more.
+pub fn lookup_ident_qasm3(ident: &str) -> Token<&str> {
+    match ident {
+        "qubit" => Token::Qubit,
+        "bit" => Token::Bit,
+        "bool" => Token::Bool,
+        "int" => Token::Int,
+        "uint" => Token::Uint,
+        "float" => Token::Float,
+        "angle" => Token::Angle,
+        "const" => Token::Const,
+        "input" => Token::Input,
+        "ctrl" => Token::Ctrl,
+        "negctrl" => Token::NegCtrl,
+        "inv" => Token::Inv,
+        "pow" => Token::Pow,
+        "for" => Token::For,
+        "in" => Token::In,
+        "while" => Token::While,
+        "else" => Token::Else,
+        "true" => Token::True,
+        "false" => Token::False,
+        "log" => Token::Ln,
+        "ln" => Token::Id(ident),
+        _ => lookup_ident(ident),
     }
 }
 
 #[test]
 fn lookup_ident_test() {
     assert_eq!(lookup_ident("opaque"), Token::Opaque);
+    assert_eq!(lookup_ident("qubit"), Token::Id("qubit"));
+    assert_eq!(lookup_ident_qasm3("qubit"), Token::Qubit);
+    assert_eq!(lookup_ident_qasm3("log"), Token::Ln);
+    assert_eq!(lookup_ident_qasm3("ln"), Token::Id("ln"));
+}
+
+#[test]
+fn borrowed_token_test() {
+    let owned = Token::Id("q".to_string());
+    assert_eq!(owned.as_borrowed(), Token::Id("q"));
+    assert_eq!(owned.as_borrowed().into_owned(), owned);
 }
@@ -0,0 +1,7 @@
+// Barriers over several qubits and registers, at the top level and in gates
+OPENQASM 2.0;
+gate g a, b { barrier a, b; CX a, b; barrier a; }
+qreg q[2];
+qreg r[2];
+g q[0], r[1];
+barrier q[0], q[1], r;
@@ -0,0 +1,5 @@
+// Reals with exponents, and with no digits before the point
+OPENQASM 2.0;
+qreg q[1];
+U(1.5e-3, 2E+2, .5) q[0];
+U(1e10, 0.25e0, 3.) q[0];
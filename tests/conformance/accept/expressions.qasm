@@ -0,0 +1,5 @@
+// Every operator and function in parameters
+OPENQASM 2.0;
+gate g(x) a { U(sin(x)+cos(x)-tan(x), exp(x)*ln(x)/sqrt(x), x^2^-1) a; }
+qreg q[1];
+g((pi*2)/3) q[0];
@@ -0,0 +1,5 @@
+// Identifiers can contain upper case letters, digits and underscores after the first letter
+OPENQASM 2.0;
+gate my_Gate2 a_B, c9 { CX a_B, c9; }
+qreg q_Reg0[2];
+my_Gate2 q_Reg0[0], q_Reg0[1];
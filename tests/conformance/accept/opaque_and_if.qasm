@@ -0,0 +1,11 @@
+// Opaque gates, conditionals, resets and measurements of whole registers
+OPENQASM 2.0;
+opaque magic(alpha) a, b;
+opaque noop a;
+qreg q[2];
+creg c[2];
+magic(0.1) q[0], q[1];
+noop q;
+reset q;
+measure q -> c;
+if (c == 3) magic(pi) q[1], q[0];
@@ -0,0 +1,11 @@
+// The standard library, with broadcasting over registers
+OPENQASM 2.0;
+include "qelib1.inc";
+qreg q[3];
+qreg anc[3];
+creg c[3];
+h q;
+cx q, anc;
+ccx q[0], q[1], anc[2];
+cu3(pi, pi/2, 0) q[2], anc[0];
+measure anc -> c;
@@ -0,0 +1,6 @@
+// Unary minus at the start of parameters inside and outside gate bodies
+OPENQASM 2.0;
+gate g(theta, phi) a { U(-theta, -phi/2, -(theta+phi)) a; }
+qreg q[1];
+g(-pi, -0.5) q[0];
+U(-pi/2, --1, -2^2) q[0];
@@ -0,0 +1,3 @@
+// error: DuplicateArgument("a")
+OPENQASM 2.0;
+gate g a, a { }
@@ -0,0 +1,4 @@
+// error: DuplicateArgument("q[0]")
+OPENQASM 2.0;
+qreg q[1];
+CX q[0], q[0];
@@ -0,0 +1,4 @@
+// error: IndexOutOfRange("q", 2)
+OPENQASM 2.0;
+qreg q[2];
+U(0, 0, 0) q[2];
@@ -0,0 +1,3 @@
+// error: IndexedGateArgument("a")
+OPENQASM 2.0;
+gate g a { U(0, 0, 0) a[0]; }
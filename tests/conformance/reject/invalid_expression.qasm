@@ -0,0 +1,4 @@
+// error: InvalidExpression("1  2")
+OPENQASM 2.0;
+qreg q[1];
+U(1 2, 0, 0) q[0];
@@ -0,0 +1,5 @@
+// error: UndefinedCReg("r")
+OPENQASM 2.0;
+qreg q[1];
+qreg r[1];
+measure q[0] -> r[0];
@@ -0,0 +1,4 @@
+// error: MissingSemicolon
+OPENQASM 2.0;
+qreg q[1]
+creg c[1];
@@ -0,0 +1,2 @@
+// error: MissingVersion
+qreg q[1];
@@ -0,0 +1,4 @@
+// error: UndefinedParameter("theta")
+OPENQASM 2.0;
+qreg q[1];
+U(theta, 0, 0) q[0];
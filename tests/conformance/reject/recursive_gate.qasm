@@ -0,0 +1,3 @@
+// error: UndefinedGate("g")
+OPENQASM 2.0;
+gate g a { g a; }
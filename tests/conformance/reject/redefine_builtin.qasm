@@ -0,0 +1,3 @@
+// error: Redefinition("U")
+OPENQASM 2.0;
+gate U(a, b, c) q { }
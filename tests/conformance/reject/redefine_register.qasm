@@ -0,0 +1,4 @@
+// error: Redefinition("q")
+OPENQASM 2.0;
+qreg q[1];
+creg q[1];
@@ -0,0 +1,3 @@
+// error: UndefinedGateArgument("c")
+OPENQASM 2.0;
+gate g a, b { CX a, c; }
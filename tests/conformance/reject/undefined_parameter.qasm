@@ -0,0 +1,3 @@
+// error: UndefinedParameter("phi")
+OPENQASM 2.0;
+gate g(theta) a { U(theta, phi, 0) a; }
@@ -0,0 +1,4 @@
+// error: UndefinedQReg("r")
+OPENQASM 2.0;
+qreg q[1];
+reset r;
@@ -0,0 +1,3 @@
+// error: UnsupportedVersion
+OPENQASM 3.0;
+qreg q[1];
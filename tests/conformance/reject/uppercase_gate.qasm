@@ -0,0 +1,3 @@
+// error: InvalidIdentifier("H")
+OPENQASM 2.0;
+gate H a { U(pi/2, 0, pi) a; }
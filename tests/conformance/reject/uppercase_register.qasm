@@ -0,0 +1,4 @@
+// error: InvalidIdentifier("Q")
+OPENQASM 2.0;
+qreg Q[2];
+CX Q[0], Q[1];
@@ -0,0 +1,5 @@
+// error: WrongArgumentCount("g")
+OPENQASM 2.0;
+gate g a, b { CX a, b; }
+qreg q[3];
+g q[0], q[1], q[2];
@@ -0,0 +1,4 @@
+// error: WrongParameterCount("U")
+OPENQASM 2.0;
+qreg q[1];
+U(0, 0) q[0];
@@ -0,0 +1,31 @@
+OPENQASM 3;
+include "stdgates.inc";
+
+const uint n = 4;
+const angle[20] step = pi / 8;
+qubit[n] q;
+bit[n] c;
+int i = 0;
+
+gate crot(k) a, b {
+    ctrl @ pow(k) @ inv @ rz(pi / 2) a, b;
+    gphase(-k);
+}
+
+for uint j in [0:n - 1] {
+    h q[j];
+}
+for int j in [0:2:n - 2] {
+    crot(j * step) q[j], q[j + 1];
+}
+for j in {1, 3} {
+    negctrl(2) @ x q[0], q[1], q[j];
+}
+
+while (i < n && !(i == 3)) {
+    reset q[i];
+    i = i + 1;
+}
+
+barrier;
+c = measure q;
@@ -0,0 +1,30 @@
+/*
+ * Quantum teleportation, with the corrections applied in classical control flow
+ */
+OPENQASM 3.0;
+include "stdgates.inc";
+
+input angle[32] theta;
+qubit[3] q;
+bit[2] c;
+bit r;
+
+// Prepare the state to send
+ry(theta) q[0];
+
+// Bell pair between q[1] and q[2]
+h q[1];
+cx q[1], q[2];
+
+cx q[0], q[1];
+h q[0];
+c[0] = measure q[0];
+c[1] = measure q[1];
+
+if (c[0] == 1) {
+    z q[2];
+}
+if (c[1] == 1) x q[2]; else {
+    id q[2];
+}
+measure q[2] -> r;
@@ -0,0 +1,16 @@
+// Barriers over lists of qubits and registers, as exported by Qiskit
+OPENQASM 2.0;
+include "qelib1.inc";
+gate bell a, b
+{
+  h a;
+  barrier a, b;
+  cx a, b;
+}
+qreg q[2];
+qreg r[3];
+creg c[2];
+bell q[0], q[1];
+barrier q[0], q[1], r;
+barrier q;
+measure q -> c;
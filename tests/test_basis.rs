@@ -0,0 +1,37 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::basis::{self, Equivalences};
+use qasm::{Operation, Program};
+
+#[test]
+fn translates_examples() {
+    let bases: [&[&str]; 3] = [
+        &["rz", "sx", "x", "cx"],
+        &["u1", "u2", "u3", "cx"],
+        &["rx", "rz", "cz"],
+    ];
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+
+        for basis in &bases {
+            let translated = basis::translate(&circuit, basis, &Equivalences::default()).unwrap();
+            for instruction in &translated.instructions {
+                if let Operation::Gate(ref name, _, _) = instruction.operation {
+                    assert!(basis.contains(&name.as_str()), "{:?}: {}", path, name);
+                }
+            }
+            let reparsed = Program::from_ast(translated.to_ast())
+                .and_then(|p| p.flatten())
+                .unwrap();
+            assert_eq!(
+                reparsed.instructions.len(),
+                translated.instructions.len(),
+                "{:?}",
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,38 @@
+extern crate glob;
+extern crate qasm;
+extern crate serde_json;
+
+use glob::glob;
+
+#[test]
+fn emits_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let json = match qasm::cirq::emit(&circuit) {
+            Ok(json) => json,
+            Err(e) => panic!("Error emitting {:?}: {}", path, e),
+        };
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let operations: usize = value["moments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["operations"].as_array().unwrap().len())
+            .sum();
+        let measures = circuit
+            .instructions
+            .iter()
+            .filter(|i| matches!(i.operation, qasm::Operation::Measure(..)))
+            .count();
+        assert_eq!(value["cirq_type"], "Circuit");
+        assert!(operations >= measures, "{:?}", path);
+        assert_eq!(
+            json.matches("\"MeasurementGate\"").count(),
+            measures,
+            "{:?}",
+            path
+        );
+    }
+}
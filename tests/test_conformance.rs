@@ -0,0 +1,60 @@
+//! Runs the conformance corpus in `tests/conformance` in strict mode.
+//!
+//! Every program in `accept` must parse. Every program in `reject` must fail,
+//! with the error given on its first line, as `// error: <Debug of the Error>`.
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::parse_str_strict;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+// The standard library is included from the other examples
+const INCLUDE_DIR: &str = "tests/source";
+
+fn read(path: &Path) -> String {
+    let mut f = File::open(path).unwrap();
+    let mut contents = String::new();
+    f.read_to_string(&mut contents).expect("Couldn't Read File");
+    contents
+}
+
+#[test]
+fn accepts_conforming_programs() {
+    for entry in glob("tests/conformance/accept/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        if let Err(e) = parse_str_strict(&read(&path), Path::new(INCLUDE_DIR)) {
+            panic!("{:?} was rejected: {:?}", path, e);
+        }
+    }
+}
+
+#[test]
+fn rejects_nonconforming_programs() {
+    for entry in glob("tests/conformance/reject/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let source = read(&path);
+        let expected = source
+            .lines()
+            .next()
+            .and_then(|line| line.trim().strip_prefix("// error: "))
+            .unwrap_or_else(|| panic!("{:?} doesn't give the expected error", path));
+
+        match parse_str_strict(&source, Path::new(INCLUDE_DIR)) {
+            Ok(_) => panic!("{:?} was accepted", path),
+            Err(e) => assert_eq!(format!("{:?}", e), expected, "{:?}", path),
+        }
+    }
+}
+
+#[test]
+fn examples_conform() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        if let Err(e) = parse_str_strict(&read(&path), Path::new(INCLUDE_DIR)) {
+            panic!("{:?} was rejected: {:?}", path, e);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::{Dag, Program};
+
+#[test]
+fn round_trips_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let dag = Dag::from_circuit(&circuit);
+
+        assert_eq!(dag.len(), circuit.instructions.len(), "{:?}", path);
+        assert_eq!(dag.to_circuit(), circuit, "{:?}", path);
+
+        let program = Program::from_ast(dag.to_ast()).unwrap();
+        assert_eq!(program.flatten().unwrap(), circuit, "{:?}", path);
+    }
+}
@@ -0,0 +1,74 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::draw::{self, SvgOptions, TextOptions};
+
+#[test]
+fn draws_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let options = TextOptions {
+            unicode: false,
+            width: Some(60),
+        };
+        let diagram = draw::text(&circuit, &options).unwrap();
+
+        // Lines only go over the width when a single column doesn't fit
+        for line in diagram.lines() {
+            assert!(line.is_ascii(), "{:?}: {}", path, line);
+            assert!(
+                line.len() <= 60 || !line.contains('>'),
+                "{:?}: {}",
+                path,
+                line
+            );
+        }
+    }
+}
+
+#[test]
+fn draws_examples_as_svg() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        for &collapse in &[true, false] {
+            let options = SvgOptions {
+                collapse,
+                ..SvgOptions::default()
+            };
+            let svg = draw::svg(&circuit, &options).unwrap();
+
+            assert!(svg.starts_with("<?xml"), "{:?}", path);
+            assert!(svg.ends_with("</svg>\n"), "{:?}", path);
+            assert!(!svg.contains("NaN") && !svg.contains("inf"), "{:?}", path);
+        }
+    }
+}
+
+#[test]
+fn writes_examples_as_quantikz() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let latex = draw::quantikz(&circuit).unwrap();
+        let rows: Vec<_> = latex
+            .lines()
+            .skip(1)
+            .take_while(|l| !l.starts_with("\\end"))
+            .collect();
+
+        // Every row has the same number of cells
+        assert_eq!(
+            rows.len(),
+            circuit.num_qubits() + circuit.cregs.len(),
+            "{:?}",
+            path
+        );
+        let cells = rows[0].matches('&').count();
+        for row in &rows {
+            assert_eq!(row.matches('&').count(), cells, "{:?}: {}", path, row);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::{Estimate, Stats};
+
+#[test]
+fn estimates_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let program = qasm::parse_file(&path).unwrap();
+        let estimate = Estimate::from_program(&program).unwrap();
+        let stats = Stats::from_program(&program).unwrap();
+
+        let gates = stats.inlined_counts.get("U").cloned().unwrap_or(0)
+            + stats.inlined_counts.get("CX").cloned().unwrap_or(0);
+        assert_eq!(estimate.gates.len(), gates, "{:?}", path);
+        assert_eq!(
+            estimate.clifford + estimate.t_gates + estimate.arbitrary,
+            gates,
+            "{:?}",
+            path
+        );
+        assert!(estimate.t_depth <= estimate.t_count, "{:?}", path);
+        assert!(estimate.arbitrary <= estimate.rotations, "{:?}", path);
+    }
+}
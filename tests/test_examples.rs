@@ -2,38 +2,119 @@ extern crate glob;
 extern crate qasm;
 
 use glob::glob;
-use qasm::{lex, parse, process};
+use qasm::{lex, parse, parse_file, parse_reader, process, Error};
 use std::path::Path;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 
-fn test_parse(source: &str) {
-    let mut tokens = lex(source);
-
-    match parse(&mut tokens) {
-        Ok(_) => assert!(true),
-        Err(e) => {
-            println!("Error: {}", e);
-            println!("Source: {}", source);
-            assert!(false)
-        }
-    }
-}
-
 // Start a custom repl
 #[test]
 fn works_with_examples() {
     for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
         match entry {
             Ok(path) => {
-                let mut f = File::open(&path).unwrap();
-                let mut contents = String::new();
-                f.read_to_string(&mut contents).expect("Couldn't Read File");
-                contents = process(&contents, Path::new("tests/source"));
+                let program = parse_file(&path).and_then(|program| program.check());
 
-                test_parse(&contents)
+                if let Err(e) = program {
+                    panic!("Error in {:?}: {}", path, e);
+                }
             }
             Err(e) => println!("{:?}", e),
         }
     }
 }
+
+#[test]
+fn streams_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let mut f = File::open(&path).unwrap();
+        let mut contents = String::new();
+        f.read_to_string(&mut contents).expect("Couldn't Read File");
+        contents = process(&contents, Path::new("tests/source"));
+
+        let streamed: Result<Vec<_>, _> = parse_reader(contents.as_bytes()).unwrap().collect();
+        assert_eq!(streamed, parse(&lex(&contents)), "{:?}", path);
+    }
+}
+
+struct FailingReader<'a>(&'a [u8]);
+
+impl<'a> Read for FailingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::ConnectionReset, "closed"));
+        }
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn stream_reports_read_errors() {
+    let source = b"OPENQASM 2.0;\nqreg q[2];\nCX q[0], q[1];\n";
+    let mut statements = parse_reader(FailingReader(source)).unwrap();
+
+    assert!(statements.next().unwrap().is_ok());
+    assert!(statements.next().unwrap().is_ok());
+    assert_eq!(
+        statements.next(),
+        Some(Err(Error::IoError(io::ErrorKind::ConnectionReset)))
+    );
+    assert_eq!(statements.next(), None);
+}
+
+#[test]
+fn reports_missing_includes() {
+    let source = "OPENQASM 2.0;\ninclude \"missing.inc\";\n";
+    assert_eq!(
+        qasm::parse_str(source, Path::new("tests/source")),
+        Err(Error::IncludeError("missing.inc".to_string()))
+    );
+}
+
+#[test]
+fn parses_barrier_lists() {
+    use qasm::{Argument, AstNode};
+
+    let program = parse_file("tests/source/barriers.qasm").unwrap();
+    let barrier = AstNode::Barrier(vec![
+        Argument::Qubit("q".to_string(), 0),
+        Argument::Qubit("q".to_string(), 1),
+        Argument::Register("r".to_string()),
+    ]);
+    assert_eq!(program.statements()[1], barrier);
+
+    let body = &program.gate("bell").unwrap().body;
+    assert_eq!(
+        body[1],
+        AstNode::Barrier(vec![
+            Argument::Register("a".to_string()),
+            Argument::Register("b".to_string()),
+        ])
+    );
+}
+
+#[test]
+fn parses_builtin_gates() {
+    use qasm::{parse_tokens, Argument, AstNode, Lexer};
+
+    let ast = parse_tokens(Lexer::new("OPENQASM 2.0; qreg q[2]; U(0, pi, 1) q[0]; CX q[0], q[1];"));
+    let q = |i| Argument::Qubit("q".to_string(), i);
+    assert_eq!(
+        ast.unwrap()[1..],
+        [
+            AstNode::U(q(0), [" 0 ".to_string(), " pi ".to_string(), " 1 ".to_string()]),
+            AstNode::CX(q(0), q(1)),
+        ]
+    );
+
+    let wrong_params = parse_tokens(Lexer::new("OPENQASM 2.0; qreg q[1]; U(0, pi) q[0];"));
+    assert_eq!(wrong_params, Err(Error::WrongParameterCount("U".to_string())));
+
+    let wrong_args = parse_tokens(Lexer::new("OPENQASM 2.0; qreg q[3]; CX q[0], q[1], q[2];"));
+    assert_eq!(wrong_args, Err(Error::WrongArgumentCount("CX".to_string())));
+
+    let redefined = parse_tokens(Lexer::new("OPENQASM 2.0; gate CX a, b { }"));
+    assert_eq!(redefined, Err(Error::Redefinition("CX".to_string())));
+}
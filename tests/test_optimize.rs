@@ -0,0 +1,71 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::optimize::{self, ResynthesisOptions};
+use qasm::{Program, Stats};
+
+#[test]
+fn optimizes_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let optimized = optimize::peephole(&circuit);
+
+        assert!(
+            optimized.instructions.len() <= circuit.instructions.len(),
+            "{:?}",
+            path
+        );
+        assert_eq!(optimize::peephole(&optimized), optimized, "{:?}", path);
+        let reparsed = Program::from_ast(optimized.to_ast())
+            .and_then(|p| p.flatten())
+            .unwrap();
+        assert_eq!(
+            reparsed.instructions.len(),
+            optimized.instructions.len(),
+            "{:?}",
+            path
+        );
+
+        let commuted = optimize::commutative_cancellation(&circuit);
+        assert!(
+            commuted.instructions.len() <= optimized.instructions.len(),
+            "{:?}",
+            path
+        );
+        assert_eq!(
+            optimize::commutative_cancellation(&commuted),
+            commuted,
+            "{:?}",
+            path
+        );
+
+        let resynthesized = optimize::resynthesize(&circuit, &ResynthesisOptions::default());
+        assert!(
+            resynthesized.instructions.len() <= circuit.instructions.len(),
+            "{:?}",
+            path
+        );
+        let reparsed = Program::from_ast(resynthesized.to_ast())
+            .and_then(|p| p.flatten())
+            .unwrap();
+        assert_eq!(
+            reparsed.instructions.len(),
+            resynthesized.instructions.len(),
+            "{:?}",
+            path
+        );
+
+        let blocks = optimize::resynthesize_blocks(&circuit, &ResynthesisOptions::default());
+        let cx = |circuit| {
+            Stats::from_circuit(circuit)
+                .unwrap()
+                .inlined_counts
+                .get("CX")
+                .cloned()
+                .unwrap_or(0)
+        };
+        assert!(cx(&blocks) <= cx(&circuit), "{:?}", path);
+    }
+}
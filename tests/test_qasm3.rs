@@ -0,0 +1,102 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use std::fs;
+use qasm::qasm3::{self, Expression, Iterable, Modifier, Operand, Statement, Type};
+
+fn without_gate_defs(statements: Vec<Statement>) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .filter(|s| !matches!(*s, Statement::GateDef(..)))
+        .collect()
+}
+
+#[test]
+fn parses_qasm3_examples() {
+    for entry in glob("tests/qasm3/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        if let Err(e) = qasm3::parse_file(&path) {
+            panic!("Error in {:?}: {}", path, e);
+        }
+    }
+}
+
+#[test]
+fn parses_control_flow() {
+    let statements = without_gate_defs(qasm3::parse_file("tests/qasm3/teleport.qasm").unwrap());
+    let q2 = Operand::Index("q".to_string(), Expression::Int(2));
+
+    assert_eq!(
+        statements[0],
+        Statement::Input(Type::Angle(Some(Expression::Int(32))), "theta".to_string())
+    );
+    assert_eq!(
+        statements[9],
+        Statement::Measure(
+            Operand::Index("q".to_string(), Expression::Int(0)),
+            Some(Operand::Index("c".to_string(), Expression::Int(0)))
+        )
+    );
+    assert_eq!(
+        statements[12],
+        Statement::If(
+            Expression::Binary(
+                qasm3::BinaryOp::Equals,
+                Box::new(Expression::Index("c".to_string(), Box::new(Expression::Int(1)))),
+                Box::new(Expression::Int(1))
+            ),
+            vec![Statement::Gate(vec![], "x".to_string(), vec![], vec![q2.clone()])],
+            vec![Statement::Gate(vec![], "id".to_string(), vec![], vec![q2])]
+        )
+    );
+}
+
+#[test]
+fn parses_loops_and_modifiers() {
+    let statements = without_gate_defs(qasm3::parse_file("tests/qasm3/loops.qasm").unwrap());
+
+    match statements[7] {
+        Statement::For(Type::Int(None), ref id, Iterable::Set(ref set), ref body) => {
+            assert_eq!(id, "j");
+            assert_eq!(set, &vec![Expression::Int(1), Expression::Int(3)]);
+            match body[0] {
+                Statement::Gate(ref modifiers, ..) => {
+                    assert_eq!(modifiers, &vec![Modifier::NegCtrl(Expression::Int(2))])
+                }
+                ref other => panic!("Expected a gate, got {:?}", other),
+            }
+        }
+        ref other => panic!("Expected a for loop, got {:?}", other),
+    }
+    assert!(matches!(statements[8], Statement::While(..)));
+    assert_eq!(statements[9], Statement::Barrier(vec![]));
+}
+
+#[test]
+fn rejects_other_versions() {
+    assert_eq!(
+        qasm3::parse("OPENQASM 2.0; qreg q[1];"),
+        Err(qasm::Error::UnsupportedVersion)
+    );
+}
+
+#[test]
+fn translates_qasm2_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let source = fs::read_to_string(&path).unwrap();
+        let source = qasm::process(&source, path.parent().unwrap());
+        let ast = qasm::parse_tokens(qasm::Lexer::new(&source)).unwrap();
+        let translated = match qasm3::translate(&ast) {
+            Ok(translated) => translated,
+            Err(e) => panic!("Error translating {:?}: {}", path, e),
+        };
+
+        // Gates from qelib1.inc come from stdgates.inc instead
+        assert!(!translated.contains("gate u3("), "{}", translated);
+        if let Err(e) = qasm3::parse(&qasm3::process(&translated, path.parent().unwrap()).unwrap()) {
+            panic!("Error parsing the translation of {:?}: {}\n{}", path, e, translated);
+        }
+    }
+}
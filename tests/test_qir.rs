@@ -0,0 +1,38 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::Error;
+
+#[test]
+fn emits_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let ir = match qasm::qir::emit(&circuit) {
+            Ok(ir) => ir,
+            // The base profile can't express these
+            Err(Error::Unsupported(ref construct))
+                if construct == "if" || construct == "operation after measurement" =>
+            {
+                continue
+            }
+            Err(e) => panic!("Error emitting {:?}: {}", path, e),
+        };
+
+        // Every function that's called is declared
+        for line in ir.lines().filter(|l| l.starts_with("  call void @")) {
+            let function = &line["  call void ".len()..line.find('(').unwrap()];
+            assert!(
+                ir.contains(&format!("declare void {}(", function)),
+                "{:?}: {}",
+                path,
+                function
+            );
+        }
+        assert!(ir.contains(&format!(
+            "\"required_num_qubits\"=\"{}\"",
+            circuit.num_qubits()
+        )));
+    }
+}
@@ -0,0 +1,28 @@
+#![cfg(feature = "qobj")]
+
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::Program;
+
+#[test]
+fn round_trips_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let json = match qasm::qobj::emit(&circuit) {
+            Ok(json) => json,
+            Err(e) => panic!("Error emitting {:?}: {}", path, e),
+        };
+
+        let nodes = match qasm::qobj::parse(&json) {
+            Ok(nodes) => nodes,
+            Err(e) => panic!("Error parsing {:?}: {}\n{}", path, e, json),
+        };
+        let parsed = Program::from_ast(nodes).and_then(|p| p.flatten()).unwrap();
+        assert_eq!(parsed.qregs, circuit.qregs, "{:?}", path);
+        assert_eq!(parsed.cregs, circuit.cregs, "{:?}", path);
+        assert_eq!(qasm::qobj::emit(&parsed).unwrap(), json, "{:?}", path);
+    }
+}
@@ -0,0 +1,104 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+
+#[test]
+fn emits_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let quil = match qasm::quil::emit(&circuit) {
+            Ok(quil) => quil,
+            Err(e) => panic!("Error emitting {:?}: {}", path, e),
+        };
+
+        let measures = quil.lines().filter(|l| l.starts_with("MEASURE")).count();
+        let expected = circuit
+            .instructions
+            .iter()
+            .filter(|i| matches!(i.operation, qasm::Operation::Measure(..)))
+            .count();
+        assert_eq!(measures, expected, "{:?}\n{}", path, quil);
+        if circuit.num_clbits() > 0 {
+            assert!(quil.starts_with("DECLARE ro BIT["));
+        }
+    }
+}
+
+#[test]
+fn emits_user_gates() {
+    let circuit = qasm::parse_file("tests/source/qec.qasm")
+        .and_then(|p| p.flatten())
+        .unwrap();
+    let quil = qasm::quil::emit(&circuit).unwrap();
+
+    assert!(quil.contains("DEFCIRCUIT syndrome d1 d2 d3 a1 a2:\n    CNOT d1 a1\n"));
+    assert!(quil.contains("MEASURE 3 ro[3]\nMEASURE 4 ro[4]\n"));
+    assert!(quil.contains("JUMP-UNLESS @skip0 ro[3]\nJUMP-WHEN @skip0 ro[4]\nX 0\nLABEL @skip0\n"));
+}
+
+#[test]
+fn emits_wide_conditions() {
+    let source = "OPENQASM 2.0;
+        qreg q[1];
+        creg c[40];
+        measure q[0] -> c[35];
+        if(c==1) U(pi, 0, 0) q[0];";
+    let circuit = qasm::parse_str(source, std::path::Path::new(""))
+        .and_then(|p| p.flatten())
+        .unwrap();
+    let quil = qasm::quil::emit(&circuit).unwrap();
+
+    // The bits past the width of the value are zero
+    assert!(quil.contains("JUMP-UNLESS @skip0 ro[0]\nJUMP-WHEN @skip0 ro[1]\n"));
+    assert!(quil.contains("JUMP-WHEN @skip0 ro[31]\n"));
+    assert!(quil.contains("JUMP-WHEN @skip0 ro[39]\n"));
+    let jumps = quil
+        .lines()
+        .filter(|l| l.starts_with("JUMP-WHEN @skip0"))
+        .count();
+    assert_eq!(jumps, 39);
+
+    let round_trip = qasm::quil::parse(&quil)
+        .and_then(qasm::Program::from_ast)
+        .and_then(|p| p.flatten())
+        .unwrap();
+    assert_eq!(qasm::quil::emit(&round_trip).unwrap(), quil);
+}
+
+#[test]
+fn round_trips_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let circuit = qasm::parse_file(&path).and_then(|p| p.flatten()).unwrap();
+        let quil = qasm::quil::emit(&circuit).unwrap();
+
+        let parsed = qasm::quil::parse(&quil);
+        if let Some(line) = quil.lines().find(|l| l.starts_with("DEFGATE")) {
+            // Gates defined by a matrix can't be turned back into OpenQASM
+            assert_eq!(parsed, Err(qasm::Error::Unsupported("DEFGATE".to_string())), "{}", line);
+            continue;
+        }
+        if circuit.cregs.len() > 1 && circuit.instructions.iter().any(|i| i.condition.is_some()) {
+            // The registers are merged into `ro`, so conditions test part of it
+            assert_eq!(
+                parsed,
+                Err(qasm::Error::Unsupported("JUMP-WHEN on part of ro".to_string()))
+            );
+            continue;
+        }
+
+        let round_trip = match parsed.and_then(qasm::Program::from_ast).and_then(|p| p.flatten()) {
+            Ok(round_trip) => round_trip,
+            Err(e) => panic!("Error parsing the Quil for {:?}: {}\n{}", path, e, quil),
+        };
+        assert_eq!(round_trip.num_clbits(), circuit.num_clbits());
+        assert_eq!(
+            qasm::quil::emit(&round_trip).unwrap(),
+            quil,
+            "{:?}",
+            path
+        );
+    }
+}
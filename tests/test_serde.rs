@@ -0,0 +1,90 @@
+#![cfg(feature = "serde")]
+
+extern crate glob;
+extern crate qasm;
+extern crate serde_json;
+
+use glob::glob;
+use qasm::{Argument, AstNode, Error, Token};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+#[test]
+fn round_trips_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let source = fs::read_to_string(&path).unwrap();
+        let processed = qasm::process(&source, path.parent().unwrap());
+        let nodes = qasm::parse_tokens(qasm::lex(&processed)).unwrap();
+
+        let json = serde_json::to_string(&nodes).unwrap();
+        let read: Vec<AstNode> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, nodes, "{:?}", path);
+    }
+}
+
+#[test]
+fn serializes_named_fields() {
+    let node = AstNode::If(
+        "c".to_string(),
+        1,
+        Box::new(AstNode::CX(
+            Argument::Qubit("q".to_string(), 0),
+            Argument::Register("r".to_string()),
+        )),
+    );
+
+    assert_eq!(
+        serde_json::to_value(&node).unwrap(),
+        serde_json::json!({
+            "type": "If",
+            "register": "c",
+            "value": 1,
+            "statement": {
+                "type": "CX",
+                "control": { "type": "Qubit", "register": "q", "index": 0 },
+                "target": { "type": "Register", "register": "r" },
+            },
+        })
+    );
+}
+
+#[test]
+fn round_trips_tokens() {
+    let tokens = qasm::lex(&qasm::process(
+        "OPENQASM 2.0;\nqreg q[2];\nU(pi/2, 0, 1.5) q;",
+        Path::new(""),
+    ));
+    let json = serde_json::to_string(&tokens).unwrap();
+
+    assert!(json.contains(r#"{"type":"Id","value":"q"}"#));
+    assert_eq!(serde_json::from_str::<Vec<Token>>(&json).unwrap(), tokens);
+}
+
+#[test]
+fn round_trips_errors() {
+    let errors = vec![
+        Error::MissingSemicolon,
+        Error::IoError(ErrorKind::NotFound),
+        Error::IndexOutOfRange("q".to_string(), 3),
+        Error::Unsupported("opaque g".to_string()),
+    ];
+    let json = serde_json::to_string(&errors).unwrap();
+
+    assert!(json.contains(r#"{"type":"IoError","kind":"NotFound"}"#));
+    assert!(json.contains(r#"{"type":"IndexOutOfRange","name":"q","index":3}"#));
+    assert_eq!(serde_json::from_str::<Vec<Error>>(&json).unwrap(), errors);
+
+    // IO error kinds have fixed names, and the kinds without one become `Other`
+    let json = serde_json::to_string(&Error::IoError(ErrorKind::IsADirectory)).unwrap();
+    assert_eq!(json, r#"{"type":"IoError","kind":"Other"}"#);
+    assert_eq!(
+        serde_json::from_str::<Error>(&json).unwrap(),
+        Error::IoError(ErrorKind::Other)
+    );
+    assert_eq!(
+        serde_json::from_str::<Error>(r#"{"type":"IoError","kind":"StorageFull"}"#).unwrap(),
+        Error::IoError(ErrorKind::Other)
+    );
+}
@@ -0,0 +1,20 @@
+extern crate glob;
+extern crate qasm;
+
+use glob::glob;
+use qasm::Stats;
+
+#[test]
+fn measures_examples() {
+    for entry in glob("tests/source/*.qasm").expect("Failed to read glob pattern") {
+        let path = entry.unwrap();
+        let program = qasm::parse_file(&path).unwrap();
+        let stats = Stats::from_program(&program).unwrap();
+
+        assert_eq!(stats.critical_path.len(), stats.depth, "{:?}", path);
+        assert!(stats.two_qubit_depth <= stats.depth, "{:?}", path);
+        assert!(stats.depth <= stats.operations, "{:?}", path);
+        assert!(stats.used_qubits <= stats.qubits, "{:?}", path);
+        assert!(stats.used_clbits <= stats.clbits, "{:?}", path);
+    }
+}